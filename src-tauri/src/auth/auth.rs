@@ -3,13 +3,17 @@ use generic_array::GenericArray;
 use typenum::U32;
 use rand::thread_rng;
 
-use crate::crypto::{encrypt_to_base64, decrypt_from_base64};
+use crate::crypto::{encrypt_to_base64, decrypt_from_base64, generate_key};
 use crate::models::User;
+use crate::words::encode_words;
 use super::database::Database;
+use super::duress::{duress_target, wipe_data_key};
+use super::keys::wrap_key;
+use super::recovery::store_recovery_secret;
 
 impl Database {
     pub fn login(&self, username: &str, password: &str, master_key: &str) -> Result<User, String> {
-        let (user_id, username_encrypted, username_nonce, password_hash, master_key_hash) = {
+        let (user_id, username_encrypted, username_nonce, password_hash, master_key_hash, duress) = {
             let conn = self.conn.lock().unwrap();
             let mut stmt = conn.prepare("SELECT id, username_encrypted, username_nonce, password_hash, master_key_hash FROM users").map_err(|e| e.to_string())?;
             let all_users: Vec<(i32, String, String, String, String)> = stmt.query_map([], |row| {
@@ -24,20 +28,32 @@ impl Database {
             .filter_map(|r| r.ok())
             .collect();
 
+            // Every account is tried, also after a match, so the time login
+            // takes does not tell which one matched, a decoy profile included.
             let mut found = None;
             for (id, enc_user, nonce, pwd_hash, mkey_hash) in all_users {
                 let salt = extract_salt_from_hash(&mkey_hash)?;
                 let key = derive_key(master_key, &salt)?;
                 
                 if let Ok(decrypted_username) = decrypt_from_base64(&enc_user, &nonce, &key) {
-                    if decrypted_username == username {
-                        found = Some((id, enc_user, nonce, pwd_hash, mkey_hash));
-                        break;
+                    if decrypted_username == username && found.is_none() {
+                        found = Some((id, enc_user, nonce, pwd_hash, mkey_hash, key));
                     }
                 }
             }
 
-            found.ok_or("User not found".to_string())?
+            let (id, enc_user, nonce, pwd_hash, mkey_hash, key) = found.ok_or("User not found".to_string())?;
+            // A decoy profile takes the password of the account it stands in for.
+            let duress = duress_target(&conn, id, &key)?;
+            let pwd_hash = match duress {
+                Some(target) => conn.query_row(
+                    "SELECT password_hash FROM users WHERE id = ?",
+                    [target.user_id],
+                    |row| row.get(0),
+                ).map_err(|e| e.to_string())?,
+                None => pwd_hash,
+            };
+            (id, enc_user, nonce, pwd_hash, mkey_hash, duress)
         };
 
         let parsed_hash = PasswordHash::new(&password_hash).map_err(|e| e.to_string())?;
@@ -46,6 +62,13 @@ impl Database {
         let parsed_master_hash = PasswordHash::new(&master_key_hash).map_err(|e| e.to_string())?;
         Argon2::default().verify_password(master_key.as_bytes(), &parsed_master_hash).map_err(|_| "Invalid master key".to_string())?;
 
+        // The decoy shows the same settings as the real account.
+        let settings_id = duress.map_or(user_id, |target| target.user_id);
+        if let Some(target) = duress.filter(|target| target.wipe_data_key) {
+            wipe_data_key(&self.conn.lock().unwrap(), target.user_id)?;
+            self.clear_session(target.user_id);
+        }
+
         Ok(User {
             id: user_id,
             username: username.to_string(),
@@ -54,11 +77,15 @@ impl Database {
             password_hash,
             master_key_hash,
             avatar: None,
+            two_factor_enabled: self.two_factor_enabled(settings_id)?,
+            secret_key_enabled: self.secret_key_enabled(settings_id)?,
         })
     }
 
-    pub fn register(&self, username: &str, password: &str, master_key: &str) -> Result<User, String> {
-        let conn = self.conn.lock().unwrap();
+    /// Creates an account. With `create_recovery_key`, its recovery key is
+    /// stored in the same transaction and returned as words once committed.
+    pub fn register(&self, username: &str, password: &str, master_key: &str, create_recovery_key: bool) -> Result<(User, Option<String>), String> {
+        let mut conn = self.conn.lock().unwrap();
 
         let count: i32 = conn.query_row("SELECT COUNT(*) FROM users", [], |row| row.get(0)).unwrap_or(0);
         if count > 0 {
@@ -88,23 +115,40 @@ impl Database {
         let salt = extract_salt_from_hash(&master_key_hash)?;
         let key = derive_key(master_key, &salt)?;
         let (username_encrypted, username_nonce) = encrypt_to_base64(username, &key).map_err(|e| e.to_string())?;
+        // Data is encrypted with a random key wrapped by the master key, so a
+        // recovery key can wrap the same key and the master key can change.
+        let data_key = generate_key();
+        let (data_key_encrypted, data_key_nonce) = wrap_key(&data_key, &key)?;
 
         let salt = SaltString::generate(&mut thread_rng());
         let password_hash = argon2.hash_password(password.as_bytes(), &salt).map_err(|e| e.to_string())?.to_string();
         
-        conn.execute("INSERT INTO users (username_encrypted, username_nonce, password_hash, master_key_hash) VALUES (?, ?, ?, ?)", [&username_encrypted, &username_nonce, &password_hash, &master_key_hash]).map_err(|e| e.to_string())?;
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        tx.execute(
+            "INSERT INTO users (username_encrypted, username_nonce, password_hash, master_key_hash, data_key_encrypted, data_key_nonce) VALUES (?, ?, ?, ?, ?, ?)",
+            [&username_encrypted, &username_nonce, &password_hash, &master_key_hash, &data_key_encrypted, &data_key_nonce],
+        ).map_err(|e| e.to_string())?;
         
-        let user_id = conn.last_insert_rowid() as i32;
+        let user_id = tx.last_insert_rowid() as i32;
+        let recovery_secret = if create_recovery_key {
+            Some(store_recovery_secret(&tx, user_id, username, &data_key)?)
+        } else {
+            None
+        };
+        tx.commit().map_err(|e| e.to_string())?;
 
-        Ok(User { 
+        let user = User { 
             id: user_id, 
             username: username.to_string(),
             username_encrypted: Some(username_encrypted),
             username_nonce: Some(username_nonce),
             password_hash, 
             master_key_hash, 
-            avatar: None 
-        })
+            avatar: None,
+            two_factor_enabled: false,
+            secret_key_enabled: false,
+        };
+        Ok((user, recovery_secret.map(|secret| encode_words(&secret))))
     }
 
     pub fn recover_password(&self, username: &str, master_key: &str, new_password: &str) -> Result<(), String> {
@@ -1,13 +1,27 @@
 use rusqlite::Connection;
+use std::path::PathBuf;
 use std::sync::Mutex;
 use generic_array::GenericArray;
 use typenum::U32;
 
+use super::quick_unlock::QuickUnlock;
 use super::schema::create_tables;
+use super::secret_key::move_secret_keys;
+use crate::db::vault_lock::VaultUnlock;
+use crate::search::SearchIndex;
 
 pub struct Database {
     pub conn: Mutex<Connection>,
     pub encryption_keys: Mutex<std::collections::HashMap<i32, GenericArray<u8, U32>>>,
+    pub data_dir: PathBuf,
+    /// Kept on this device only, unlike `data_dir`, which may be synced.
+    pub local_dir: PathBuf,
+    pub search_indexes: Mutex<std::collections::HashMap<i32, SearchIndex>>,
+    pub quick_unlocks: Mutex<std::collections::HashMap<i32, QuickUnlock>>,
+    pub vault_unlocks: Mutex<std::collections::HashMap<String, VaultUnlock>>,
+    /// The user whose session the window shows, for requests that carry no
+    /// user id, like those for `vaultimg://` images.
+    pub active_user: Mutex<Option<i32>>,
 }
 
 impl Database {
@@ -19,9 +33,19 @@ impl Database {
         
         create_tables(&conn)?;
 
+        let local_dir = dirs::data_local_dir().unwrap().join("n-cryption");
+        std::fs::create_dir_all(&local_dir).unwrap();
+        move_secret_keys(&data_dir, &local_dir);
+
         Ok(Database {
             conn: Mutex::new(conn),
             encryption_keys: Mutex::new(std::collections::HashMap::new()),
+            data_dir,
+            local_dir,
+            search_indexes: Mutex::new(std::collections::HashMap::new()),
+            quick_unlocks: Mutex::new(std::collections::HashMap::new()),
+            vault_unlocks: Mutex::new(std::collections::HashMap::new()),
+            active_user: Mutex::new(None),
         })
     }
 }
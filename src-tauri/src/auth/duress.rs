@@ -0,0 +1,224 @@
+use argon2::{Argon2, PasswordHasher, password_hash::SaltString};
+use base64::{Engine as _, engine::general_purpose::STANDARD};
+use generic_array::GenericArray;
+use rand::thread_rng;
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use typenum::U32;
+
+use crate::crypto::{decrypt_from_base64, encrypt_to_base64, generate_key, key_from_bytes};
+use crate::db::attachments::remove_attachment_files;
+use super::database::Database;
+use super::keys::{master_key_wrapping_key, unlock_with_master_key, unwrap_data_key, wrap_key};
+use super::user::delete_user_rows;
+
+/// What a decoy profile stands in for, stored encrypted with the key derived
+/// from the duress master key so only logging in with it reveals the link.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub(crate) struct DuressTarget {
+    pub user_id: i32,
+    pub wipe_data_key: bool,
+}
+
+/// The decoy profile of an account, stored encrypted with the account's data
+/// key. The decoy's data key is kept so the account's two-factor settings can
+/// be copied onto it.
+#[derive(Serialize, Deserialize)]
+struct DecoyLink {
+    user_id: i32,
+    data_key: String,
+}
+
+struct Decoy {
+    user_id: i32,
+    data_key: GenericArray<u8, U32>,
+}
+
+impl Database {
+    /// Adds a duress master key to the account. Logging in with it and the
+    /// account's username and password opens a decoy profile with its own,
+    /// initially empty, vaults instead, and with `wipe_data_key` also makes
+    /// the real data unreadable.
+    pub fn set_duress_master_key(&self, user_id: i32, master_key: &str, duress_master_key: &str, wipe_data_key: bool) -> Result<(), String> {
+        if duress_master_key == master_key {
+            return Err("The duress master key must differ from the master key".to_string());
+        }
+
+        let mut conn = self.conn.lock().unwrap();
+        let (wrapping_key, username) = unlock_with_master_key(&conn, user_id, master_key)?;
+        let wrapping_key = self.data_key_wrapping_key(&conn, user_id, wrapping_key)?;
+        let data_key = unwrap_data_key(&conn, user_id, &wrapping_key)?;
+        if decoy_link(&conn, user_id)?.is_some() {
+            return Err("The account already has a duress master key".to_string());
+        }
+
+        let password_hash: String = conn.query_row(
+            "SELECT password_hash FROM users WHERE id = ?",
+            [user_id],
+            |row| row.get(0),
+        ).map_err(|e| e.to_string())?;
+
+        let master_salt = SaltString::generate(&mut thread_rng());
+        let decoy_master_key_hash = Argon2::default().hash_password(duress_master_key.as_bytes(), &master_salt).map_err(|e| e.to_string())?.to_string();
+        let decoy_wrapping_key = master_key_wrapping_key(duress_master_key, &decoy_master_key_hash)?;
+        let (username_encrypted, username_nonce) = encrypt_to_base64(&username, &decoy_wrapping_key).map_err(|e| e.to_string())?;
+        let decoy_data_key = generate_key();
+        let (data_key_encrypted, data_key_nonce) = wrap_key(&decoy_data_key, &decoy_wrapping_key)?;
+        let target = serde_json::to_string(&DuressTarget { user_id, wipe_data_key }).map_err(|e| e.to_string())?;
+        let (target_encrypted, target_nonce) = encrypt_to_base64(&target, &decoy_wrapping_key).map_err(|e| e.to_string())?;
+
+        // The decoy keeps a copy of the password hash only to fill the column;
+        // login checks the real account's, so a changed password carries over.
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        tx.execute(
+            "INSERT INTO users (username_encrypted, username_nonce, password_hash, master_key_hash, data_key_encrypted, data_key_nonce, duress_target_encrypted, duress_target_nonce)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+            rusqlite::params![username_encrypted, username_nonce, password_hash, decoy_master_key_hash, data_key_encrypted, data_key_nonce, target_encrypted, target_nonce],
+        ).map_err(|e| e.to_string())?;
+        let decoy_id = tx.last_insert_rowid() as i32;
+
+        let link = serde_json::to_string(&DecoyLink { user_id: decoy_id, data_key: STANDARD.encode(decoy_data_key) })
+            .map_err(|e| e.to_string())?;
+        let (decoy_encrypted, decoy_nonce) = encrypt_to_base64(&link, &data_key).map_err(|e| e.to_string())?;
+        tx.execute(
+            "UPDATE users SET duress_decoy_encrypted = ?, duress_decoy_nonce = ? WHERE id = ?",
+            rusqlite::params![decoy_encrypted, decoy_nonce, user_id],
+        ).map_err(|e| e.to_string())?;
+        // Unlocking the decoy asks for the same second factor as the account.
+        mirror_two_factor(&tx, user_id, &data_key)?;
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    /// Removes the duress master key together with the decoy profile and
+    /// everything in it.
+    pub fn remove_duress_master_key(&self, user_id: i32, master_key: &str) -> Result<(), String> {
+        let mut conn = self.conn.lock().unwrap();
+        let decoy_id = self.linked_decoy(&conn, user_id, master_key)?
+            .ok_or("The account has no duress master key")?;
+
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let attachments = delete_user_rows(&tx, decoy_id)?;
+        tx.execute(
+            "UPDATE users SET duress_decoy_encrypted = NULL, duress_decoy_nonce = NULL WHERE id = ?",
+            [user_id],
+        ).map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())?;
+
+        remove_attachment_files(&self.data_dir, &attachments);
+        self.clear_session(decoy_id);
+        Ok(())
+    }
+
+    pub fn duress_master_key_enabled(&self, user_id: i32) -> Result<bool, String> {
+        let conn = self.conn.lock().unwrap();
+        Ok(decoy_link(&conn, user_id)?.is_some())
+    }
+
+    /// The id of the decoy profile of the account, whose link is encrypted
+    /// with the data key.
+    pub(crate) fn linked_decoy(&self, conn: &Connection, user_id: i32, master_key: &str) -> Result<Option<i32>, String> {
+        if decoy_link(conn, user_id)?.is_none() {
+            return Ok(None);
+        }
+        let (wrapping_key, _) = unlock_with_master_key(conn, user_id, master_key)?;
+        let wrapping_key = self.data_key_wrapping_key(conn, user_id, wrapping_key)?;
+        let data_key = unwrap_data_key(conn, user_id, &wrapping_key)?;
+        Ok(linked_decoy_key(conn, user_id, &data_key)?.map(|decoy| decoy.user_id))
+    }
+}
+
+/// The account a row logged into with `key` is a decoy for, if it is one.
+pub(crate) fn duress_target(conn: &Connection, user_id: i32, key: &GenericArray<u8, U32>) -> Result<Option<DuressTarget>, String> {
+    let row: Option<(Option<String>, Option<String>)> = conn.query_row(
+        "SELECT duress_target_encrypted, duress_target_nonce FROM users WHERE id = ?",
+        [user_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    ).optional().map_err(|e| e.to_string())?;
+
+    match row {
+        Some((Some(encrypted), Some(nonce))) => {
+            let target = decrypt_from_base64(&encrypted, &nonce, key).map_err(|e| e.to_string())?;
+            serde_json::from_str(&target).map(Some).map_err(|e| e.to_string())
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Overwrites every wrapped copy of the user's data key with one of a random
+/// key, so neither the master key nor a recovery key opens the data again.
+/// The rows look the same as before.
+pub(crate) fn wipe_data_key(conn: &Connection, user_id: i32) -> Result<(), String> {
+    let (data_key_encrypted, data_key_nonce) = wrap_key(&generate_key(), &generate_key())?;
+    conn.execute(
+        "UPDATE users SET data_key_encrypted = ?, data_key_nonce = ? WHERE id = ?",
+        rusqlite::params![data_key_encrypted, data_key_nonce, user_id],
+    ).map_err(|e| e.to_string())?;
+
+    let (recovery_encrypted, recovery_nonce) = wrap_key(&generate_key(), &generate_key())?;
+    conn.execute(
+        "UPDATE users SET recovery_data_key_encrypted = ?, recovery_data_key_nonce = ? WHERE id = ? AND recovery_data_key_encrypted IS NOT NULL",
+        rusqlite::params![recovery_encrypted, recovery_nonce, user_id],
+    ).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Copies the account's two-factor secret and backup codes onto its decoy
+/// profile, if it has one, replacing what the decoy had. Called whenever they
+/// change, so unlocking the decoy asks for the same codes.
+pub(crate) fn mirror_two_factor(conn: &Connection, user_id: i32, key: &GenericArray<u8, U32>) -> Result<(), String> {
+    let Decoy { user_id: decoy_id, data_key: decoy_key } = match linked_decoy_key(conn, user_id, key)? {
+        Some(decoy) => decoy,
+        None => return Ok(()),
+    };
+    conn.execute("DELETE FROM user_two_factor WHERE user_id = ?", [decoy_id])
+        .map_err(|e| e.to_string())?;
+    conn.execute("DELETE FROM two_factor_backup_codes WHERE user_id = ?", [decoy_id])
+        .map_err(|e| e.to_string())?;
+
+    let row: Option<(String, String, bool, Option<i64>, i64)> = conn.query_row(
+        "SELECT secret_encrypted, secret_nonce, enabled, last_counter, created_at FROM user_two_factor WHERE user_id = ?",
+        [user_id],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?)),
+    ).optional().map_err(|e| e.to_string())?;
+    let (secret_encrypted, secret_nonce, enabled, last_counter, created_at) = match row {
+        Some(row) => row,
+        None => return Ok(()),
+    };
+    let uri = decrypt_from_base64(&secret_encrypted, &secret_nonce, key).map_err(|e| e.to_string())?;
+    let (secret_encrypted, secret_nonce) = encrypt_to_base64(&uri, &decoy_key).map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT INTO user_two_factor (user_id, secret_encrypted, secret_nonce, enabled, last_counter, created_at) VALUES (?, ?, ?, ?, ?, ?)",
+        rusqlite::params![decoy_id, secret_encrypted, secret_nonce, enabled, last_counter, created_at],
+    ).map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT INTO two_factor_backup_codes (user_id, code_hash, used_at) SELECT ?, code_hash, used_at FROM two_factor_backup_codes WHERE user_id = ?",
+        [decoy_id, user_id],
+    ).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// The id and data key of the account's decoy profile, read with the
+/// account's data key.
+fn linked_decoy_key(conn: &Connection, user_id: i32, key: &GenericArray<u8, U32>) -> Result<Option<Decoy>, String> {
+    let (encrypted, nonce) = match decoy_link(conn, user_id)? {
+        Some(link) => link,
+        None => return Ok(None),
+    };
+    let link = decrypt_from_base64(&encrypted, &nonce, key).map_err(|e| e.to_string())?;
+    let link: DecoyLink = serde_json::from_str(&link).map_err(|_| "Invalid duress link".to_string())?;
+    let data_key = STANDARD.decode(&link.data_key).map_err(|_| "Invalid duress link".to_string())?;
+    Ok(Some(Decoy {
+        user_id: link.user_id,
+        data_key: key_from_bytes(&data_key).map_err(|e| e.to_string())?,
+    }))
+}
+
+fn decoy_link(conn: &Connection, user_id: i32) -> Result<Option<(String, String)>, String> {
+    let (encrypted, nonce): (Option<String>, Option<String>) = conn.query_row(
+        "SELECT duress_decoy_encrypted, duress_decoy_nonce FROM users WHERE id = ?",
+        [user_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    ).map_err(|e| e.to_string())?;
+    Ok(encrypted.zip(nonce))
+}
@@ -0,0 +1,55 @@
+use generic_array::GenericArray;
+use rusqlite::Connection;
+use typenum::U32;
+
+use crate::crypto::{decrypt_bytes_from_base64, decrypt_from_base64, derive_encryption_key, encrypt_bytes_to_base64, key_from_bytes};
+
+/// Derives the key wrapping the user's data key from `master_key`, salted
+/// with the salt of the stored master key hash.
+pub(crate) fn master_key_wrapping_key(master_key: &str, master_key_hash: &str) -> Result<GenericArray<u8, U32>, String> {
+    use argon2::PasswordHash;
+    let parsed_hash = PasswordHash::new(master_key_hash).map_err(|e| e.to_string())?;
+    let salt = parsed_hash.salt.ok_or("Salt not found in hash".to_string())?;
+    derive_encryption_key(master_key, salt.as_str().as_bytes()).map_err(|e| e.to_string())
+}
+
+/// Checks `master_key` by decrypting the username with the key derived from
+/// it, and returns both.
+pub(crate) fn unlock_with_master_key(conn: &Connection, user_id: i32, master_key: &str) -> Result<(GenericArray<u8, U32>, String), String> {
+    let (username_encrypted, username_nonce, master_key_hash): (String, String, String) = conn.query_row(
+        "SELECT username_encrypted, username_nonce, master_key_hash FROM users WHERE id = ?",
+        [user_id],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    ).map_err(|e| e.to_string())?;
+
+    let wrapping_key = master_key_wrapping_key(master_key, &master_key_hash)?;
+    let username = decrypt_from_base64(&username_encrypted, &username_nonce, &wrapping_key)
+        .map_err(|_| "Invalid master key".to_string())?;
+    Ok((wrapping_key, username))
+}
+
+/// The key the user's data is encrypted with, unwrapped with the key derived
+/// from their master key. Accounts from before data keys were wrapped have
+/// none stored and encrypt their data with that derived key itself.
+pub(crate) fn unwrap_data_key(conn: &Connection, user_id: i32, wrapping_key: &GenericArray<u8, U32>) -> Result<GenericArray<u8, U32>, String> {
+    let (encrypted, nonce): (Option<String>, Option<String>) = conn.query_row(
+        "SELECT data_key_encrypted, data_key_nonce FROM users WHERE id = ?",
+        [user_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    ).map_err(|e| e.to_string())?;
+
+    match (encrypted, nonce) {
+        (Some(encrypted), Some(nonce)) => unwrap_key(&encrypted, &nonce, wrapping_key)
+            .map_err(|_| "Invalid master key".to_string()),
+        _ => Ok(*wrapping_key),
+    }
+}
+
+pub(crate) fn wrap_key(key: &GenericArray<u8, U32>, wrapping_key: &GenericArray<u8, U32>) -> Result<(String, String), String> {
+    encrypt_bytes_to_base64(key.as_slice(), wrapping_key).map_err(|e| e.to_string())
+}
+
+pub(crate) fn unwrap_key(encrypted: &str, nonce: &str, wrapping_key: &GenericArray<u8, U32>) -> Result<GenericArray<u8, U32>, String> {
+    let bytes = decrypt_bytes_from_base64(encrypted, nonce, wrapping_key).map_err(|e| e.to_string())?;
+    key_from_bytes(&bytes).map_err(|e| e.to_string())
+}
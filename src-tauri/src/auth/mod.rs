@@ -1,7 +1,14 @@
+#[allow(clippy::module_inception)]
 pub mod auth;
 pub mod database;
+pub mod duress;
+pub mod keys;
+pub mod quick_unlock;
+pub mod recovery;
+pub mod secret_key;
 pub mod schema;
 pub mod session;
+pub mod two_factor;
 pub mod user;
 
 pub use database::Database;
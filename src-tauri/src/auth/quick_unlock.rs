@@ -0,0 +1,107 @@
+use generic_array::GenericArray;
+use rand::{thread_rng, RngCore};
+use typenum::U32;
+
+use crate::crypto::derive_encryption_key;
+use super::database::Database;
+use super::keys::{unwrap_key, wrap_key};
+
+pub const MAX_PIN_ATTEMPTS: u32 = 5;
+const PIN_SALT_LENGTH: usize = 16;
+
+/// The session key wrapped with a key derived from the user's PIN. Only ever
+/// held in memory, so a restart always asks for the master key again.
+pub struct QuickUnlock {
+    salt: [u8; PIN_SALT_LENGTH],
+    key_encrypted: String,
+    key_nonce: String,
+    attempts_left: u32,
+}
+
+impl Database {
+    /// Lets the current session be unlocked again with `pin` after
+    /// `lock_session`, until the app restarts or the user logs out.
+    pub fn enable_quick_unlock(&self, user_id: i32, pin: &str) -> Result<(), String> {
+        validate_pin(pin)?;
+        let key = self.get_encryption_key(user_id)?;
+
+        let mut salt = [0u8; PIN_SALT_LENGTH];
+        thread_rng().fill_bytes(&mut salt);
+        let (key_encrypted, key_nonce) = wrap_key(&key, &pin_key(pin, &salt)?)?;
+
+        self.quick_unlocks.lock().unwrap().insert(user_id, QuickUnlock {
+            salt,
+            key_encrypted,
+            key_nonce,
+            attempts_left: MAX_PIN_ATTEMPTS,
+        });
+        Ok(())
+    }
+
+    pub fn disable_quick_unlock(&self, user_id: i32) {
+        self.quick_unlocks.lock().unwrap().remove(&user_id);
+    }
+
+    pub fn quick_unlock_available(&self, user_id: i32) -> bool {
+        self.quick_unlocks.lock().unwrap().contains_key(&user_id)
+    }
+
+    /// Drops the session key but, unlike `clear_session`, keeps the quick
+    /// unlock so the PIN can bring it back. Unlocked vaults lock, and stay
+    /// locked after the PIN.
+    pub fn lock_session(&self, user_id: i32) {
+        self.encryption_keys.lock().unwrap().remove(&user_id);
+        self.search_indexes.lock().unwrap().remove(&user_id);
+        self.lock_vaults(user_id);
+        self.deactivate_user(user_id);
+    }
+
+    /// Restores the session key locked away by `lock_session`. After
+    /// `MAX_PIN_ATTEMPTS` wrong PINs in a row the quick unlock is forgotten
+    /// and only the master key unlocks.
+    pub fn quick_unlock(&self, user_id: i32, pin: &str) -> Result<(), String> {
+        // The PIN key is derived outside the lock, as it is slow on purpose.
+        let salt = self.quick_unlocks.lock().unwrap()
+            .get(&user_id)
+            .map(|q| q.salt)
+            .ok_or("Quick unlock is not enabled, unlock with the master key")?;
+        let pin_key = pin_key(pin, &salt)?;
+
+        let key = {
+            let mut quick_unlocks = self.quick_unlocks.lock().unwrap();
+            let quick_unlock = quick_unlocks.get_mut(&user_id)
+                .ok_or("Quick unlock is not enabled, unlock with the master key")?;
+            match unwrap_key(&quick_unlock.key_encrypted, &quick_unlock.key_nonce, &pin_key) {
+                Ok(key) => {
+                    quick_unlock.attempts_left = MAX_PIN_ATTEMPTS;
+                    key
+                }
+                Err(_) => {
+                    quick_unlock.attempts_left -= 1;
+                    if quick_unlock.attempts_left == 0 {
+                        quick_unlocks.remove(&user_id);
+                        return Err("Too many wrong PINs, unlock with the master key".to_string());
+                    }
+                    return Err(format!("Wrong PIN, {} attempts left", quick_unlock.attempts_left));
+                }
+            }
+        };
+
+        let conn = self.conn.lock().unwrap();
+        self.build_search_index(&conn, user_id, &key)?;
+        self.encryption_keys.lock().unwrap().insert(user_id, key);
+        *self.active_user.lock().unwrap() = Some(user_id);
+        Ok(())
+    }
+}
+
+fn validate_pin(pin: &str) -> Result<(), String> {
+    if !(4..=12).contains(&pin.len()) || !pin.bytes().all(|b| b.is_ascii_digit()) {
+        return Err("The PIN must be 4 to 12 digits".to_string());
+    }
+    Ok(())
+}
+
+fn pin_key(pin: &str, salt: &[u8]) -> Result<GenericArray<u8, U32>, String> {
+    derive_encryption_key(pin, salt).map_err(|e| e.to_string())
+}
@@ -0,0 +1,170 @@
+use argon2::{Argon2, PasswordHasher, password_hash::SaltString};
+use generic_array::GenericArray;
+use rand::{thread_rng, RngCore};
+use rusqlite::Connection;
+use typenum::U32;
+
+use crate::crypto::{decrypt_from_base64, derive_key_from_secret, encrypt_to_base64};
+use crate::shamir::{self, Share};
+use crate::words::{decode_words, encode_words};
+use super::database::Database;
+use super::keys::{master_key_wrapping_key, unlock_with_master_key, unwrap_data_key, unwrap_key, wrap_key};
+use super::secret_key::remove_secret_key_file;
+
+pub(crate) const RECOVERY_SECRET_LENGTH: usize = 16;
+const RECOVERY_KEY_DOMAIN: &[u8] = b"recovery-key";
+
+impl Database {
+    /// Creates a recovery key for the user, replacing any earlier one, and
+    /// returns it as words to write down. It wraps the same data key the
+    /// master key does, so it can set a new master key if that is forgotten.
+    pub fn create_recovery_key(&self, user_id: i32, master_key: &str) -> Result<String, String> {
+        let conn = self.conn.lock().unwrap();
+        let (wrapping_key, username) = unlock_with_master_key(&conn, user_id, master_key)?;
+        let wrapping_key = self.data_key_wrapping_key(&conn, user_id, wrapping_key)?;
+        let data_key = unwrap_data_key(&conn, user_id, &wrapping_key)?;
+        let secret = store_recovery_secret(&conn, user_id, &username, &data_key)?;
+        Ok(encode_words(&secret))
+    }
+
+    /// Sets a new master key and password on the account of `username` with
+    /// its recovery key. That recovery key stops working; the one returned
+    /// replaces it. A secret key is dropped too, as it may be lost with the
+    /// master key; a new one can be created afterwards.
+    pub fn recover_with_recovery_key(&self, username: &str, recovery_key: &str, new_master_key: &str, new_password: &str) -> Result<String, String> {
+        let secret = decode_words(recovery_key)?;
+        let secret = self.recover_with_secret(username, &secret, new_master_key, new_password)?;
+        Ok(encode_words(&secret))
+    }
+
+    /// Replaces the user's recovery key with one split into `count` shares,
+    /// any `threshold` of which recover the account, so no single holder can.
+    /// A plain recovery key made before stops working.
+    pub fn split_recovery_key(&self, user_id: i32, master_key: &str, threshold: u8, count: u8) -> Result<Vec<String>, String> {
+        shamir::check_parameters(threshold, count)?;
+
+        let conn = self.conn.lock().unwrap();
+        let (wrapping_key, username) = unlock_with_master_key(&conn, user_id, master_key)?;
+        let wrapping_key = self.data_key_wrapping_key(&conn, user_id, wrapping_key)?;
+        let data_key = unwrap_data_key(&conn, user_id, &wrapping_key)?;
+        let secret = store_recovery_secret(&conn, user_id, &username, &data_key)?;
+        encode_shares(&secret, threshold, count)
+    }
+
+    /// `recover_with_recovery_key` with shares from `split_recovery_key`. The
+    /// new recovery key comes back split the same way.
+    pub fn recover_with_shares(&self, username: &str, shares: &[String], new_master_key: &str, new_password: &str) -> Result<Vec<String>, String> {
+        let (threshold, count, shares) = decode_shares(shares)?;
+        if shares.len() < threshold as usize {
+            return Err(format!("{} of the {} shares are needed, only {} given", threshold, count, shares.len()));
+        }
+        let secret = shamir::combine(&shares)?;
+        let secret = self.recover_with_secret(username, &secret, new_master_key, new_password)?;
+        encode_shares(&secret, threshold, count)
+    }
+
+    /// `recover_with_recovery_key` with the recovery secret as bytes. Returns
+    /// the new secret.
+    pub(crate) fn recover_with_secret(&self, username: &str, secret: &[u8], new_master_key: &str, new_password: &str) -> Result<[u8; RECOVERY_SECRET_LENGTH], String> {
+        let (user_id, new_secret) = {
+            let mut conn = self.conn.lock().unwrap();
+            let recovery_key = derive_key_from_secret(secret, RECOVERY_KEY_DOMAIN);
+
+            let mut stmt = conn.prepare(
+                "SELECT id, recovery_username_encrypted, recovery_username_nonce, recovery_data_key_encrypted, recovery_data_key_nonce
+                 FROM users WHERE recovery_data_key_encrypted IS NOT NULL"
+            ).map_err(|e| e.to_string())?;
+            let candidates: Vec<(i32, String, String, String, String)> = stmt.query_map([], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?))
+            }).map_err(|e| e.to_string())?
+            .filter_map(|r| r.ok())
+            .collect();
+            drop(stmt);
+
+            // Every candidate is tried, so the time taken does not tell where
+            // the account is among them.
+            let mut found = None;
+            for (id, enc_user, user_nonce, enc_data_key, data_key_nonce) in candidates {
+                let matches = decrypt_from_base64(&enc_user, &user_nonce, &recovery_key)
+                    .is_ok_and(|decrypted_username| decrypted_username == username);
+                if matches && found.is_none() {
+                    found = Some((id, enc_data_key, data_key_nonce));
+                }
+            }
+            let (user_id, enc_data_key, data_key_nonce) = found.ok_or("Invalid recovery key".to_string())?;
+            let data_key = unwrap_key(&enc_data_key, &data_key_nonce, &recovery_key)?;
+
+            let argon2 = Argon2::default();
+            let master_salt = SaltString::generate(&mut thread_rng());
+            let master_key_hash = argon2.hash_password(new_master_key.as_bytes(), &master_salt).map_err(|e| e.to_string())?.to_string();
+            let wrapping_key = master_key_wrapping_key(new_master_key, &master_key_hash)?;
+            let (username_encrypted, username_nonce) = encrypt_to_base64(username, &wrapping_key).map_err(|e| e.to_string())?;
+            let (data_key_encrypted, data_key_nonce) = wrap_key(&data_key, &wrapping_key)?;
+
+            let salt = SaltString::generate(&mut thread_rng());
+            let password_hash = argon2.hash_password(new_password.as_bytes(), &salt).map_err(|e| e.to_string())?.to_string();
+
+            let tx = conn.transaction().map_err(|e| e.to_string())?;
+            tx.execute(
+                "UPDATE users SET username_encrypted = ?, username_nonce = ?, password_hash = ?, master_key_hash = ?, data_key_encrypted = ?, data_key_nonce = ?, secret_key_check = NULL WHERE id = ?",
+                rusqlite::params![username_encrypted, username_nonce, password_hash, master_key_hash, data_key_encrypted, data_key_nonce, user_id],
+            ).map_err(|e| e.to_string())?;
+
+            let new_secret = store_recovery_secret(&tx, user_id, username, &data_key)?;
+            tx.commit().map_err(|e| e.to_string())?;
+            (user_id, new_secret)
+        };
+
+        remove_secret_key_file(&self.local_dir, user_id);
+        // A session unlocked with the old master key does not outlive it.
+        self.clear_session(user_id);
+        Ok(new_secret)
+    }
+}
+
+/// Generates a recovery secret wrapping `data_key`, replacing the user's
+/// current one. Only keys derived from the secret are stored.
+pub(crate) fn store_recovery_secret(conn: &Connection, user_id: i32, username: &str, data_key: &GenericArray<u8, U32>) -> Result<[u8; RECOVERY_SECRET_LENGTH], String> {
+    let mut secret = [0u8; RECOVERY_SECRET_LENGTH];
+    thread_rng().fill_bytes(&mut secret);
+    let recovery_key = derive_key_from_secret(&secret, RECOVERY_KEY_DOMAIN);
+
+    // The username is kept with it so recovery can find the account the way
+    // login does, and write the username back under the new master key.
+    let (username_encrypted, username_nonce) = encrypt_to_base64(username, &recovery_key).map_err(|e| e.to_string())?;
+    let (data_key_encrypted, data_key_nonce) = wrap_key(data_key, &recovery_key)?;
+    conn.execute(
+        "UPDATE users SET recovery_username_encrypted = ?, recovery_username_nonce = ?, recovery_data_key_encrypted = ?, recovery_data_key_nonce = ? WHERE id = ?",
+        rusqlite::params![username_encrypted, username_nonce, data_key_encrypted, data_key_nonce, user_id],
+    ).map_err(|e| e.to_string())?;
+    Ok(secret)
+}
+
+/// Shares are written as words, each starting with the threshold, the number
+/// of shares and its own point, so recovery knows how many it still needs.
+fn encode_shares(secret: &[u8], threshold: u8, count: u8) -> Result<Vec<String>, String> {
+    Ok(shamir::split(secret, threshold, count)?
+        .into_iter()
+        .map(|share| {
+            let mut bytes = vec![threshold, count, share.x];
+            bytes.extend_from_slice(&share.y);
+            encode_words(&bytes)
+        })
+        .collect())
+}
+
+fn decode_shares(shares: &[String]) -> Result<(u8, u8, Vec<Share>), String> {
+    let mut split = None;
+    let mut decoded = Vec::with_capacity(shares.len());
+    for (i, words) in shares.iter().enumerate() {
+        let bytes = decode_words(words).map_err(|e| format!("Share {}: {}", i + 1, e))?;
+        let (header, y) = bytes.split_at_checked(3).ok_or(format!("Share {} is too short", i + 1))?;
+        let (threshold, count) = (header[0], header[1]);
+        if *split.get_or_insert((threshold, count)) != (threshold, count) {
+            return Err("The shares are from different splits".to_string());
+        }
+        decoded.push(Share { x: header[2], y: y.to_vec() });
+    }
+    let (threshold, count) = split.ok_or("No shares given")?;
+    Ok((threshold, count, decoded))
+}
@@ -9,7 +9,19 @@ pub fn create_tables(conn: &Connection) -> Result<(), rusqlite::Error> {
             password_hash TEXT NOT NULL,
             master_key_hash TEXT NOT NULL,
             avatar BLOB,
-            avatar_nonce TEXT
+            avatar_nonce TEXT,
+            avatar_id TEXT,
+            data_key_encrypted TEXT,
+            data_key_nonce TEXT,
+            recovery_username_encrypted TEXT,
+            recovery_username_nonce TEXT,
+            recovery_data_key_encrypted TEXT,
+            recovery_data_key_nonce TEXT,
+            secret_key_check TEXT,
+            duress_target_encrypted TEXT,
+            duress_target_nonce TEXT,
+            duress_decoy_encrypted TEXT,
+            duress_decoy_nonce TEXT
         )",
         [],
     )?;
@@ -22,6 +34,8 @@ pub fn create_tables(conn: &Connection) -> Result<(), rusqlite::Error> {
             name_nonce TEXT NOT NULL,
             vault_ids TEXT NOT NULL,
             created_at INTEGER NOT NULL,
+            updated_at INTEGER,
+            last_used_at INTEGER,
             position INTEGER DEFAULT 0,
             FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
         )",
@@ -37,7 +51,14 @@ pub fn create_tables(conn: &Connection) -> Result<(), rusqlite::Error> {
             color TEXT NOT NULL,
             image BLOB,
             image_nonce TEXT,
+            image_id TEXT,
+            full_text_search INTEGER NOT NULL DEFAULT 1,
+            vault_key_encrypted TEXT,
+            vault_key_nonce TEXT,
+            passphrase_salt TEXT,
             created_at INTEGER NOT NULL,
+            updated_at INTEGER,
+            last_used_at INTEGER,
             position INTEGER DEFAULT 0,
             FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
         )",
@@ -56,11 +77,17 @@ pub fn create_tables(conn: &Connection) -> Result<(), rusqlite::Error> {
             full_name_nonce TEXT NOT NULL,
             id_number_encrypted TEXT NOT NULL,
             id_number_nonce TEXT NOT NULL,
+            expiry_date_encrypted TEXT,
+            expiry_date_nonce TEXT,
             color TEXT NOT NULL,
             image BLOB,
             image_nonce TEXT,
+            image_id TEXT,
             created_at INTEGER NOT NULL,
+            updated_at INTEGER,
+            last_used_at INTEGER,
             position INTEGER DEFAULT 0,
+            favorite_position INTEGER,
             FOREIGN KEY (vault_id) REFERENCES vaults(id) ON DELETE CASCADE
         )",
         [],
@@ -83,9 +110,12 @@ pub fn create_tables(conn: &Connection) -> Result<(), rusqlite::Error> {
             color TEXT NOT NULL,
             image BLOB,
             image_nonce TEXT,
+            image_id TEXT,
             created_at INTEGER NOT NULL,
             updated_at INTEGER NOT NULL,
+            last_used_at INTEGER,
             position INTEGER DEFAULT 0,
+            favorite_position INTEGER,
             FOREIGN KEY (vault_id) REFERENCES vaults(id) ON DELETE CASCADE
         )",
         [],
@@ -105,12 +135,19 @@ pub fn create_tables(conn: &Connection) -> Result<(), rusqlite::Error> {
             password_nonce TEXT NOT NULL,
             details_encrypted TEXT,
             details_nonce TEXT,
+            rotation_due_encrypted TEXT,
+            rotation_due_nonce TEXT,
+            totp_encrypted TEXT,
+            totp_nonce TEXT,
             color TEXT NOT NULL,
             image BLOB,
             image_nonce TEXT,
+            image_id TEXT,
             created_at INTEGER NOT NULL,
             updated_at INTEGER NOT NULL,
+            last_used_at INTEGER,
             position INTEGER DEFAULT 0,
+            favorite_position INTEGER,
             FOREIGN KEY (vault_id) REFERENCES vaults(id) ON DELETE CASCADE
         )",
         [],
@@ -127,12 +164,316 @@ pub fn create_tables(conn: &Connection) -> Result<(), rusqlite::Error> {
             color TEXT NOT NULL,
             image BLOB,
             image_nonce TEXT,
+            image_id TEXT,
             created_at INTEGER NOT NULL,
+            updated_at INTEGER,
+            last_used_at INTEGER,
             position INTEGER DEFAULT 0,
+            favorite_position INTEGER,
             FOREIGN KEY (vault_id) REFERENCES vaults(id) ON DELETE CASCADE
         )",
         [],
     )?;
 
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS custom_fields (
+            id TEXT PRIMARY KEY,
+            item_kind TEXT NOT NULL,
+            item_id TEXT NOT NULL,
+            label_encrypted TEXT NOT NULL,
+            label_nonce TEXT NOT NULL,
+            value_encrypted TEXT NOT NULL,
+            value_nonce TEXT NOT NULL,
+            field_type TEXT NOT NULL,
+            position INTEGER DEFAULT 0
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_custom_fields_item ON custom_fields (item_id, position)",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS attachments (
+            id TEXT PRIMARY KEY,
+            item_kind TEXT NOT NULL,
+            item_id TEXT NOT NULL,
+            file_name_encrypted TEXT NOT NULL,
+            file_name_nonce TEXT NOT NULL,
+            file_key_encrypted TEXT NOT NULL,
+            file_key_nonce TEXT NOT NULL,
+            size INTEGER NOT NULL,
+            created_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_attachments_item ON attachments (item_id)",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS image_blobs (
+            id TEXT PRIMARY KEY,
+            user_id INTEGER NOT NULL,
+            data BLOB NOT NULL,
+            thumbnail BLOB,
+            ref_count INTEGER NOT NULL DEFAULT 0,
+            created_at INTEGER NOT NULL,
+            FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS tags (
+            id TEXT PRIMARY KEY,
+            user_id INTEGER NOT NULL,
+            name_encrypted TEXT NOT NULL,
+            name_nonce TEXT NOT NULL,
+            name_hash TEXT NOT NULL,
+            created_at INTEGER NOT NULL,
+            UNIQUE (user_id, name_hash),
+            FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS item_tags (
+            tag_id TEXT NOT NULL,
+            item_kind TEXT NOT NULL,
+            item_id TEXT NOT NULL,
+            PRIMARY KEY (tag_id, item_id),
+            FOREIGN KEY (tag_id) REFERENCES tags(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_item_tags_item ON item_tags (item_id)",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS full_text_tokens (
+            token TEXT NOT NULL,
+            user_id INTEGER NOT NULL,
+            item_kind TEXT NOT NULL,
+            item_id TEXT NOT NULL,
+            PRIMARY KEY (token, item_id),
+            FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_full_text_tokens_item ON full_text_tokens (item_id)",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS full_text_state (
+            user_id INTEGER PRIMARY KEY,
+            key_check TEXT NOT NULL,
+            FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS item_templates (
+            id TEXT PRIMARY KEY,
+            user_id INTEGER NOT NULL,
+            item_kind TEXT NOT NULL,
+            name_encrypted TEXT NOT NULL,
+            name_nonce TEXT NOT NULL,
+            fields_encrypted TEXT NOT NULL,
+            fields_nonce TEXT NOT NULL,
+            color TEXT NOT NULL,
+            created_at INTEGER NOT NULL,
+            FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS user_two_factor (
+            user_id INTEGER PRIMARY KEY,
+            secret_encrypted TEXT NOT NULL,
+            secret_nonce TEXT NOT NULL,
+            enabled INTEGER NOT NULL DEFAULT 0,
+            last_counter INTEGER,
+            created_at INTEGER NOT NULL,
+            FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS two_factor_backup_codes (
+            user_id INTEGER NOT NULL,
+            code_hash TEXT NOT NULL,
+            used_at INTEGER,
+            PRIMARY KEY (user_id, code_hash),
+            FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+
+    add_column_if_missing(conn, "users", "avatar_id", "TEXT")?;
+    add_column_if_missing(conn, "users", "secret_key_check", "TEXT")?;
+    add_column_if_missing(conn, "vaults", "full_text_search", "INTEGER NOT NULL DEFAULT 1")?;
+    add_column_if_missing(conn, "vaults", "passphrase_salt", "TEXT")?;
+    let encrypted_fields = [
+        ("id_cards", "expiry_date"),
+        ("login_keys", "rotation_due"),
+        ("login_keys", "totp"),
+        ("users", "data_key"),
+        ("users", "recovery_username"),
+        ("users", "recovery_data_key"),
+        ("users", "duress_target"),
+        ("users", "duress_decoy"),
+        ("vaults", "vault_key"),
+    ];
+    for (table, field) in encrypted_fields {
+        add_column_if_missing(conn, table, &format!("{}_encrypted", field), "TEXT")?;
+        add_column_if_missing(conn, table, &format!("{}_nonce", field), "TEXT")?;
+    }
+    for table in ["vaults", "id_cards", "credit_cards", "login_keys", "notes"] {
+        add_column_if_missing(conn, table, "image_id", "TEXT")?;
+    }
+    for table in ["id_cards", "credit_cards", "login_keys", "notes"] {
+        add_column_if_missing(conn, table, "favorite_position", "INTEGER")?;
+    }
+    for table in ["collections", "vaults", "id_cards", "credit_cards", "login_keys", "notes"] {
+        if add_column_if_missing(conn, table, "updated_at", "INTEGER")? {
+            // Records from before `updated_at` count as unchanged since created.
+            conn.execute(&format!("UPDATE {} SET updated_at = created_at", table), [])?;
+        }
+        add_column_if_missing(conn, table, "last_used_at", "INTEGER")?;
+    }
+
+    // Custom fields, attachments, tags and full-text tokens name their item by
+    // `item_kind` and `item_id`, which no foreign key can reference across the
+    // four item tables, so they are deleted along with the item here. The
+    // files of attachments are not; `delete_item_rows` collects them first.
+    for (table, kind) in [("login_keys", "login_key"), ("credit_cards", "credit_card"), ("id_cards", "id_card"), ("notes", "note")] {
+        conn.execute(
+            &format!(
+                "CREATE TRIGGER IF NOT EXISTS {0}_delete_dependents AFTER DELETE ON {0} BEGIN
+                    DELETE FROM custom_fields WHERE item_kind = '{1}' AND item_id = OLD.id;
+                    DELETE FROM attachments WHERE item_kind = '{1}' AND item_id = OLD.id;
+                    DELETE FROM item_tags WHERE item_kind = '{1}' AND item_id = OLD.id;
+                    DELETE FROM full_text_tokens WHERE item_kind = '{1}' AND item_id = OLD.id;
+                END",
+                table, kind
+            ),
+            [],
+        )?;
+    }
+
     Ok(())
 }
+
+/// `CREATE TABLE IF NOT EXISTS` leaves existing tables alone, so columns added
+/// later have to be added to databases created before them. Returns whether
+/// the column was added.
+fn add_column_if_missing(conn: &Connection, table: &str, column: &str, definition: &str) -> Result<bool, rusqlite::Error> {
+    let exists: bool = conn.query_row(
+        &format!("SELECT EXISTS(SELECT 1 FROM pragma_table_info('{}') WHERE name = ?)", table),
+        [column],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition), [])?;
+    }
+    Ok(!exists)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn connection() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        create_tables(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO users (id, username_encrypted, username_nonce, password_hash, master_key_hash) VALUES (1, '', '', '', '')",
+            [],
+        ).unwrap();
+        conn.execute(
+            "INSERT INTO vaults (id, user_id, name_encrypted, name_nonce, color, created_at) VALUES ('v', 1, '', '', '', 1)",
+            [],
+        ).unwrap();
+        conn.execute(
+            "INSERT INTO tags (id, user_id, name_encrypted, name_nonce, name_hash, created_at) VALUES ('t', 1, '', '', '', 1)",
+            [],
+        ).unwrap();
+        conn
+    }
+
+    fn add_note(conn: &Connection, id: &str) {
+        conn.execute(
+            "INSERT INTO notes (id, vault_id, note_name_encrypted, note_name_nonce, content_encrypted, content_nonce, color, created_at) VALUES (?, 'v', '', '', '', '', '', 1)",
+            [id],
+        ).unwrap();
+        conn.execute(
+            "INSERT INTO custom_fields (id, item_kind, item_id, label_encrypted, label_nonce, value_encrypted, value_nonce, field_type) VALUES (?, 'note', ?, '', '', '', '', 'text')",
+            [format!("field-{}", id), id.to_string()],
+        ).unwrap();
+        conn.execute(
+            "INSERT INTO attachments (id, item_kind, item_id, file_name_encrypted, file_name_nonce, file_key_encrypted, file_key_nonce, size, created_at) VALUES (?, 'note', ?, '', '', '', '', 0, 1)",
+            [format!("attachment-{}", id), id.to_string()],
+        ).unwrap();
+        conn.execute("INSERT INTO item_tags (tag_id, item_kind, item_id) VALUES ('t', 'note', ?)", [id]).unwrap();
+        conn.execute("INSERT INTO full_text_tokens (token, user_id, item_kind, item_id) VALUES ('word', 1, 'note', ?)", [id]).unwrap();
+    }
+
+    fn dependents(conn: &Connection, item_id: &str) -> i64 {
+        ["custom_fields", "attachments", "item_tags", "full_text_tokens"].iter()
+            .map(|table| conn.query_row(&format!("SELECT COUNT(*) FROM {} WHERE item_id = ?", table), [item_id], |row| row.get::<_, i64>(0)).unwrap())
+            .sum()
+    }
+
+    #[test]
+    fn deleting_an_item_deletes_what_hangs_off_it() {
+        let conn = connection();
+        add_note(&conn, "a");
+        add_note(&conn, "b");
+
+        conn.execute("DELETE FROM notes WHERE id = 'a'", []).unwrap();
+        assert_eq!(dependents(&conn, "a"), 0);
+        assert_eq!(dependents(&conn, "b"), 4);
+
+        // Items deleted with their vault, by foreign key, go the same way.
+        conn.execute("DELETE FROM vaults WHERE id = 'v'", []).unwrap();
+        assert_eq!(dependents(&conn, "b"), 0);
+    }
+
+    #[test]
+    fn rows_of_other_item_kinds_are_left_alone() {
+        let conn = connection();
+        conn.execute(
+            "INSERT INTO custom_fields (id, item_kind, item_id, label_encrypted, label_nonce, value_encrypted, value_nonce, field_type) VALUES ('f', 'login_key', 'a', '', '', '', '', 'text')",
+            [],
+        ).unwrap();
+        add_note(&conn, "a");
+
+        conn.execute("DELETE FROM notes WHERE id = 'a'", []).unwrap();
+        assert_eq!(dependents(&conn, "a"), 1);
+    }
+
+    #[test]
+    fn opening_again_leaves_updated_at_alone() {
+        let conn = connection();
+        conn.execute("UPDATE vaults SET updated_at = NULL", []).unwrap();
+        create_tables(&conn).unwrap();
+
+        let updated_at: Option<i64> = conn.query_row("SELECT updated_at FROM vaults", [], |row| row.get(0)).unwrap();
+        assert_eq!(updated_at, None);
+    }
+}
@@ -0,0 +1,221 @@
+use std::fs::{self, File};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use generic_array::GenericArray;
+use rand::{thread_rng, RngCore};
+use rusqlite::{Connection, OptionalExtension};
+use sha2::{Digest, Sha256};
+use typenum::U32;
+
+use crate::crypto::derive_key_from_secret;
+use super::database::Database;
+use super::keys::{unlock_with_master_key, unwrap_data_key, wrap_key};
+
+const SECRET_KEY_LENGTH: usize = 16;
+const SECRET_KEY_PREFIX: &str = "NC1";
+const SECRET_KEY_DOMAIN: &[u8] = b"secret-key";
+/// Returned when unlocking an account whose secret key is not on this device.
+pub const SECRET_KEY_MISSING: &str = "This account needs its secret key, which is not on this device. Import it to unlock";
+
+impl Database {
+    pub fn secret_key_enabled(&self, user_id: i32) -> Result<bool, String> {
+        let conn = self.conn.lock().unwrap();
+        Ok(stored_check(&conn, user_id)?.is_some())
+    }
+
+    /// Adds a secret key to the account: a random value kept in a file on this
+    /// device and combined with the master key to wrap the data key, so the
+    /// master key alone no longer unlocks `account.db`. Returns it for the
+    /// user to keep, as other devices need it imported.
+    pub fn create_secret_key(&self, user_id: i32, master_key: &str) -> Result<String, String> {
+        let conn = self.conn.lock().unwrap();
+        if stored_check(&conn, user_id)?.is_some() {
+            return Err("The account already has a secret key".to_string());
+        }
+        self.replace_secret_key(&conn, user_id, master_key)
+    }
+
+    /// Replaces the secret key; the old one no longer unlocks the account.
+    pub fn rotate_secret_key(&self, user_id: i32, master_key: &str) -> Result<String, String> {
+        let conn = self.conn.lock().unwrap();
+        if stored_check(&conn, user_id)?.is_none() {
+            return Err("The account has no secret key".to_string());
+        }
+        self.replace_secret_key(&conn, user_id, master_key)
+    }
+
+    pub fn export_secret_key(&self, user_id: i32, master_key: &str) -> Result<String, String> {
+        let conn = self.conn.lock().unwrap();
+        unlock_with_master_key(&conn, user_id, master_key)?;
+        let secret = self.load_secret_key(&conn, user_id)?.ok_or("The account has no secret key")?;
+        Ok(format_secret_key(&secret))
+    }
+
+    /// Saves an exported secret key on this device, after checking it is the
+    /// one the account uses. A decoy profile has no secret key of its own but
+    /// asks for that of the account it stands in for, which is saved instead.
+    pub fn import_secret_key(&self, user_id: i32, secret_key: &str) -> Result<(), String> {
+        let conn = self.conn.lock().unwrap();
+        let secret = parse_secret_key(secret_key)?;
+        let check = check_value(&secret);
+        let owner = match stored_check(&conn, user_id)? {
+            Some(stored) => Some(user_id).filter(|_| stored == check),
+            None => conn.query_row("SELECT id FROM users WHERE secret_key_check = ?", [&check], |row| row.get(0))
+                .optional()
+                .map_err(|e| e.to_string())?,
+        };
+        let owner = owner.ok_or("This secret key does not belong to the account")?;
+        write_secret_key_file(&self.local_dir, owner, &secret)
+    }
+
+    /// The key the data key is wrapped with: the one derived from the master
+    /// key, combined with the secret key when the account has one.
+    pub(crate) fn data_key_wrapping_key(&self, conn: &Connection, user_id: i32, master_key_wrapping_key: GenericArray<u8, U32>) -> Result<GenericArray<u8, U32>, String> {
+        Ok(match self.load_secret_key(conn, user_id)? {
+            Some(secret) => combine(&secret, &master_key_wrapping_key),
+            None => master_key_wrapping_key,
+        })
+    }
+
+    fn replace_secret_key(&self, conn: &Connection, user_id: i32, master_key: &str) -> Result<String, String> {
+        let (master_key_wrapping_key, _) = unlock_with_master_key(conn, user_id, master_key)?;
+        let old_secret = self.load_secret_key(conn, user_id)?;
+        let wrapping_key = match &old_secret {
+            Some(old_secret) => combine(old_secret, &master_key_wrapping_key),
+            None => master_key_wrapping_key,
+        };
+        let data_key = unwrap_data_key(conn, user_id, &wrapping_key)?;
+
+        let mut secret = [0u8; SECRET_KEY_LENGTH];
+        thread_rng().fill_bytes(&mut secret);
+        let (data_key_encrypted, data_key_nonce) = wrap_key(&data_key, &combine(&secret, &master_key_wrapping_key))?;
+
+        // The file is written first; if the database then is not updated the
+        // old key is put back, as neither alone may be lost.
+        write_secret_key_file(&self.local_dir, user_id, &secret)?;
+        let updated = conn.execute(
+            "UPDATE users SET data_key_encrypted = ?, data_key_nonce = ?, secret_key_check = ? WHERE id = ?",
+            rusqlite::params![data_key_encrypted, data_key_nonce, check_value(&secret), user_id],
+        );
+        if let Err(e) = updated {
+            match old_secret {
+                Some(old_secret) => { let _ = write_secret_key_file(&self.local_dir, user_id, &old_secret); }
+                None => remove_secret_key_file(&self.local_dir, user_id),
+            }
+            return Err(e.to_string());
+        }
+        Ok(format_secret_key(&secret))
+    }
+
+    pub(crate) fn load_secret_key(&self, conn: &Connection, user_id: i32) -> Result<Option<[u8; SECRET_KEY_LENGTH]>, String> {
+        let check = match stored_check(conn, user_id)? {
+            Some(check) => check,
+            None => return Ok(None),
+        };
+        let content = match fs::read_to_string(secret_key_path(&self.local_dir, user_id)) {
+            Ok(content) => content,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Err(SECRET_KEY_MISSING.to_string()),
+            Err(e) => return Err(e.to_string()),
+        };
+        let secret = parse_secret_key(&content)?;
+        if check_value(&secret) != check {
+            return Err("The secret key on this device is not the account's current one. Import it to unlock".to_string());
+        }
+        Ok(Some(secret))
+    }
+}
+
+pub(crate) fn remove_secret_key_file(local_dir: &Path, user_id: i32) {
+    let _ = fs::remove_file(secret_key_path(local_dir, user_id));
+}
+
+/// Moves secret keys stored next to `account.db` by earlier versions out of
+/// the data folder, which may be synced along with the database.
+pub(crate) fn move_secret_keys(data_dir: &Path, local_dir: &Path) {
+    let entries = match fs::read_dir(secret_keys_dir(data_dir)) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    if fs::create_dir_all(secret_keys_dir(local_dir)).is_err() {
+        return;
+    }
+    for entry in entries.flatten() {
+        let target = secret_keys_dir(local_dir).join(entry.file_name());
+        if !target.exists() && fs::copy(entry.path(), &target).is_ok() {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+    let _ = fs::remove_dir(secret_keys_dir(data_dir));
+}
+
+fn stored_check(conn: &Connection, user_id: i32) -> Result<Option<String>, String> {
+    conn.query_row("SELECT secret_key_check FROM users WHERE id = ?", [user_id], |row| row.get(0))
+        .map_err(|e| e.to_string())
+}
+
+/// Tells a missing or stale secret key apart from a wrong master key. The
+/// secret is random, so an unkeyed hash of it gives nothing away.
+fn check_value(secret: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(SECRET_KEY_DOMAIN);
+    hasher.update(secret);
+    hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn combine(secret: &[u8], master_key_wrapping_key: &GenericArray<u8, U32>) -> GenericArray<u8, U32> {
+    derive_key_from_secret(&[secret, master_key_wrapping_key.as_slice()].concat(), SECRET_KEY_DOMAIN)
+}
+
+/// `NC1-` followed by the secret in hex, in groups of four.
+fn format_secret_key(secret: &[u8]) -> String {
+    let hex: String = secret.iter().map(|b| format!("{:02X}", b)).collect();
+    let groups: Vec<&str> = hex.as_bytes().chunks(4).map(|c| std::str::from_utf8(c).unwrap()).collect();
+    format!("{}-{}", SECRET_KEY_PREFIX, groups.join("-"))
+}
+
+fn parse_secret_key(value: &str) -> Result<[u8; SECRET_KEY_LENGTH], String> {
+    let invalid = || format!("Invalid secret key, expected {}- followed by {} hex digits", SECRET_KEY_PREFIX, SECRET_KEY_LENGTH * 2);
+
+    let value = value.trim().to_ascii_uppercase();
+    let hex: String = value.strip_prefix(SECRET_KEY_PREFIX).unwrap_or(&value)
+        .chars()
+        .filter(|c| !matches!(c, '-' | ' '))
+        .collect();
+    if hex.len() != SECRET_KEY_LENGTH * 2 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(invalid());
+    }
+    let mut secret = [0u8; SECRET_KEY_LENGTH];
+    for (i, byte) in secret.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).map_err(|_| invalid())?;
+    }
+    Ok(secret)
+}
+
+/// Writes the key to a temporary file first and renames it into place, so a
+/// crash never leaves a truncated key behind.
+fn write_secret_key_file(local_dir: &Path, user_id: i32, secret: &[u8]) -> Result<(), String> {
+    fs::create_dir_all(secret_keys_dir(local_dir)).map_err(|e| e.to_string())?;
+    let path = secret_key_path(local_dir, user_id);
+    let temp_path = path.with_extension("key.tmp");
+
+    let written = File::create(&temp_path)
+        .and_then(|mut file| {
+            file.write_all(format_secret_key(secret).as_bytes())?;
+            file.sync_all()
+        })
+        .and_then(|_| fs::rename(&temp_path, &path));
+    if let Err(e) = written {
+        let _ = fs::remove_file(&temp_path);
+        return Err(e.to_string());
+    }
+    Ok(())
+}
+
+fn secret_keys_dir(local_dir: &Path) -> PathBuf {
+    local_dir.join("secret-keys")
+}
+
+fn secret_key_path(local_dir: &Path, user_id: i32) -> PathBuf {
+    secret_keys_dir(local_dir).join(format!("{}.key", user_id))
+}
@@ -1,43 +1,74 @@
 use generic_array::GenericArray;
 use typenum::U32;
 
-use crate::crypto::derive_encryption_key;
+use crate::db::full_text::ensure_full_text_index;
+use crate::images::migrate_inline_images;
 use super::database::Database;
+use super::duress::duress_target;
+use super::keys::{master_key_wrapping_key, unwrap_data_key};
+use super::two_factor::check_two_factor;
 
 impl Database {
-    pub fn init_session(&self, user_id: i32, master_key: &str) -> Result<(), String> {
-        let conn = self.conn.lock().unwrap();
+    /// Unwraps the user's data key with their master key and keeps it for the
+    /// session. With two-factor unlock on, `two_factor_code` has to check out
+    /// first, unless the session already holds this key, e.g. when the window
+    /// reloads.
+    pub fn init_session(&self, user_id: i32, master_key: &str, two_factor_code: Option<&str>) -> Result<(), String> {
+        let mut conn = self.conn.lock().unwrap();
         let master_key_hash: String = conn.query_row(
             "SELECT master_key_hash FROM users WHERE id = ?",
             [user_id],
             |row| row.get(0)
         ).map_err(|e| e.to_string())?;
         
-        let salt = extract_salt_from_hash(&master_key_hash)?;
-        let key = derive_encryption_key(master_key, &salt).map_err(|e| e.to_string())?;
+        let wrapping_key = master_key_wrapping_key(master_key, &master_key_hash)?;
+        // A decoy profile needs the secret key of the account it stands in
+        // for, and has that account's second factor copied onto it.
+        if let Some(target) = duress_target(&conn, user_id, &wrapping_key)? {
+            self.load_secret_key(&conn, target.user_id)?;
+        }
+        let wrapping_key = self.data_key_wrapping_key(&conn, user_id, wrapping_key)?;
+        let key = unwrap_data_key(&conn, user_id, &wrapping_key)?;
+        let unlocked = self.encryption_keys.lock().unwrap().get(&user_id) == Some(&key);
+        if !unlocked {
+            check_two_factor(&conn, user_id, &key, two_factor_code)?;
+        }
+
+        migrate_inline_images(&mut conn, user_id, &key)?;
+        self.build_search_index(&conn, user_id, &key)?;
+        ensure_full_text_index(&mut conn, user_id, &key)?;
 
         let mut keys = self.encryption_keys.lock().unwrap();
         keys.insert(user_id, key);
+        *self.active_user.lock().unwrap() = Some(user_id);
         Ok(())
     }
 
     pub fn clear_session(&self, user_id: i32) {
         let mut keys = self.encryption_keys.lock().unwrap();
         keys.remove(&user_id);
+        self.search_indexes.lock().unwrap().remove(&user_id);
+        self.quick_unlocks.lock().unwrap().remove(&user_id);
+        self.lock_vaults(user_id);
+        self.deactivate_user(user_id);
     }
 
     pub fn get_encryption_key(&self, user_id: i32) -> Result<GenericArray<u8, U32>, String> {
         let keys = self.encryption_keys.lock().map_err(|e| e.to_string())?;
-        Ok(keys.get(&user_id)
-            .ok_or("Session not initialized. Call init_session first.".to_string())?
-            .clone())
+        Ok(*keys.get(&user_id)
+            .ok_or("Session not initialized. Call init_session first.".to_string())?)
     }
-}
 
-fn extract_salt_from_hash(hash: &str) -> Result<Vec<u8>, String> {
-    use argon2::PasswordHash;
-    let parsed_hash = PasswordHash::new(hash).map_err(|e| e.to_string())?;
-    let salt = parsed_hash.salt
-        .ok_or("Salt not found in hash".to_string())?;
-    Ok(salt.as_ref().as_bytes().to_vec())
+    /// The user of the session the window shows.
+    pub fn active_user(&self) -> Result<i32, String> {
+        self.active_user.lock().unwrap()
+            .ok_or("Session not initialized. Call init_session first.".to_string())
+    }
+
+    pub(crate) fn deactivate_user(&self, user_id: i32) {
+        let mut active_user = self.active_user.lock().unwrap();
+        if *active_user == Some(user_id) {
+            *active_user = None;
+        }
+    }
 }
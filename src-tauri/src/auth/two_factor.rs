@@ -0,0 +1,208 @@
+use chrono::Utc;
+use generic_array::GenericArray;
+use rand::Rng;
+use rusqlite::{Connection, OptionalExtension};
+use typenum::U32;
+
+use crate::crypto::{decrypt_from_base64, derive_key_from_secret, encrypt_to_base64, keyed_hash};
+use crate::models::TwoFactorEnrollment;
+use crate::totp::OtpAuth;
+use super::database::Database;
+use super::duress::mirror_two_factor;
+use super::keys::unlock_with_master_key;
+
+/// Returned by `init_session` when the account has two-factor unlock and no
+/// code was given, so the frontend knows to ask for one.
+pub const TWO_FACTOR_REQUIRED: &str = "Two-factor code required";
+const ISSUER: &str = "n-cryption";
+const BACKUP_CODE_COUNT: usize = 10;
+const BACKUP_CODE_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+const BACKUP_CODE_DOMAIN: &[u8] = b"two-factor-backup-code";
+/// Codes from one period either side of now are accepted.
+const ALLOWED_SKEW: u64 = 1;
+
+impl Database {
+    pub fn two_factor_enabled(&self, user_id: i32) -> Result<bool, String> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM user_two_factor WHERE user_id = ? AND enabled = 1)",
+            [user_id],
+            |row| row.get(0),
+        ).map_err(|e| e.to_string())
+    }
+
+    /// Generates a new secret for the user to add to an authenticator app.
+    /// Nothing changes for unlocking until `confirm_two_factor` is called
+    /// with a code made from it; starting over replaces a pending secret. The
+    /// master key is asked for again, and names the account in the app.
+    pub fn begin_two_factor_enrollment(&self, user_id: i32, master_key: &str) -> Result<TwoFactorEnrollment, String> {
+        let conn = self.conn.lock().unwrap();
+        let key = self.get_encryption_key(user_id)?;
+
+        if load_secret(&conn, user_id, &key, true)?.is_some() {
+            return Err("Two-factor unlock is already enabled".to_string());
+        }
+
+        let (_, username) = unlock_with_master_key(&conn, user_id, master_key)?;
+
+        let otp = OtpAuth::generate(&format!("{}:{}", ISSUER, username), ISSUER);
+        let uri = otp.to_uri();
+        let (secret_encrypted, secret_nonce) = encrypt_to_base64(&uri, &key).map_err(|e| e.to_string())?;
+        conn.execute(
+            "INSERT OR REPLACE INTO user_two_factor (user_id, secret_encrypted, secret_nonce, enabled, last_counter, created_at)
+             VALUES (?, ?, ?, 0, NULL, ?)",
+            rusqlite::params![user_id, secret_encrypted, secret_nonce, Utc::now().timestamp()],
+        ).map_err(|e| e.to_string())?;
+
+        Ok(TwoFactorEnrollment { secret: otp.secret_base32(), uri })
+    }
+
+    /// Turns two-factor unlock on once `code` shows the authenticator app
+    /// has the pending secret. Returns the backup codes, which are only
+    /// stored hashed and cannot be shown again.
+    pub fn confirm_two_factor(&self, user_id: i32, code: &str) -> Result<Vec<String>, String> {
+        let conn = self.conn.lock().unwrap();
+        let key = self.get_encryption_key(user_id)?;
+
+        let (otp, _) = load_secret(&conn, user_id, &key, false)?
+            .ok_or("No two-factor enrollment in progress")?;
+        let counter = otp.matching_counter(code.trim(), Utc::now().timestamp() as u64, ALLOWED_SKEW)
+            .ok_or("Invalid two-factor code")?;
+
+        conn.execute(
+            "UPDATE user_two_factor SET enabled = 1, last_counter = ? WHERE user_id = ?",
+            rusqlite::params![counter as i64, user_id],
+        ).map_err(|e| e.to_string())?;
+        let codes = replace_backup_codes(&conn, user_id, &otp)?;
+        mirror_two_factor(&conn, user_id, &key)?;
+        Ok(codes)
+    }
+
+    /// Replaces the backup codes; a code is required so an unlocked but
+    /// unattended app cannot be used to get new ones.
+    pub fn regenerate_backup_codes(&self, user_id: i32, code: &str) -> Result<Vec<String>, String> {
+        let conn = self.conn.lock().unwrap();
+        let key = self.get_encryption_key(user_id)?;
+
+        verify_second_factor(&conn, user_id, &key, code)?;
+        let (otp, _) = load_secret(&conn, user_id, &key, true)?
+            .ok_or("Two-factor unlock is not enabled")?;
+        let codes = replace_backup_codes(&conn, user_id, &otp)?;
+        mirror_two_factor(&conn, user_id, &key)?;
+        Ok(codes)
+    }
+
+    pub fn disable_two_factor(&self, user_id: i32, code: &str) -> Result<(), String> {
+        let conn = self.conn.lock().unwrap();
+        let key = self.get_encryption_key(user_id)?;
+
+        verify_second_factor(&conn, user_id, &key, code)?;
+        conn.execute("DELETE FROM user_two_factor WHERE user_id = ?", [user_id])
+            .map_err(|e| e.to_string())?;
+        conn.execute("DELETE FROM two_factor_backup_codes WHERE user_id = ?", [user_id])
+            .map_err(|e| e.to_string())?;
+        mirror_two_factor(&conn, user_id, &key)
+    }
+}
+
+/// Checks the second factor before a session gets `key`. Accounts without
+/// two-factor unlock pass whatever `code` is.
+pub(crate) fn check_two_factor(conn: &Connection, user_id: i32, key: &GenericArray<u8, U32>, code: Option<&str>) -> Result<(), String> {
+    if load_secret(conn, user_id, key, true)?.is_none() {
+        return Ok(());
+    }
+    match code.map(str::trim).filter(|c| !c.is_empty()) {
+        Some(code) => verify_second_factor(conn, user_id, key, code),
+        None => Err(TWO_FACTOR_REQUIRED.to_string()),
+    }
+}
+
+/// Accepts a current TOTP code not used before, or an unused backup code,
+/// which is used up.
+fn verify_second_factor(conn: &Connection, user_id: i32, key: &GenericArray<u8, U32>, code: &str) -> Result<(), String> {
+    let (otp, last_counter) = load_secret(conn, user_id, key, true)?
+        .ok_or("Two-factor unlock is not enabled")?;
+    let code = code.trim();
+
+    if let Some(counter) = otp.matching_counter(code, Utc::now().timestamp() as u64, ALLOWED_SKEW) {
+        // A code seen once, e.g. over a shoulder, must not unlock again.
+        if last_counter.is_none_or(|last| counter as i64 > last) {
+            conn.execute(
+                "UPDATE user_two_factor SET last_counter = ? WHERE user_id = ?",
+                rusqlite::params![counter as i64, user_id],
+            ).map_err(|e| e.to_string())?;
+            return Ok(());
+        }
+    }
+
+    // Older codes were hashed with the data key instead.
+    let code = normalize_backup_code(code);
+    let hash = keyed_hash(&backup_code_key(&otp), BACKUP_CODE_DOMAIN, code.as_bytes());
+    let data_key_hash = keyed_hash(key, BACKUP_CODE_DOMAIN, code.as_bytes());
+    let used = conn.execute(
+        "UPDATE two_factor_backup_codes SET used_at = ? WHERE user_id = ? AND code_hash IN (?, ?) AND used_at IS NULL",
+        rusqlite::params![Utc::now().timestamp(), user_id, hash, data_key_hash],
+    ).map_err(|e| e.to_string())?;
+    if used == 1 {
+        Ok(())
+    } else {
+        Err("Invalid two-factor code".to_string())
+    }
+}
+
+/// The stored secret with the last TOTP counter accepted, if the user has
+/// one that is `enabled`, or pending when not.
+fn load_secret(conn: &Connection, user_id: i32, key: &GenericArray<u8, U32>, enabled: bool) -> Result<Option<(OtpAuth, Option<i64>)>, String> {
+    let row: Option<(String, String, Option<i64>)> = conn.query_row(
+        "SELECT secret_encrypted, secret_nonce, last_counter FROM user_two_factor WHERE user_id = ? AND enabled = ?",
+        rusqlite::params![user_id, enabled],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    ).optional().map_err(|e| e.to_string())?;
+
+    match row {
+        Some((encrypted, nonce, last_counter)) => {
+            let uri = decrypt_from_base64(&encrypted, &nonce, key).map_err(|e| e.to_string())?;
+            Ok(Some((OtpAuth::parse(&uri)?, last_counter)))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Backup codes are hashed with a key derived from the TOTP secret rather
+/// than the data key, so the hashes stay valid where the secret is copied to,
+/// like the decoy profile of a duress master key.
+fn backup_code_key(otp: &OtpAuth) -> GenericArray<u8, U32> {
+    derive_key_from_secret(&otp.secret, BACKUP_CODE_DOMAIN)
+}
+
+fn replace_backup_codes(conn: &Connection, user_id: i32, otp: &OtpAuth) -> Result<Vec<String>, String> {
+    let key = backup_code_key(otp);
+    conn.execute("DELETE FROM two_factor_backup_codes WHERE user_id = ?", [user_id])
+        .map_err(|e| e.to_string())?;
+
+    let mut rng = rand::thread_rng();
+    let mut codes = Vec::with_capacity(BACKUP_CODE_COUNT);
+    while codes.len() < BACKUP_CODE_COUNT {
+        let raw: String = (0..10)
+            .map(|_| BACKUP_CODE_ALPHABET[rng.gen_range(0..BACKUP_CODE_ALPHABET.len())] as char)
+            .collect();
+        let hash = keyed_hash(&key, BACKUP_CODE_DOMAIN, raw.as_bytes());
+        let inserted = conn.execute(
+            "INSERT OR IGNORE INTO two_factor_backup_codes (user_id, code_hash, used_at) VALUES (?, ?, NULL)",
+            rusqlite::params![user_id, hash],
+        ).map_err(|e| e.to_string())?;
+        if inserted == 1 {
+            codes.push(format!("{}-{}", &raw[..5], &raw[5..]));
+        }
+    }
+    Ok(codes)
+}
+
+/// Backup codes are shown grouped; accept them typed with or without the
+/// dash and in any case.
+fn normalize_backup_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| !matches!(c, '-' | ' '))
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
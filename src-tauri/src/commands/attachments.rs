@@ -0,0 +1,25 @@
+use std::path::Path;
+
+use crate::auth::Database;
+use crate::db::items::{with_item_type, ItemKind};
+use crate::models::Attachment;
+
+#[tauri::command(async)]
+pub fn add_attachment(kind: ItemKind, item_id: String, source_path: String, user_id: i32, state: tauri::State<Database>) -> Result<Attachment, String> {
+    with_item_type!(kind, |T| state.add_attachment::<T>(&item_id, Path::new(&source_path), user_id))
+}
+
+#[tauri::command]
+pub fn get_attachments(kind: ItemKind, item_id: String, user_id: i32, state: tauri::State<Database>) -> Result<Vec<Attachment>, String> {
+    with_item_type!(kind, |T| state.get_attachments::<T>(&item_id, user_id))
+}
+
+#[tauri::command(async)]
+pub fn save_attachment(attachment_id: String, destination_path: String, user_id: i32, state: tauri::State<Database>) -> Result<(), String> {
+    state.save_attachment(&attachment_id, Path::new(&destination_path), user_id)
+}
+
+#[tauri::command]
+pub fn delete_attachment(attachment_id: String, user_id: i32, state: tauri::State<Database>) -> Result<(), String> {
+    state.delete_attachment(&attachment_id, user_id)
+}
@@ -1,5 +1,8 @@
+use tauri::Emitter;
+
 use crate::auth::Database;
-use crate::models::UserResponse;
+use crate::db::expirations::{DEFAULT_EXPIRY_WINDOW_DAYS, EXPIRATIONS_EVENT};
+use crate::models::{TwoFactorEnrollment, UserResponse};
 
 #[tauri::command]
 pub fn login(username: String, password: String, master_key: String, state: tauri::State<Database>) -> Result<UserResponse, String> {
@@ -8,14 +11,24 @@ pub fn login(username: String, password: String, master_key: String, state: taur
 }
 
 #[tauri::command]
-pub fn register(username: String, password: String, master_key: String, state: tauri::State<Database>) -> Result<UserResponse, String> {
-    let user = state.register(&username, &password, &master_key)?;
-    Ok(user.into())
+pub fn register(username: String, password: String, master_key: String, create_recovery_key: Option<bool>, state: tauri::State<Database>) -> Result<UserResponse, String> {
+    let (user, recovery_key) = state.register(&username, &password, &master_key, create_recovery_key.unwrap_or(false))?;
+    let mut response: UserResponse = user.into();
+    response.recovery_key = recovery_key;
+    Ok(response)
 }
 
 #[tauri::command]
-pub fn init_session(user_id: i32, master_key: String, state: tauri::State<Database>) -> Result<(), String> {
-    state.init_session(user_id, &master_key)
+pub fn init_session(user_id: i32, master_key: String, two_factor_code: Option<String>, app: tauri::AppHandle, state: tauri::State<Database>) -> Result<(), String> {
+    state.init_session(user_id, &master_key, two_factor_code.as_deref())?;
+
+    // A failed reminder must not fail the unlock.
+    if let Ok(expirations) = state.upcoming_expirations(DEFAULT_EXPIRY_WINDOW_DAYS, user_id) {
+        if !expirations.is_empty() {
+            let _ = app.emit(EXPIRATIONS_EVENT, &expirations);
+        }
+    }
+    Ok(())
 }
 
 #[tauri::command]
@@ -24,16 +37,118 @@ pub fn logout(user_id: i32, state: tauri::State<Database>) -> Result<(), String>
     Ok(())
 }
 
+#[tauri::command]
+pub fn lock_session(user_id: i32, state: tauri::State<Database>) -> Result<(), String> {
+    state.lock_session(user_id);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn enable_quick_unlock(user_id: i32, pin: String, state: tauri::State<Database>) -> Result<(), String> {
+    state.enable_quick_unlock(user_id, &pin)
+}
+
+#[tauri::command]
+pub fn disable_quick_unlock(user_id: i32, state: tauri::State<Database>) -> Result<(), String> {
+    state.disable_quick_unlock(user_id);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn quick_unlock_available(user_id: i32, state: tauri::State<Database>) -> Result<bool, String> {
+    Ok(state.quick_unlock_available(user_id))
+}
+
+#[tauri::command]
+pub fn quick_unlock(user_id: i32, pin: String, state: tauri::State<Database>) -> Result<(), String> {
+    state.quick_unlock(user_id, &pin)
+}
+
 #[tauri::command]
 pub fn recover_password(username: String, master_key: String, new_password: String, state: tauri::State<Database>) -> Result<(), String> {
     state.recover_password(&username, &master_key, &new_password)
 }
 
+#[tauri::command]
+pub fn create_recovery_key(user_id: i32, master_key: String, state: tauri::State<Database>) -> Result<String, String> {
+    state.create_recovery_key(user_id, &master_key)
+}
+
+#[tauri::command]
+pub fn recover_with_recovery_key(username: String, recovery_key: String, new_master_key: String, new_password: String, state: tauri::State<Database>) -> Result<String, String> {
+    state.recover_with_recovery_key(&username, &recovery_key, &new_master_key, &new_password)
+}
+
+#[tauri::command]
+pub fn split_recovery_key(user_id: i32, master_key: String, threshold: u8, count: u8, state: tauri::State<Database>) -> Result<Vec<String>, String> {
+    state.split_recovery_key(user_id, &master_key, threshold, count)
+}
+
+#[tauri::command]
+pub fn recover_with_shares(username: String, shares: Vec<String>, new_master_key: String, new_password: String, state: tauri::State<Database>) -> Result<Vec<String>, String> {
+    state.recover_with_shares(&username, &shares, &new_master_key, &new_password)
+}
+
+#[tauri::command]
+pub fn create_secret_key(user_id: i32, master_key: String, state: tauri::State<Database>) -> Result<String, String> {
+    state.create_secret_key(user_id, &master_key)
+}
+
+#[tauri::command]
+pub fn rotate_secret_key(user_id: i32, master_key: String, state: tauri::State<Database>) -> Result<String, String> {
+    state.rotate_secret_key(user_id, &master_key)
+}
+
+#[tauri::command]
+pub fn export_secret_key(user_id: i32, master_key: String, state: tauri::State<Database>) -> Result<String, String> {
+    state.export_secret_key(user_id, &master_key)
+}
+
+#[tauri::command]
+pub fn import_secret_key(user_id: i32, secret_key: String, state: tauri::State<Database>) -> Result<(), String> {
+    state.import_secret_key(user_id, &secret_key)
+}
+
+#[tauri::command]
+pub fn set_duress_master_key(user_id: i32, master_key: String, duress_master_key: String, wipe_data_key: bool, state: tauri::State<Database>) -> Result<(), String> {
+    state.set_duress_master_key(user_id, &master_key, &duress_master_key, wipe_data_key)
+}
+
+#[tauri::command]
+pub fn remove_duress_master_key(user_id: i32, master_key: String, state: tauri::State<Database>) -> Result<(), String> {
+    state.remove_duress_master_key(user_id, &master_key)
+}
+
+#[tauri::command]
+pub fn duress_master_key_enabled(user_id: i32, state: tauri::State<Database>) -> Result<bool, String> {
+    state.duress_master_key_enabled(user_id)
+}
+
 #[tauri::command]
 pub fn change_password(user_id: i32, master_key: String, new_password: String, state: tauri::State<Database>) -> Result<(), String> {
     state.change_password(user_id, &master_key, &new_password)
 }
 
+#[tauri::command]
+pub fn begin_two_factor_enrollment(user_id: i32, master_key: String, state: tauri::State<Database>) -> Result<TwoFactorEnrollment, String> {
+    state.begin_two_factor_enrollment(user_id, &master_key)
+}
+
+#[tauri::command]
+pub fn confirm_two_factor(user_id: i32, code: String, state: tauri::State<Database>) -> Result<Vec<String>, String> {
+    state.confirm_two_factor(user_id, &code)
+}
+
+#[tauri::command]
+pub fn regenerate_backup_codes(user_id: i32, code: String, state: tauri::State<Database>) -> Result<Vec<String>, String> {
+    state.regenerate_backup_codes(user_id, &code)
+}
+
+#[tauri::command]
+pub fn disable_two_factor(user_id: i32, code: String, state: tauri::State<Database>) -> Result<(), String> {
+    state.disable_two_factor(user_id, &code)
+}
+
 #[tauri::command]
 pub fn get_user_avatar(user_id: i32, state: tauri::State<Database>) -> Result<Option<String>, String> {
     state.get_user_avatar(user_id)
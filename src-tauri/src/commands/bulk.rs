@@ -0,0 +1,32 @@
+use crate::auth::Database;
+use crate::models::{BulkResult, ItemRef};
+
+#[tauri::command]
+pub fn bulk_delete_items(items: Vec<ItemRef>, user_id: i32, state: tauri::State<Database>) -> Result<BulkResult, String> {
+    state.bulk_delete(&items, user_id)
+}
+
+#[tauri::command]
+pub fn bulk_move_items(items: Vec<ItemRef>, target_vault_id: String, user_id: i32, state: tauri::State<Database>) -> Result<BulkResult, String> {
+    state.bulk_move(&items, &target_vault_id, user_id)
+}
+
+#[tauri::command]
+pub fn bulk_tag_items(items: Vec<ItemRef>, name: String, user_id: i32, state: tauri::State<Database>) -> Result<BulkResult, String> {
+    state.bulk_tag(&items, &name, user_id)
+}
+
+#[tauri::command]
+pub fn bulk_recolor_items(items: Vec<ItemRef>, color: String, user_id: i32, state: tauri::State<Database>) -> Result<BulkResult, String> {
+    state.bulk_recolor(&items, &color, user_id)
+}
+
+#[tauri::command]
+pub fn bulk_favorite_items(items: Vec<ItemRef>, favorite: bool, user_id: i32, state: tauri::State<Database>) -> Result<BulkResult, String> {
+    state.bulk_favorite(&items, favorite, user_id)
+}
+
+#[tauri::command]
+pub fn bulk_export_items(items: Vec<ItemRef>, user_id: i32, state: tauri::State<Database>) -> Result<BulkResult, String> {
+    state.bulk_export(&items, user_id)
+}
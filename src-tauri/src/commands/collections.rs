@@ -1,9 +1,12 @@
 use crate::auth::Database;
+use crate::db::sort::{sort_records, SortMode};
 use crate::models::Collection;
 
 #[tauri::command]
-pub fn get_collections(user_id: i32, state: tauri::State<Database>) -> Result<Vec<Collection>, String> {
-    state.get_collections(user_id)
+pub fn get_collections(user_id: i32, sort: Option<SortMode>, state: tauri::State<Database>) -> Result<Vec<Collection>, String> {
+    let mut collections = state.get_collections(user_id)?;
+    sort_records(&mut collections, sort.unwrap_or_default());
+    Ok(collections)
 }
 
 #[tauri::command]
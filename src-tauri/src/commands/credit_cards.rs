@@ -1,54 +1,36 @@
 use crate::auth::Database;
-use crate::models::CreditCard;
+use crate::db::sort::{sort_records, SortMode};
+use crate::images::ImageUpdate;
+use crate::models::{CreditCard, CreditCardInput};
 
 #[tauri::command]
-pub fn get_credit_cards_decrypted(vault_id: String, user_id: i32, state: tauri::State<Database>) -> Result<Vec<CreditCard>, String> {
-    state.get_credit_cards_decrypted(&vault_id, user_id)
+pub fn get_credit_cards_decrypted(vault_id: String, user_id: i32, sort: Option<SortMode>, state: tauri::State<Database>) -> Result<Vec<CreditCard>, String> {
+    let mut cards = state.list_items(&vault_id, user_id)?;
+    sort_records(&mut cards, sort.unwrap_or_default());
+    Ok(cards)
 }
 
 #[tauri::command]
 pub fn get_credit_card_with_content(card_id: String, user_id: i32, state: tauri::State<Database>) -> Result<Option<CreditCard>, String> {
-    state.get_credit_card_with_content(&card_id, user_id)
+    state.get_item(&card_id, user_id)
 }
 
 #[tauri::command]
-pub fn create_credit_card(
-    vault_id: String,
-    card_name: String,
-    holder_name: String,
-    card_number: String,
-    expiry: String,
-    cvv: String,
-    color: String,
-    image: Option<Vec<u8>>,
-    user_id: i32,
-    state: tauri::State<Database>,
-) -> Result<CreditCard, String> {
-    state.create_credit_card(&vault_id, &card_name, &holder_name, &card_number, &expiry, &cvv, &color, image.as_deref(), user_id)
+pub fn create_credit_card(vault_id: String, credit_card: CreditCardInput, color: String, image: Option<Vec<u8>>, user_id: i32, state: tauri::State<Database>) -> Result<CreditCard, String> {
+    state.create_item(&vault_id, &credit_card.into(), &color, image.as_deref(), user_id)
 }
 
 #[tauri::command]
-pub fn update_credit_card(
-    card_id: String,
-    card_name: String,
-    holder_name: String,
-    card_number: String,
-    expiry: String,
-    cvv: String,
-    color: String,
-    image: Option<Vec<u8>>,
-    user_id: i32,
-    state: tauri::State<Database>,
-) -> Result<(), String> {
-    state.update_credit_card(&card_id, &card_name, &holder_name, &card_number, &expiry, &cvv, &color, image.as_deref(), user_id)
+pub fn update_credit_card(card_id: String, credit_card: CreditCardInput, color: String, image: Option<Vec<u8>>, keep_image: Option<bool>, user_id: i32, state: tauri::State<Database>) -> Result<(), String> {
+    state.update_item::<CreditCard>(&card_id, &credit_card.into(), &color, ImageUpdate::from_request(image.as_deref(), keep_image.unwrap_or(false)), user_id)
 }
 
 #[tauri::command]
-pub fn update_credit_card_position(card_id: String, new_position: i32, state: tauri::State<Database>) -> Result<(), String> {
-    state.update_credit_card_position(&card_id, new_position)
+pub fn update_credit_card_position(card_id: String, new_position: i32, user_id: i32, state: tauri::State<Database>) -> Result<(), String> {
+    state.update_item_position::<CreditCard>(&card_id, new_position, user_id)
 }
 
 #[tauri::command]
-pub fn delete_credit_card(card_id: String, state: tauri::State<Database>) -> Result<(), String> {
-    state.delete_credit_card(&card_id)
-}
\ No newline at end of file
+pub fn delete_credit_card(card_id: String, user_id: i32, state: tauri::State<Database>) -> Result<(), String> {
+    state.delete_item::<CreditCard>(&card_id, user_id)
+}
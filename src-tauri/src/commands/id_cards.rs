@@ -1,52 +1,36 @@
 use crate::auth::Database;
-use crate::models::IdCard;
+use crate::db::sort::{sort_records, SortMode};
+use crate::images::ImageUpdate;
+use crate::models::{IdCard, IdCardInput};
 
 #[tauri::command]
-pub fn get_id_cards_decrypted(vault_id: String, user_id: i32, state: tauri::State<Database>) -> Result<Vec<IdCard>, String> {
-    state.get_id_cards_decrypted(&vault_id, user_id)
+pub fn get_id_cards_decrypted(vault_id: String, user_id: i32, sort: Option<SortMode>, state: tauri::State<Database>) -> Result<Vec<IdCard>, String> {
+    let mut cards = state.list_items(&vault_id, user_id)?;
+    sort_records(&mut cards, sort.unwrap_or_default());
+    Ok(cards)
 }
 
 #[tauri::command]
 pub fn get_id_card_with_content(card_id: String, user_id: i32, state: tauri::State<Database>) -> Result<Option<IdCard>, String> {
-    state.get_id_card_with_content(&card_id, user_id)
+    state.get_item(&card_id, user_id)
 }
 
 #[tauri::command]
-pub fn create_id_card(
-    vault_id: String,
-    id_name: String,
-    id_type: String,
-    full_name: String,
-    id_number: String,
-    color: String,
-    image: Option<Vec<u8>>,
-    user_id: i32,
-    state: tauri::State<Database>,
-) -> Result<IdCard, String> {
-    state.create_id_card(&vault_id, &id_name, &id_type, &full_name, &id_number, &color, image.as_deref(), user_id)
+pub fn create_id_card(vault_id: String, id_card: IdCardInput, color: String, image: Option<Vec<u8>>, user_id: i32, state: tauri::State<Database>) -> Result<IdCard, String> {
+    state.create_item(&vault_id, &id_card.into(), &color, image.as_deref(), user_id)
 }
 
 #[tauri::command]
-pub fn update_id_card(
-    card_id: String,
-    id_name: String,
-    id_type: String,
-    full_name: String,
-    id_number: String,
-    color: String,
-    image: Option<Vec<u8>>,
-    user_id: i32,
-    state: tauri::State<Database>,
-) -> Result<(), String> {
-    state.update_id_card(&card_id, &id_name, &id_type, &full_name, &id_number, &color, image.as_deref(), user_id)
+pub fn update_id_card(card_id: String, id_card: IdCardInput, color: String, image: Option<Vec<u8>>, keep_image: Option<bool>, user_id: i32, state: tauri::State<Database>) -> Result<(), String> {
+    state.update_item::<IdCard>(&card_id, &id_card.into(), &color, ImageUpdate::from_request(image.as_deref(), keep_image.unwrap_or(false)), user_id)
 }
 
 #[tauri::command]
-pub fn update_id_card_position(card_id: String, new_position: i32, state: tauri::State<Database>) -> Result<(), String> {
-    state.update_id_card_position(&card_id, new_position)
+pub fn update_id_card_position(card_id: String, new_position: i32, user_id: i32, state: tauri::State<Database>) -> Result<(), String> {
+    state.update_item_position::<IdCard>(&card_id, new_position, user_id)
 }
 
 #[tauri::command]
-pub fn delete_id_card(card_id: String, state: tauri::State<Database>) -> Result<(), String> {
-    state.delete_id_card(&card_id)
+pub fn delete_id_card(card_id: String, user_id: i32, state: tauri::State<Database>) -> Result<(), String> {
+    state.delete_item::<IdCard>(&card_id, user_id)
 }
@@ -0,0 +1,40 @@
+use tauri::http::{header, Request, Response, StatusCode};
+use tauri::{Manager, Runtime, UriSchemeContext};
+
+use crate::auth::Database;
+use crate::images::mime_type;
+
+/// Serves `vaultimg://localhost/<blob id>[/thumbnail]`, decrypting the image on
+/// demand. Blob ids name their content, so responses can be cached for good.
+pub fn handle_image_request<R: Runtime>(ctx: UriSchemeContext<'_, R>, request: Request<Vec<u8>>) -> Response<Vec<u8>> {
+    let state = ctx.app_handle().state::<Database>();
+
+    let mut segments = request.uri().path().trim_start_matches('/').split('/');
+    let (blob_id, thumbnail) = match (segments.next(), segments.next(), segments.next()) {
+        (Some(id), None, None) if !id.is_empty() => (id, false),
+        (Some(id), Some("thumbnail"), None) if !id.is_empty() => (id, true),
+        _ => return empty_response(StatusCode::BAD_REQUEST),
+    };
+
+    match state.get_image(blob_id, thumbnail) {
+        Ok(Some(bytes)) => {
+            Response::builder()
+                .status(StatusCode::OK)
+                .header(header::CONTENT_TYPE, mime_type(&bytes))
+                .header(header::CACHE_CONTROL, "private, max-age=31536000, immutable")
+                .header("X-Content-Type-Options", "nosniff")
+                .header(header::CONTENT_SECURITY_POLICY, "default-src 'none'; style-src 'unsafe-inline'; sandbox")
+                .header(header::ETAG, format!("\"{}\"", blob_id))
+                .body(bytes)
+                .unwrap_or_else(|_| empty_response(StatusCode::INTERNAL_SERVER_ERROR))
+        }
+        Ok(None) => empty_response(StatusCode::NOT_FOUND),
+        Err(_) => empty_response(StatusCode::FORBIDDEN),
+    }
+}
+
+fn empty_response(status: StatusCode) -> Response<Vec<u8>> {
+    let mut response = Response::new(Vec::new());
+    *response.status_mut() = status;
+    response
+}
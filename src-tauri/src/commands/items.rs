@@ -0,0 +1,106 @@
+use crate::auth::Database;
+use crate::db::items::{with_item_type, Item, ItemFields, ItemKind, VaultItem};
+use crate::db::sort::{sort_records, SortMode};
+use crate::images::ImageUpdate;
+use crate::models::{CustomField, Expiration, ItemRef, ItemSummary, NewCustomField};
+
+#[tauri::command]
+pub fn get_items(kind: ItemKind, vault_id: String, user_id: i32, sort: Option<SortMode>, state: tauri::State<Database>) -> Result<Vec<Item>, String> {
+    with_item_type!(kind, |T| {
+        let mut items = state.list_items::<T>(&vault_id, user_id)?;
+        sort_records(&mut items, sort.unwrap_or_default());
+        Ok(items.into_iter().map(T::into_item).collect())
+    })
+}
+
+#[tauri::command]
+pub fn get_item(kind: ItemKind, item_id: String, user_id: i32, state: tauri::State<Database>) -> Result<Option<Item>, String> {
+    with_item_type!(kind, |T| Ok(state.get_item::<T>(&item_id, user_id)?.map(T::into_item)))
+}
+
+#[tauri::command]
+pub fn create_item(
+    kind: ItemKind,
+    vault_id: String,
+    fields: ItemFields,
+    color: String,
+    image: Option<Vec<u8>>,
+    user_id: i32,
+    state: tauri::State<Database>,
+) -> Result<Item, String> {
+    with_item_type!(kind, |T| {
+        let item = state.create_item::<T>(&vault_id, &fields, &color, image.as_deref(), user_id)?;
+        Ok(item.into_item())
+    })
+}
+
+#[tauri::command]
+pub fn update_item(
+    item: ItemRef,
+    fields: ItemFields,
+    color: String,
+    image: Option<Vec<u8>>,
+    keep_image: Option<bool>,
+    user_id: i32,
+    state: tauri::State<Database>,
+) -> Result<(), String> {
+    let image = ImageUpdate::from_request(image.as_deref(), keep_image.unwrap_or(false));
+    with_item_type!(item.kind, |T| state.update_item::<T>(&item.id, &fields, &color, image, user_id))
+}
+
+#[tauri::command]
+pub fn update_item_position(kind: ItemKind, item_id: String, new_position: i32, user_id: i32, state: tauri::State<Database>) -> Result<(), String> {
+    with_item_type!(kind, |T| state.update_item_position::<T>(&item_id, new_position, user_id))
+}
+
+#[tauri::command]
+pub fn mark_item_used(kind: ItemKind, item_id: String, user_id: i32, state: tauri::State<Database>) -> Result<(), String> {
+    with_item_type!(kind, |T| state.mark_item_used::<T>(&item_id, user_id))
+}
+
+#[tauri::command]
+pub fn delete_item(kind: ItemKind, item_id: String, user_id: i32, state: tauri::State<Database>) -> Result<(), String> {
+    with_item_type!(kind, |T| state.delete_item::<T>(&item_id, user_id))
+}
+
+#[tauri::command]
+pub fn duplicate_item(kind: ItemKind, item_id: String, user_id: i32, state: tauri::State<Database>) -> Result<Item, String> {
+    with_item_type!(kind, |T| Ok(state.duplicate_item::<T>(&item_id, user_id)?.into_item()))
+}
+
+#[tauri::command]
+pub fn move_items(items: Vec<ItemRef>, target_vault_id: String, user_id: i32, state: tauri::State<Database>) -> Result<(), String> {
+    state.move_items(&items, &target_vault_id, user_id)
+}
+
+#[tauri::command]
+pub fn copy_items(items: Vec<ItemRef>, target_vault_id: String, user_id: i32, state: tauri::State<Database>) -> Result<Vec<Item>, String> {
+    state.copy_items(&items, &target_vault_id, user_id)
+}
+
+#[tauri::command]
+pub fn set_custom_fields(kind: ItemKind, item_id: String, fields: Vec<NewCustomField>, user_id: i32, state: tauri::State<Database>) -> Result<Vec<CustomField>, String> {
+    with_item_type!(kind, |T| state.set_custom_fields::<T>(&item_id, &fields, user_id))
+}
+
+#[tauri::command]
+pub fn set_favorite(kind: ItemKind, item_id: String, favorite: bool, user_id: i32, state: tauri::State<Database>) -> Result<(), String> {
+    with_item_type!(kind, |T| state.set_favorite::<T>(&item_id, favorite, user_id))
+}
+
+#[tauri::command]
+pub fn update_favorite_position(kind: ItemKind, item_id: String, new_position: i32, user_id: i32, state: tauri::State<Database>) -> Result<(), String> {
+    with_item_type!(kind, |T| state.update_favorite_position::<T>(&item_id, new_position, user_id))
+}
+
+#[tauri::command]
+pub fn upcoming_expirations(days: i64, user_id: i32, state: tauri::State<Database>) -> Result<Vec<Expiration>, String> {
+    state.upcoming_expirations(days, user_id)
+}
+
+#[tauri::command]
+pub fn get_favorites(user_id: i32, sort: Option<SortMode>, state: tauri::State<Database>) -> Result<Vec<ItemSummary>, String> {
+    let mut favorites = state.get_favorites(user_id)?;
+    sort_records(&mut favorites, sort.unwrap_or_default());
+    Ok(favorites)
+}
@@ -1,74 +1,42 @@
 use crate::auth::Database;
-use crate::models::LoginKey;
+use crate::db::sort::{sort_records, SortMode};
+use crate::images::ImageUpdate;
+use crate::models::{LoginKey, LoginKeyInput};
+use crate::totp::OtpCode;
 
 #[tauri::command]
-pub fn get_login_keys_decrypted(vault_id: String, user_id: i32, state: tauri::State<Database>) -> Result<Vec<LoginKey>, String> {
-    state.get_login_keys_decrypted(&vault_id, user_id)
+pub fn get_login_keys_decrypted(vault_id: String, user_id: i32, sort: Option<SortMode>, state: tauri::State<Database>) -> Result<Vec<LoginKey>, String> {
+    let mut login_keys = state.list_items(&vault_id, user_id)?;
+    sort_records(&mut login_keys, sort.unwrap_or_default());
+    Ok(login_keys)
 }
 
 #[tauri::command]
 pub fn get_login_key_with_content(login_key_id: String, user_id: i32, state: tauri::State<Database>) -> Result<Option<LoginKey>, String> {
-    state.get_login_key_with_content(&login_key_id, user_id)
+    state.get_item(&login_key_id, user_id)
 }
 
 #[tauri::command]
-pub fn create_login_key(
-    vault_id: String,
-    site_name: String,
-    url: Option<String>,
-    username: String,
-    password: String,
-    details: Option<String>,
-    color: String,
-    image: Option<Vec<u8>>,
-    user_id: i32,
-    state: tauri::State<Database>
-) -> Result<LoginKey, String> {
-    state.create_login_key(
-        &vault_id,
-        &site_name,
-        url.as_deref(),
-        &username,
-        &password,
-        details.as_deref(),
-        &color,
-        image.as_deref(),
-        user_id
-    )
+pub fn create_login_key(vault_id: String, login_key: LoginKeyInput, color: String, image: Option<Vec<u8>>, user_id: i32, state: tauri::State<Database>) -> Result<LoginKey, String> {
+    state.create_item(&vault_id, &login_key.into(), &color, image.as_deref(), user_id)
 }
 
 #[tauri::command]
-pub fn update_login_key(
-    login_key_id: String,
-    site_name: String,
-    url: Option<String>,
-    username: String,
-    password: String,
-    details: Option<String>,
-    color: String,
-    image: Option<Vec<u8>>,
-    user_id: i32,
-    state: tauri::State<Database>
-) -> Result<(), String> {
-    state.update_login_key(
-        &login_key_id,
-        &site_name,
-        url.as_deref(),
-        &username,
-        &password,
-        details.as_deref(),
-        &color,
-        image.as_deref(),
-        user_id
-    )
+pub fn update_login_key(login_key_id: String, login_key: LoginKeyInput, color: String, image: Option<Vec<u8>>, keep_image: Option<bool>, user_id: i32, state: tauri::State<Database>) -> Result<(), String> {
+    state.update_item::<LoginKey>(&login_key_id, &login_key.into(), &color, ImageUpdate::from_request(image.as_deref(), keep_image.unwrap_or(false)), user_id)
 }
 
 #[tauri::command]
-pub fn update_login_key_position(login_key_id: String, new_position: i32, state: tauri::State<Database>) -> Result<(), String> {
-    state.update_login_key_position(&login_key_id, new_position)
+pub fn get_totp_code(login_key_id: String, user_id: i32, state: tauri::State<Database>) -> Result<OtpCode, String> {
+    state.get_totp_code(&login_key_id, user_id)
 }
 
 #[tauri::command]
-pub fn delete_login_key(login_key_id: String, state: tauri::State<Database>) -> Result<(), String> {
-    state.delete_login_key(&login_key_id)
+pub fn update_login_key_position(login_key_id: String, new_position: i32, user_id: i32, state: tauri::State<Database>) -> Result<(), String> {
+    state.update_item_position::<LoginKey>(&login_key_id, new_position, user_id)
+}
+
+#[tauri::command]
+pub fn delete_login_key(login_key_id: String, user_id: i32, state: tauri::State<Database>) -> Result<(), String> {
+    state.delete_item::<LoginKey>(&login_key_id, user_id)
 }
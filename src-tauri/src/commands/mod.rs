@@ -5,8 +5,17 @@ pub mod id_cards;
 pub mod credit_cards;
 pub mod login_keys;
 pub mod notes;
+pub mod items;
+pub mod bulk;
+pub mod attachments;
+pub mod images;
+pub mod tags;
+pub mod search;
+pub mod reorder;
+pub mod templates;
 
 use crate::auth::Database;
+use crate::images::IMAGE_SCHEME;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
@@ -14,13 +23,34 @@ pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .manage(db)
+        .register_uri_scheme_protocol(IMAGE_SCHEME, images::handle_image_request)
         .invoke_handler(tauri::generate_handler![
             auth::login,
             auth::register,
             auth::init_session,
             auth::logout,
+            auth::lock_session,
+            auth::enable_quick_unlock,
+            auth::disable_quick_unlock,
+            auth::quick_unlock_available,
+            auth::quick_unlock,
             auth::recover_password,
+            auth::create_recovery_key,
+            auth::recover_with_recovery_key,
+            auth::split_recovery_key,
+            auth::recover_with_shares,
+            auth::create_secret_key,
+            auth::rotate_secret_key,
+            auth::export_secret_key,
+            auth::import_secret_key,
+            auth::set_duress_master_key,
+            auth::remove_duress_master_key,
+            auth::duress_master_key_enabled,
             auth::change_password,
+            auth::begin_two_factor_enrollment,
+            auth::confirm_two_factor,
+            auth::regenerate_backup_codes,
+            auth::disable_two_factor,
             auth::get_user_avatar,
             auth::update_avatar,
             auth::delete_user,
@@ -34,6 +64,12 @@ pub fn run() {
             vaults::get_vault,
             vaults::create_vault,
             vaults::update_vault,
+            vaults::set_vault_full_text_search,
+            vaults::set_vault_passphrase,
+            vaults::remove_vault_passphrase,
+            vaults::unlock_vault,
+            vaults::lock_vault,
+            vaults::vault_unlocked_until,
             vaults::update_vault_position,
             vaults::delete_vault,
             id_cards::get_id_cards_decrypted,
@@ -52,6 +88,7 @@ pub fn run() {
             login_keys::get_login_key_with_content,
             login_keys::create_login_key,
             login_keys::update_login_key,
+            login_keys::get_totp_code,
             login_keys::update_login_key_position,
             login_keys::delete_login_key,
             notes::get_notes_decrypted,
@@ -60,6 +97,45 @@ pub fn run() {
             notes::update_note,
             notes::update_note_position,
             notes::delete_note,
+            items::get_items,
+            items::get_item,
+            items::create_item,
+            items::update_item,
+            items::update_item_position,
+            items::mark_item_used,
+            items::delete_item,
+            items::duplicate_item,
+            items::move_items,
+            items::copy_items,
+            items::set_custom_fields,
+            items::set_favorite,
+            items::update_favorite_position,
+            items::upcoming_expirations,
+            items::get_favorites,
+            reorder::reorder,
+            templates::create_item_template,
+            templates::get_item_templates,
+            templates::update_item_template,
+            templates::delete_item_template,
+            templates::create_item_from_template,
+            bulk::bulk_delete_items,
+            bulk::bulk_move_items,
+            bulk::bulk_tag_items,
+            bulk::bulk_recolor_items,
+            bulk::bulk_favorite_items,
+            bulk::bulk_export_items,
+            attachments::add_attachment,
+            attachments::get_attachments,
+            attachments::save_attachment,
+            attachments::delete_attachment,
+            tags::add_tag,
+            tags::remove_tag,
+            tags::rename_tag,
+            tags::delete_tag,
+            tags::get_tags,
+            tags::get_items_by_tags,
+            search::search,
+            search::search_full_text,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -1,32 +1,36 @@
 use crate::auth::Database;
-use crate::models::Note;
+use crate::db::sort::{sort_records, SortMode};
+use crate::images::ImageUpdate;
+use crate::models::{Note, NoteInput};
 
 #[tauri::command]
-pub fn get_notes_decrypted(vault_id: String, user_id: i32, state: tauri::State<Database>) -> Result<Vec<Note>, String> {
-    state.get_notes_decrypted(&vault_id, user_id)
+pub fn get_notes_decrypted(vault_id: String, user_id: i32, sort: Option<SortMode>, state: tauri::State<Database>) -> Result<Vec<Note>, String> {
+    let mut notes = state.list_items(&vault_id, user_id)?;
+    sort_records(&mut notes, sort.unwrap_or_default());
+    Ok(notes)
 }
 
 #[tauri::command]
 pub fn get_note_with_content(note_id: String, user_id: i32, state: tauri::State<Database>) -> Result<Option<Note>, String> {
-    state.get_note_with_content(&note_id, user_id)
+    state.get_item(&note_id, user_id)
 }
 
 #[tauri::command]
-pub fn create_note(vault_id: String, title: String, content: String, color: String, image: Option<Vec<u8>>, user_id: i32, state: tauri::State<Database>) -> Result<Note, String> {
-    state.create_note(&vault_id, &title, &content, &color, image.as_deref(), user_id)
+pub fn create_note(vault_id: String, note: NoteInput, color: String, image: Option<Vec<u8>>, user_id: i32, state: tauri::State<Database>) -> Result<Note, String> {
+    state.create_item(&vault_id, &note.into(), &color, image.as_deref(), user_id)
 }
 
 #[tauri::command]
-pub fn update_note(note_id: String, title: String, content: String, color: String, image: Option<Vec<u8>>, user_id: i32, state: tauri::State<Database>) -> Result<(), String> {
-    state.update_note(&note_id, &title, &content, &color, image.as_deref(), user_id)
+pub fn update_note(note_id: String, note: NoteInput, color: String, image: Option<Vec<u8>>, keep_image: Option<bool>, user_id: i32, state: tauri::State<Database>) -> Result<(), String> {
+    state.update_item::<Note>(&note_id, &note.into(), &color, ImageUpdate::from_request(image.as_deref(), keep_image.unwrap_or(false)), user_id)
 }
 
 #[tauri::command]
-pub fn update_note_position(note_id: String, new_position: i32, state: tauri::State<Database>) -> Result<(), String> {
-    state.update_note_position(&note_id, new_position)
+pub fn update_note_position(note_id: String, new_position: i32, user_id: i32, state: tauri::State<Database>) -> Result<(), String> {
+    state.update_item_position::<Note>(&note_id, new_position, user_id)
 }
 
 #[tauri::command]
-pub fn delete_note(note_id: String, state: tauri::State<Database>) -> Result<(), String> {
-    state.delete_note(&note_id)
+pub fn delete_note(note_id: String, user_id: i32, state: tauri::State<Database>) -> Result<(), String> {
+    state.delete_item::<Note>(&note_id, user_id)
 }
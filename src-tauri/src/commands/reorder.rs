@@ -0,0 +1,7 @@
+use crate::auth::Database;
+use crate::db::reorder::ReorderKind;
+
+#[tauri::command]
+pub fn reorder(kind: ReorderKind, parent_id: Option<String>, ordered_ids: Vec<String>, user_id: i32, state: tauri::State<Database>) -> Result<(), String> {
+    state.reorder(kind, parent_id.as_deref(), &ordered_ids, user_id)
+}
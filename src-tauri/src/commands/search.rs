@@ -0,0 +1,16 @@
+use crate::auth::Database;
+use crate::db::sort::{sort_records, SortMode};
+use crate::models::{ItemSummary, SearchHit};
+use crate::search::DEFAULT_SEARCH_LIMIT;
+
+#[tauri::command]
+pub fn search(query: String, limit: Option<usize>, user_id: i32, state: tauri::State<Database>) -> Result<Vec<SearchHit>, String> {
+    state.search(&query, limit.unwrap_or(DEFAULT_SEARCH_LIMIT), user_id)
+}
+
+#[tauri::command]
+pub fn search_full_text(query: String, user_id: i32, sort: Option<SortMode>, state: tauri::State<Database>) -> Result<Vec<ItemSummary>, String> {
+    let mut results = state.search_full_text(&query, user_id)?;
+    sort_records(&mut results, sort.unwrap_or_default());
+    Ok(results)
+}
@@ -0,0 +1,36 @@
+use crate::auth::Database;
+use crate::db::items::{with_item_type, Item, ItemKind};
+use crate::db::sort::{sort_records, SortMode};
+use crate::models::{Tag, TagCount};
+
+#[tauri::command]
+pub fn add_tag(kind: ItemKind, item_id: String, name: String, user_id: i32, state: tauri::State<Database>) -> Result<Tag, String> {
+    with_item_type!(kind, |T| state.add_tag::<T>(&item_id, &name, user_id))
+}
+
+#[tauri::command]
+pub fn remove_tag(kind: ItemKind, item_id: String, tag_id: String, user_id: i32, state: tauri::State<Database>) -> Result<(), String> {
+    with_item_type!(kind, |T| state.remove_tag::<T>(&item_id, &tag_id, user_id))
+}
+
+#[tauri::command]
+pub fn rename_tag(tag_id: String, name: String, user_id: i32, state: tauri::State<Database>) -> Result<Tag, String> {
+    state.rename_tag(&tag_id, &name, user_id)
+}
+
+#[tauri::command]
+pub fn delete_tag(tag_id: String, user_id: i32, state: tauri::State<Database>) -> Result<(), String> {
+    state.delete_tag(&tag_id, user_id)
+}
+
+#[tauri::command]
+pub fn get_tags(user_id: i32, state: tauri::State<Database>) -> Result<Vec<TagCount>, String> {
+    state.get_tags(user_id)
+}
+
+#[tauri::command]
+pub fn get_items_by_tags(tag_ids: Vec<String>, match_all: Option<bool>, user_id: i32, sort: Option<SortMode>, state: tauri::State<Database>) -> Result<Vec<Item>, String> {
+    let mut items = state.get_items_by_tags(&tag_ids, match_all.unwrap_or(false), user_id)?;
+    sort_records(&mut items, sort.unwrap_or_default());
+    Ok(items)
+}
@@ -0,0 +1,28 @@
+use crate::auth::Database;
+use crate::db::items::{with_item_type, Item, ItemFields, ItemKind};
+use crate::models::ItemTemplate;
+
+#[tauri::command]
+pub fn create_item_template(kind: ItemKind, name: String, fields: ItemFields, color: String, user_id: i32, state: tauri::State<Database>) -> Result<ItemTemplate, String> {
+    with_item_type!(kind, |T| state.create_item_template::<T>(&name, &fields, &color, user_id))
+}
+
+#[tauri::command]
+pub fn get_item_templates(kind: Option<ItemKind>, user_id: i32, state: tauri::State<Database>) -> Result<Vec<ItemTemplate>, String> {
+    state.get_item_templates(kind, user_id)
+}
+
+#[tauri::command]
+pub fn update_item_template(template_id: String, name: String, fields: ItemFields, color: String, user_id: i32, state: tauri::State<Database>) -> Result<(), String> {
+    state.update_item_template(&template_id, &name, &fields, &color, user_id)
+}
+
+#[tauri::command]
+pub fn delete_item_template(template_id: String, user_id: i32, state: tauri::State<Database>) -> Result<(), String> {
+    state.delete_item_template(&template_id, user_id)
+}
+
+#[tauri::command]
+pub fn create_item_from_template(template_id: String, vault_id: String, fields: Option<ItemFields>, user_id: i32, state: tauri::State<Database>) -> Result<Item, String> {
+    state.create_item_from_template(&template_id, &vault_id, fields.unwrap_or_default(), user_id)
+}
@@ -1,9 +1,13 @@
 use crate::auth::Database;
+use crate::db::sort::{sort_records, SortMode};
+use crate::images::ImageUpdate;
 use crate::models::Vault;
 
 #[tauri::command]
-pub fn get_vaults(user_id: i32, state: tauri::State<Database>) -> Result<Vec<Vault>, String> {
-    state.get_vaults(user_id)
+pub fn get_vaults(user_id: i32, sort: Option<SortMode>, state: tauri::State<Database>) -> Result<Vec<Vault>, String> {
+    let mut vaults = state.get_vaults(user_id)?;
+    sort_records(&mut vaults, sort.unwrap_or_default());
+    Ok(vaults)
 }
 
 #[tauri::command]
@@ -17,14 +21,44 @@ pub fn create_vault(user_id: i32, name: String, color: String, image: Option<Vec
 }
 
 #[tauri::command]
-pub fn update_vault(vault: String, name: String, color: String, image: Option<Vec<u8>>, state: tauri::State<Database>) -> Result<(), String> {
+pub fn update_vault(vault: String, name: String, color: String, image: Option<Vec<u8>>, keep_image: Option<bool>, state: tauri::State<Database>) -> Result<(), String> {
     let vault_model: Vault = serde_json::from_str(&vault).map_err(|e| e.to_string())?;
     let updated_vault = Vault {
         name,
         color,
         ..vault_model
     };
-    state.update_vault(&updated_vault, image.as_deref())
+    state.update_vault(&updated_vault, ImageUpdate::from_request(image.as_deref(), keep_image.unwrap_or(false)))
+}
+
+#[tauri::command]
+pub fn set_vault_full_text_search(vault_id: String, enabled: bool, user_id: i32, state: tauri::State<Database>) -> Result<(), String> {
+    state.set_vault_full_text_search(&vault_id, enabled, user_id)
+}
+
+#[tauri::command]
+pub fn set_vault_passphrase(vault_id: String, passphrase: String, user_id: i32, state: tauri::State<Database>) -> Result<(), String> {
+    state.set_vault_passphrase(&vault_id, &passphrase, user_id)
+}
+
+#[tauri::command]
+pub fn remove_vault_passphrase(vault_id: String, passphrase: String, user_id: i32, state: tauri::State<Database>) -> Result<(), String> {
+    state.remove_vault_passphrase(&vault_id, &passphrase, user_id)
+}
+
+#[tauri::command]
+pub fn unlock_vault(vault_id: String, passphrase: String, user_id: i32, state: tauri::State<Database>) -> Result<i64, String> {
+    state.unlock_vault(&vault_id, &passphrase, user_id)
+}
+
+#[tauri::command]
+pub fn lock_vault(vault_id: String, user_id: i32, state: tauri::State<Database>) -> Result<(), String> {
+    state.lock_vault(&vault_id, user_id)
+}
+
+#[tauri::command]
+pub fn vault_unlocked_until(vault_id: String, user_id: i32, state: tauri::State<Database>) -> Result<Option<i64>, String> {
+    Ok(state.vault_unlocked_until(&vault_id, user_id))
 }
 
 #[tauri::command]
@@ -33,6 +67,6 @@ pub fn update_vault_position(vault_id: String, new_position: i32, state: tauri::
 }
 
 #[tauri::command]
-pub fn delete_vault(vault_id: String, state: tauri::State<Database>) -> Result<(), String> {
-    state.delete_vault(&vault_id)
+pub fn delete_vault(vault_id: String, user_id: i32, state: tauri::State<Database>) -> Result<(), String> {
+    state.delete_vault(&vault_id, user_id)
 }
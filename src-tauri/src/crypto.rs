@@ -1,15 +1,25 @@
 use argon2::{Argon2, Params};
-use aes_gcm::{Aes256Gcm, aead::{Aead, KeyInit}};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use aes_gcm::{Aes256Gcm, aead::{Aead, KeyInit, Payload, stream::{DecryptorBE32, EncryptorBE32}}};
 use generic_array::GenericArray;
 use typenum::U32;
 use rand::Rng;
 use base64::{Engine as _, engine::general_purpose::STANDARD};
 use std::fmt;
+use std::io::{Read, Write};
+use zeroize::Zeroizing;
 
 pub const KEY_LENGTH: usize = 32;
 pub const NONCE_LENGTH: usize = 12;
+pub const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+
+const STREAM_MAGIC: &[u8; 4] = b"NCS1";
+const STREAM_NONCE_PREFIX_LENGTH: usize = 7;
+const TAG_LENGTH: usize = 16;
 
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum CryptoError {
     EncryptionFailed(String),
     DecryptionFailed(String),
@@ -51,6 +61,24 @@ pub fn derive_encryption_key(master_key: &str, salt: &[u8]) -> Result<GenericArr
     Ok(key)
 }
 
+/// Hex encoded HMAC-SHA256 of `data`. `domain` keeps hashes made with the same
+/// key for different purposes apart.
+pub fn keyed_hash(key: &GenericArray<u8, U32>, domain: &[u8], data: &[u8]) -> String {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(domain);
+    mac.update(&[0]);
+    mac.update(data);
+    mac.finalize().into_bytes().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Key for `domain` from a secret that is already random, like a recovery
+/// key, which needs no slow derivation to resist guessing.
+pub fn derive_key_from_secret(secret: &[u8], domain: &[u8]) -> GenericArray<u8, U32> {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(domain);
+    mac.finalize().into_bytes()
+}
+
 pub fn generate_nonce() -> [u8; NONCE_LENGTH] {
     let mut nonce = [0u8; NONCE_LENGTH];
     rand::thread_rng().fill(&mut nonce);
@@ -72,14 +100,14 @@ pub fn decrypt(encrypted: &[u8], key: &GenericArray<u8, U32>, nonce: &[u8; NONCE
         aes_gcm::Nonce::from_slice(nonce),
         encrypted
     ).map_err(|e| CryptoError::DecryptionFailed(e.to_string()))?;
-    Ok(String::from_utf8(plaintext).map_err(|e| CryptoError::DecryptionFailed(e.to_string()))?)
+    String::from_utf8(plaintext).map_err(|e| CryptoError::DecryptionFailed(e.to_string()))
 }
 
 pub fn encrypt_to_base64(content: &str, key: &GenericArray<u8, U32>) -> Result<(String, String), CryptoError> {
     let nonce = generate_nonce();
     let encrypted = encrypt(content, key, &nonce)?;
     let encrypted_b64 = STANDARD.encode(&encrypted);
-    let nonce_b64 = STANDARD.encode(&nonce);
+    let nonce_b64 = STANDARD.encode(nonce);
     Ok((encrypted_b64, nonce_b64))
 }
 
@@ -119,7 +147,7 @@ pub fn encrypt_bytes_to_base64(content: &[u8], key: &GenericArray<u8, U32>) -> R
     let nonce = generate_nonce();
     let encrypted = encrypt_bytes(content, key, &nonce)?;
     let encrypted_b64 = STANDARD.encode(&encrypted);
-    let nonce_b64 = STANDARD.encode(&nonce);
+    let nonce_b64 = STANDARD.encode(nonce);
     Ok((encrypted_b64, nonce_b64))
 }
 
@@ -136,3 +164,279 @@ pub fn decrypt_bytes_from_base64(encrypted_b64: &str, nonce_b64: &str, key: &Gen
     
     decrypt_bytes(&encrypted, key, &nonce)
 }
+
+/// Encrypts `content` into a single buffer laid out as `nonce || ciphertext`,
+/// suitable for storing in a BLOB column.
+pub fn encrypt_bytes_to_blob(content: &[u8], key: &GenericArray<u8, U32>) -> Result<Vec<u8>, CryptoError> {
+    let nonce = generate_nonce();
+    let encrypted = encrypt_bytes(content, key, &nonce)?;
+    let mut blob = Vec::with_capacity(NONCE_LENGTH + encrypted.len());
+    blob.extend_from_slice(&nonce);
+    blob.extend_from_slice(&encrypted);
+    Ok(blob)
+}
+
+pub fn decrypt_bytes_from_blob(blob: &[u8], key: &GenericArray<u8, U32>) -> Result<Vec<u8>, CryptoError> {
+    if blob.len() < NONCE_LENGTH {
+        return Err(CryptoError::DecryptionFailed("Invalid blob length".to_string()));
+    }
+
+    let mut nonce = [0u8; NONCE_LENGTH];
+    nonce.copy_from_slice(&blob[..NONCE_LENGTH]);
+
+    decrypt_bytes(&blob[NONCE_LENGTH..], key, &nonce)
+}
+
+pub fn generate_key() -> GenericArray<u8, U32> {
+    let mut key = GenericArray::<u8, U32>::clone_from_slice(&[0u8; KEY_LENGTH]);
+    rand::thread_rng().fill(key.as_mut_slice());
+    key
+}
+
+pub fn key_from_bytes(bytes: &[u8]) -> Result<GenericArray<u8, U32>, CryptoError> {
+    if bytes.len() != KEY_LENGTH {
+        return Err(CryptoError::DecryptionFailed("Invalid key length".to_string()));
+    }
+    Ok(GenericArray::<u8, U32>::clone_from_slice(bytes))
+}
+
+/// Encrypts `reader` into `writer` as a STREAM of AES-256-GCM chunks of
+/// `STREAM_CHUNK_SIZE` bytes, so the plaintext never has to fit in memory.
+/// `aad` is authenticated with every chunk. Returns the plaintext length.
+pub fn encrypt_stream<R: Read, W: Write>(reader: &mut R, writer: &mut W, key: &GenericArray<u8, U32>, aad: &[u8]) -> Result<u64, CryptoError> {
+    let mut prefix = [0u8; STREAM_NONCE_PREFIX_LENGTH];
+    rand::thread_rng().fill(&mut prefix);
+
+    writer.write_all(STREAM_MAGIC).map_err(|e| CryptoError::EncryptionFailed(e.to_string()))?;
+    writer.write_all(&prefix).map_err(|e| CryptoError::EncryptionFailed(e.to_string()))?;
+
+    let mut encryptor = EncryptorBE32::<Aes256Gcm>::new(key, GenericArray::from_slice(&prefix));
+    let mut total = 0u64;
+
+    let mut current = vec![0u8; STREAM_CHUNK_SIZE];
+    let mut current_len = fill_buffer(reader, &mut current).map_err(|e| CryptoError::EncryptionFailed(e.to_string()))?;
+    loop {
+        let mut next = vec![0u8; STREAM_CHUNK_SIZE];
+        let next_len = if current_len == STREAM_CHUNK_SIZE {
+            fill_buffer(reader, &mut next).map_err(|e| CryptoError::EncryptionFailed(e.to_string()))?
+        } else {
+            0
+        };
+
+        let payload = Payload { msg: &current[..current_len], aad };
+        total += current_len as u64;
+
+        if next_len == 0 {
+            let ciphertext = encryptor.encrypt_last(payload)
+                .map_err(|e| CryptoError::EncryptionFailed(e.to_string()))?;
+            writer.write_all(&ciphertext).map_err(|e| CryptoError::EncryptionFailed(e.to_string()))?;
+            break;
+        }
+
+        let ciphertext = encryptor.encrypt_next(payload)
+            .map_err(|e| CryptoError::EncryptionFailed(e.to_string()))?;
+        writer.write_all(&ciphertext).map_err(|e| CryptoError::EncryptionFailed(e.to_string()))?;
+
+        current = next;
+        current_len = next_len;
+    }
+
+    writer.flush().map_err(|e| CryptoError::EncryptionFailed(e.to_string()))?;
+    Ok(total)
+}
+
+/// Reverses `encrypt_stream`. Fails if any chunk was modified, reordered or
+/// if the stream was truncated. Returns the plaintext length.
+pub fn decrypt_stream<R: Read, W: Write>(reader: &mut R, writer: &mut W, key: &GenericArray<u8, U32>, aad: &[u8]) -> Result<u64, CryptoError> {
+    let mut decryptor = StreamDecryptor::new(reader, key, aad)?;
+    let total = std::io::copy(&mut decryptor, writer).map_err(|e| CryptoError::DecryptionFailed(e.to_string()))?;
+    writer.flush().map_err(|e| CryptoError::DecryptionFailed(e.to_string()))?;
+    Ok(total)
+}
+
+/// Reads the plaintext of a stream from `encrypt_stream` one chunk at a
+/// time, so it can be encrypted again with `encrypt_stream` without ever
+/// holding more than a chunk. A chunk that fails to decrypt is an
+/// `InvalidData` error.
+pub struct StreamDecryptor<'a, R: Read> {
+    reader: R,
+    aad: &'a [u8],
+    decryptor: Option<DecryptorBE32<Aes256Gcm>>,
+    current: Vec<u8>,
+    current_len: usize,
+    plaintext: Zeroizing<Vec<u8>>,
+    position: usize,
+}
+
+impl<'a, R: Read> StreamDecryptor<'a, R> {
+    pub fn new(mut reader: R, key: &GenericArray<u8, U32>, aad: &'a [u8]) -> Result<Self, CryptoError> {
+        let mut header = [0u8; 4 + STREAM_NONCE_PREFIX_LENGTH];
+        reader.read_exact(&mut header).map_err(|e| CryptoError::DecryptionFailed(e.to_string()))?;
+        if &header[..4] != STREAM_MAGIC {
+            return Err(CryptoError::DecryptionFailed("Invalid stream header".to_string()));
+        }
+
+        let mut current = vec![0u8; STREAM_CHUNK_SIZE + TAG_LENGTH];
+        let current_len = fill_buffer(&mut reader, &mut current).map_err(|e| CryptoError::DecryptionFailed(e.to_string()))?;
+        Ok(StreamDecryptor {
+            reader,
+            aad,
+            decryptor: Some(DecryptorBE32::<Aes256Gcm>::new(key, GenericArray::from_slice(&header[4..]))),
+            current,
+            current_len,
+            plaintext: Zeroizing::new(Vec::new()),
+            position: 0,
+        })
+    }
+
+    /// Decrypts the next chunk into `plaintext`. A chunk is the last one
+    /// when no full chunk precedes more data, so the chunk after it is read
+    /// ahead first.
+    fn next_chunk(&mut self) -> std::io::Result<()> {
+        let Some(mut decryptor) = self.decryptor.take() else {
+            return Ok(());
+        };
+
+        let mut next = vec![0u8; self.current.len()];
+        let next_len = if self.current_len == self.current.len() {
+            fill_buffer(&mut self.reader, &mut next)?
+        } else {
+            0
+        };
+
+        let payload = Payload { msg: &self.current[..self.current_len], aad: self.aad };
+        let plaintext = if next_len == 0 {
+            decryptor.decrypt_last(payload)
+        } else {
+            let plaintext = decryptor.decrypt_next(payload);
+            self.decryptor = Some(decryptor);
+            plaintext
+        }.map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))?;
+
+        self.plaintext = Zeroizing::new(plaintext);
+        self.position = 0;
+        self.current = next;
+        self.current_len = next_len;
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for StreamDecryptor<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.position == self.plaintext.len() {
+            if self.decryptor.is_none() {
+                return Ok(0);
+            }
+            self.next_chunk()?;
+        }
+
+        let len = buf.len().min(self.plaintext.len() - self.position);
+        buf[..len].copy_from_slice(&self.plaintext[self.position..self.position + len]);
+        self.position += len;
+        Ok(len)
+    }
+}
+
+fn fill_buffer<R: Read>(reader: &mut R, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Long enough for two full chunks and a partial last one.
+    fn sample() -> Vec<u8> {
+        (0..STREAM_CHUNK_SIZE * 2 + 1000).map(|i| (i % 251) as u8).collect()
+    }
+
+    fn encrypted(plaintext: &[u8], key: &GenericArray<u8, U32>, aad: &[u8]) -> Vec<u8> {
+        let mut ciphertext = Vec::new();
+        encrypt_stream(&mut &plaintext[..], &mut ciphertext, key, aad).unwrap();
+        ciphertext
+    }
+
+    fn decrypted(ciphertext: &[u8], key: &GenericArray<u8, U32>, aad: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        let mut plaintext = Vec::new();
+        decrypt_stream(&mut &ciphertext[..], &mut plaintext, key, aad)?;
+        Ok(plaintext)
+    }
+
+    const HEADER_LENGTH: usize = STREAM_MAGIC.len() + STREAM_NONCE_PREFIX_LENGTH;
+    const CHUNK_LENGTH: usize = STREAM_CHUNK_SIZE + TAG_LENGTH;
+
+    #[test]
+    fn stream_round_trips() {
+        let key = generate_key();
+        for plaintext in [Vec::new(), b"short".to_vec(), vec![7u8; STREAM_CHUNK_SIZE], sample()] {
+            let ciphertext = encrypted(&plaintext, &key, b"id");
+            assert_eq!(decrypted(&ciphertext, &key, b"id").unwrap(), plaintext);
+        }
+    }
+
+    #[test]
+    fn stream_fails_with_another_key_or_aad() {
+        let key = generate_key();
+        let ciphertext = encrypted(&sample(), &key, b"id");
+        assert!(decrypted(&ciphertext, &generate_key(), b"id").is_err());
+        assert!(decrypted(&ciphertext, &key, b"other").is_err());
+    }
+
+    #[test]
+    fn stream_detects_truncation() {
+        let key = generate_key();
+        let ciphertext = encrypted(&sample(), &key, b"id");
+
+        // Cut at a chunk boundary, so the last chunk left was not sealed as last.
+        let whole_chunks = &ciphertext[..HEADER_LENGTH + 2 * CHUNK_LENGTH];
+        assert!(decrypted(whole_chunks, &key, b"id").is_err());
+        assert!(decrypted(&ciphertext[..ciphertext.len() - 1], &key, b"id").is_err());
+        assert!(decrypted(&ciphertext[..HEADER_LENGTH], &key, b"id").is_err());
+        assert!(decrypted(&ciphertext[..3], &key, b"id").is_err());
+    }
+
+    #[test]
+    fn stream_detects_reordered_and_modified_chunks() {
+        let key = generate_key();
+        let ciphertext = encrypted(&sample(), &key, b"id");
+
+        let mut reordered = ciphertext[..HEADER_LENGTH].to_vec();
+        reordered.extend_from_slice(&ciphertext[HEADER_LENGTH + CHUNK_LENGTH..HEADER_LENGTH + 2 * CHUNK_LENGTH]);
+        reordered.extend_from_slice(&ciphertext[HEADER_LENGTH..HEADER_LENGTH + CHUNK_LENGTH]);
+        reordered.extend_from_slice(&ciphertext[HEADER_LENGTH + 2 * CHUNK_LENGTH..]);
+        assert_eq!(reordered.len(), ciphertext.len());
+        assert!(decrypted(&reordered, &key, b"id").is_err());
+
+        let mut modified = ciphertext.clone();
+        modified[HEADER_LENGTH + CHUNK_LENGTH + 10] ^= 1;
+        assert!(decrypted(&modified, &key, b"id").is_err());
+
+        let mut bad_magic = ciphertext;
+        bad_magic[0] ^= 1;
+        assert!(decrypted(&bad_magic, &key, b"id").is_err());
+    }
+
+    #[test]
+    fn stream_decryptor_feeds_encrypt_stream() {
+        let plaintext = sample();
+        let (from, to) = (generate_key(), generate_key());
+        let ciphertext = encrypted(&plaintext, &from, b"from");
+
+        let mut decryptor = StreamDecryptor::new(&ciphertext[..], &from, b"from").unwrap();
+        let mut copied = Vec::new();
+        encrypt_stream(&mut decryptor, &mut copied, &to, b"to").unwrap();
+
+        let mut decrypted = Vec::new();
+        decrypt_stream(&mut &copied[..], &mut decrypted, &to, b"to").unwrap();
+        assert_eq!(decrypted, plaintext);
+    }
+}
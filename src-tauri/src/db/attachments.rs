@@ -0,0 +1,301 @@
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+use chrono::Utc;
+use generic_array::GenericArray;
+use rusqlite::{Connection, OptionalExtension};
+use typenum::U32;
+use zeroize::Zeroizing;
+
+use crate::auth::Database;
+use crate::crypto::{decrypt_bytes_from_base64, decrypt_from_base64, decrypt_stream, encrypt_bytes_to_base64, encrypt_stream, encrypt_to_base64, generate_key, key_from_bytes, StreamDecryptor};
+use crate::db::items::{check_item_owner, with_item_type, ItemKind, VaultItem};
+use crate::models::Attachment;
+
+impl Database {
+    /// Encrypts the file at `source_path` chunk by chunk into the attachments
+    /// directory; only its metadata is stored in the database.
+    pub fn add_attachment<T: VaultItem>(&self, item_id: &str, source_path: &Path, user_id: i32) -> Result<Attachment, String> {
+        self.get_encryption_key(user_id)?;
+        let key = {
+            let conn = self.conn.lock().unwrap();
+            check_item_owner::<T>(&conn, item_id, user_id)?;
+            self.item_vault_keys::<T>(&conn, item_id, user_id)?.items
+        };
+
+        let file_name = source_path.file_name()
+            .and_then(|name| name.to_str())
+            .ok_or("Invalid file name".to_string())?;
+
+        let id = uuid::Uuid::new_v4().to_string();
+        let created_at = Utc::now().timestamp_millis();
+        let file_key = generate_key();
+
+        let dir = attachments_dir(&self.data_dir);
+        fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+        let path = attachment_path(&self.data_dir, &id);
+
+        let mut reader = BufReader::new(File::open(source_path).map_err(|e| e.to_string())?);
+        let mut writer = BufWriter::new(File::create(&path).map_err(|e| e.to_string())?);
+        let size = match encrypt_stream(&mut reader, &mut writer, &file_key, id.as_bytes()) {
+            Ok(size) => size as i64,
+            Err(e) => {
+                drop(writer);
+                let _ = fs::remove_file(&path);
+                return Err(e.to_string());
+            }
+        };
+        drop(writer);
+
+        let attachment = Attachment {
+            id,
+            item_id: item_id.to_string(),
+            file_name: file_name.to_string(),
+            size,
+            created_at,
+        };
+        let mut conn = self.conn.lock().unwrap();
+        if let Err(e) = self.insert_attachment::<T>(&mut conn, &attachment, &file_key, &key, user_id) {
+            let _ = fs::remove_file(&path);
+            return Err(e);
+        }
+        Ok(attachment)
+    }
+
+    /// Stores the row of an attachment whose file is written, checking again
+    /// that the item is the user's and still uses `key`, as either may have
+    /// changed while the file was encrypted.
+    fn insert_attachment<T: VaultItem>(&self, conn: &mut Connection, attachment: &Attachment, file_key: &GenericArray<u8, U32>, key: &GenericArray<u8, U32>, user_id: i32) -> Result<(), String> {
+        check_item_owner::<T>(conn, &attachment.item_id, user_id)?;
+        if self.item_vault_keys::<T>(conn, &attachment.item_id, user_id)?.items != *key {
+            return Err("The item moved to another vault while the file was added".to_string());
+        }
+        let (file_name_encrypted, file_name_nonce) = encrypt_to_base64(&attachment.file_name, key)?;
+        let (file_key_encrypted, file_key_nonce) = encrypt_bytes_to_base64(file_key, key)?;
+
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        tx.execute(
+            "INSERT INTO attachments (id, item_kind, item_id, file_name_encrypted, file_name_nonce, file_key_encrypted, file_key_nonce, size, created_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            rusqlite::params![&attachment.id, T::KIND.as_str(), &attachment.item_id, &file_name_encrypted, &file_name_nonce, &file_key_encrypted, &file_key_nonce, attachment.size, attachment.created_at],
+        ).map_err(|e| e.to_string())?;
+        tx.execute(
+            &format!("UPDATE {} SET updated_at = ? WHERE id = ?", T::TABLE),
+            rusqlite::params![attachment.created_at, &attachment.item_id],
+        ).map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())
+    }
+
+    pub fn get_attachments<T: VaultItem>(&self, item_id: &str, user_id: i32) -> Result<Vec<Attachment>, String> {
+        let conn = self.conn.lock().unwrap();
+        self.get_encryption_key(user_id)?;
+        check_item_owner::<T>(&conn, item_id, user_id)?;
+        let key = self.item_vault_keys::<T>(&conn, item_id, user_id)?.items;
+
+        let mut stmt = conn.prepare(
+            "SELECT id, item_id, file_name_encrypted, file_name_nonce, size, created_at FROM attachments WHERE item_id = ? ORDER BY created_at ASC"
+        ).map_err(|e| e.to_string())?;
+
+        let rows = stmt.query_map([item_id], |row| {
+            Ok((
+                row.get(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+                row.get(4)?,
+                row.get(5)?,
+            ))
+        }).map_err(|e| e.to_string())?;
+
+        let mut result = Vec::new();
+        for row in rows {
+            let (id, item_id, file_name_encrypted, file_name_nonce, size, created_at): (String, String, String, String, i64, i64) = row.map_err(|e| e.to_string())?;
+            result.push(Attachment {
+                id,
+                item_id,
+                file_name: decrypt_from_base64(&file_name_encrypted, &file_name_nonce, &key)?,
+                size,
+                created_at,
+            });
+        }
+        Ok(result)
+    }
+
+    /// Decrypts an attachment to `destination`. The plaintext is written to a
+    /// temporary file first so a tampered attachment never leaves a partial file.
+    pub fn save_attachment(&self, attachment_id: &str, destination: &Path, user_id: i32) -> Result<(), String> {
+        self.get_encryption_key(user_id)?;
+
+        let (key, file_key_encrypted, file_key_nonce): (GenericArray<u8, U32>, String, String) = {
+            let conn = self.conn.lock().unwrap();
+            let (kind, item_id) = attachment_item(&conn, attachment_id, user_id)?;
+            let key = with_item_type!(kind, |T| self.item_vault_keys::<T>(&conn, &item_id, user_id))?.items;
+            let (file_key_encrypted, file_key_nonce) = conn.query_row(
+                "SELECT file_key_encrypted, file_key_nonce FROM attachments WHERE id = ?",
+                [attachment_id],
+                |row| Ok((row.get(0)?, row.get(1)?))
+            ).map_err(|e| e.to_string())?;
+            (key, file_key_encrypted, file_key_nonce)
+        };
+
+        let file_key = key_from_bytes(&decrypt_bytes_from_base64(&file_key_encrypted, &file_key_nonce, &key)?)?;
+
+        let mut temp_path = destination.as_os_str().to_owned();
+        temp_path.push(".part");
+        let temp_path = PathBuf::from(temp_path);
+
+        let mut reader = BufReader::new(File::open(attachment_path(&self.data_dir, attachment_id)).map_err(|e| e.to_string())?);
+        let mut writer = BufWriter::new(File::create(&temp_path).map_err(|e| e.to_string())?);
+        let result = decrypt_stream(&mut reader, &mut writer, &file_key, attachment_id.as_bytes());
+        drop(writer);
+
+        if let Err(e) = result {
+            let _ = fs::remove_file(&temp_path);
+            return Err(e.to_string());
+        }
+
+        fs::rename(&temp_path, destination).map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    pub fn delete_attachment(&self, attachment_id: &str, user_id: i32) -> Result<(), String> {
+        let conn = self.conn.lock().unwrap();
+        attachment_item(&conn, attachment_id, user_id)?;
+        conn.execute("DELETE FROM attachments WHERE id = ?", [attachment_id])
+            .map_err(|e| e.to_string())?;
+        remove_attachment_file(&self.data_dir, attachment_id);
+        Ok(())
+    }
+}
+
+/// The kind and id of the item an attachment belongs to, which has to be one
+/// of the user's.
+fn attachment_item(conn: &Connection, attachment_id: &str, user_id: i32) -> Result<(ItemKind, String), String> {
+    let (kind, item_id): (String, String) = conn.query_row(
+        "SELECT item_kind, item_id FROM attachments WHERE id = ?",
+        [attachment_id],
+        |row| Ok((row.get(0)?, row.get(1)?))
+    ).optional().map_err(|e| e.to_string())?
+    .ok_or("Attachment not found".to_string())?;
+
+    let kind = ItemKind::parse(&kind).ok_or(format!("Unknown item kind '{}'", kind))?;
+    with_item_type!(kind, |T| check_item_owner::<T>(conn, &item_id, user_id))
+        .map_err(|_| "Attachment not found".to_string())?;
+    Ok((kind, item_id))
+}
+
+/// Wraps the file keys and names of an item's attachments with `to` instead
+/// of `from`. The files themselves are encrypted with their own keys and stay
+/// as they are.
+pub(crate) fn reencrypt_attachments(conn: &Connection, item_id: &str, from: &GenericArray<u8, U32>, to: &GenericArray<u8, U32>) -> Result<(), String> {
+    let mut stmt = conn.prepare(
+        "SELECT id, file_name_encrypted, file_name_nonce, file_key_encrypted, file_key_nonce FROM attachments WHERE item_id = ?"
+    ).map_err(|e| e.to_string())?;
+    let rows = stmt.query_map([item_id], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            (row.get::<_, String>(1)?, row.get::<_, String>(2)?),
+            (row.get::<_, String>(3)?, row.get::<_, String>(4)?),
+        ))
+    }).map_err(|e| e.to_string())?
+    .collect::<Result<Vec<_>, _>>()
+    .map_err(|e| e.to_string())?;
+
+    for (id, (file_name_encrypted, file_name_nonce), (file_key_encrypted, file_key_nonce)) in rows {
+        let file_name = decrypt_from_base64(&file_name_encrypted, &file_name_nonce, from)?;
+        let file_key = Zeroizing::new(decrypt_bytes_from_base64(&file_key_encrypted, &file_key_nonce, from)?);
+        let (file_name_encrypted, file_name_nonce) = encrypt_to_base64(&file_name, to)?;
+        let (file_key_encrypted, file_key_nonce) = encrypt_bytes_to_base64(&file_key, to)?;
+        conn.execute(
+            "UPDATE attachments SET file_name_encrypted = ?, file_name_nonce = ?, file_key_encrypted = ?, file_key_nonce = ? WHERE id = ?",
+            rusqlite::params![&file_name_encrypted, &file_name_nonce, &file_key_encrypted, &file_key_nonce, &id],
+        ).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Deletes the attachment rows of an item and returns their ids. The files
+/// are left for `remove_attachment_files`, once the deletion is committed.
+pub(crate) fn delete_item_attachments(conn: &Connection, item_id: &str) -> Result<Vec<String>, String> {
+    let mut stmt = conn.prepare("SELECT id FROM attachments WHERE item_id = ?")
+        .map_err(|e| e.to_string())?;
+    let ids: Vec<String> = stmt.query_map([item_id], |row| row.get(0))
+        .map_err(|e| e.to_string())?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    conn.execute("DELETE FROM attachments WHERE item_id = ?", [item_id])
+        .map_err(|e| e.to_string())?;
+    Ok(ids)
+}
+
+pub(crate) fn remove_attachment_files(data_dir: &Path, attachment_ids: &[String]) {
+    for id in attachment_ids {
+        remove_attachment_file(data_dir, id);
+    }
+}
+
+/// Copies the attachments of an item to another one. Each copy gets its own
+/// file key and is encrypted again, as an attachment's id is bound into its
+/// ciphertext. Names and file keys are unwrapped with `from` and wrapped with
+/// `to`. The ids of copies are pushed to `created` as their files are
+/// written, so a caller rolling back can remove them.
+pub(crate) fn copy_item_attachments(
+    conn: &Connection,
+    data_dir: &Path,
+    from_item_id: &str,
+    to_item_id: &str,
+    from: &GenericArray<u8, U32>,
+    to: &GenericArray<u8, U32>,
+    created: &mut Vec<String>,
+) -> Result<(), String> {
+    let mut stmt = conn.prepare(
+        "SELECT id, item_kind, file_name_encrypted, file_name_nonce, file_key_encrypted, file_key_nonce, size, created_at FROM attachments WHERE item_id = ?"
+    ).map_err(|e| e.to_string())?;
+    let rows = stmt.query_map([from_item_id], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            (row.get::<_, String>(2)?, row.get::<_, String>(3)?),
+            (row.get::<_, String>(4)?, row.get::<_, String>(5)?),
+            row.get::<_, i64>(6)?,
+            row.get::<_, i64>(7)?,
+        ))
+    }).map_err(|e| e.to_string())?
+    .collect::<Result<Vec<_>, _>>()
+    .map_err(|e| e.to_string())?;
+
+    for (id, item_kind, (file_name_encrypted, file_name_nonce), (file_key_encrypted, file_key_nonce), size, created_at) in rows {
+        let file_key = key_from_bytes(&decrypt_bytes_from_base64(&file_key_encrypted, &file_key_nonce, from)?)?;
+        let reader = BufReader::new(File::open(attachment_path(data_dir, &id)).map_err(|e| e.to_string())?);
+        let mut plaintext = StreamDecryptor::new(reader, &file_key, id.as_bytes())?;
+
+        let new_id = uuid::Uuid::new_v4().to_string();
+        let new_file_key = generate_key();
+        let mut writer = BufWriter::new(File::create(attachment_path(data_dir, &new_id)).map_err(|e| e.to_string())?);
+        created.push(new_id.clone());
+        encrypt_stream(&mut plaintext, &mut writer, &new_file_key, new_id.as_bytes())?;
+        writer.into_inner().map_err(|e| e.to_string())?.sync_all().map_err(|e| e.to_string())?;
+
+        let file_name = decrypt_from_base64(&file_name_encrypted, &file_name_nonce, from)?;
+        let (file_name_encrypted, file_name_nonce) = encrypt_to_base64(&file_name, to)?;
+        let (file_key_encrypted, file_key_nonce) = encrypt_bytes_to_base64(&new_file_key, to)?;
+        conn.execute(
+            "INSERT INTO attachments (id, item_kind, item_id, file_name_encrypted, file_name_nonce, file_key_encrypted, file_key_nonce, size, created_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            rusqlite::params![&new_id, &item_kind, to_item_id, &file_name_encrypted, &file_name_nonce, &file_key_encrypted, &file_key_nonce, size, created_at],
+        ).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+fn attachments_dir(data_dir: &Path) -> PathBuf {
+    data_dir.join("attachments")
+}
+
+fn attachment_path(data_dir: &Path, attachment_id: &str) -> PathBuf {
+    attachments_dir(data_dir).join(format!("{}.bin", attachment_id))
+}
+
+fn remove_attachment_file(data_dir: &Path, attachment_id: &str) {
+    let _ = fs::remove_file(attachment_path(data_dir, attachment_id));
+}
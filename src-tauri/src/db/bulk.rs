@@ -0,0 +1,155 @@
+use std::collections::HashSet;
+
+use chrono::Utc;
+use rusqlite::Connection;
+
+use crate::auth::Database;
+use crate::db::attachments::remove_attachment_files;
+use crate::db::favorites::set_item_favorite;
+use crate::db::items::{check_item_owner, delete_item_rows, load_item, with_item_type, Item, VaultItem};
+use crate::db::tags::{find_or_create_tag, normalize_tag_name, tag_item};
+use crate::db::vaults::check_vault_owner;
+use crate::models::{BulkResult, ItemRef, ItemResult};
+
+impl Database {
+    pub fn bulk_delete(&self, items: &[ItemRef], user_id: i32) -> Result<BulkResult, String> {
+        let mut conn = self.conn.lock().unwrap();
+        self.get_encryption_key(user_id)?;
+
+        let mut attachments = Vec::new();
+        let result = run_bulk(&mut conn, items, user_id, |conn, item| {
+            attachments.extend(with_item_type!(item.kind, |T| delete_item_rows::<T>(conn, &item.id))?);
+            Ok(None)
+        })?;
+
+        if result.applied {
+            remove_attachment_files(&self.data_dir, &attachments);
+            for item in items {
+                self.unindex_item(&item.id);
+            }
+        }
+        Ok(result)
+    }
+
+    pub fn bulk_move(&self, items: &[ItemRef], target_vault_id: &str, user_id: i32) -> Result<BulkResult, String> {
+        let mut conn = self.conn.lock().unwrap();
+        self.get_encryption_key(user_id)?;
+        check_vault_owner(&conn, target_vault_id, user_id)?;
+        let target_keys = self.vault_keys(&conn, target_vault_id, user_id)?;
+
+        let result = run_bulk(&mut conn, items, user_id, |conn, item| {
+            with_item_type!(item.kind, |T| self.move_item::<T>(conn, &item.id, target_vault_id, user_id, &target_keys))?;
+            Ok(None)
+        })?;
+
+        if result.applied {
+            self.rebuild_search_index(&conn, user_id)?;
+        }
+        Ok(result)
+    }
+
+    /// Tags every item with `name`, creating the tag if needed.
+    pub fn bulk_tag(&self, items: &[ItemRef], name: &str, user_id: i32) -> Result<BulkResult, String> {
+        let name = normalize_tag_name(name)?;
+        let mut conn = self.conn.lock().unwrap();
+        let key = self.get_encryption_key(user_id)?;
+
+        let mut tag_id: Option<String> = None;
+        let result = run_bulk(&mut conn, items, user_id, |conn, item| {
+            let id = match &tag_id {
+                Some(id) => id.clone(),
+                None => tag_id.insert(find_or_create_tag(conn, &name, user_id, &key)?).clone(),
+            };
+            tag_item(conn, &id, item.kind, &item.id)?;
+            Ok(None)
+        })?;
+
+        if result.applied {
+            self.rebuild_search_index(&conn, user_id)?;
+        }
+        Ok(result)
+    }
+
+    pub fn bulk_recolor(&self, items: &[ItemRef], color: &str, user_id: i32) -> Result<BulkResult, String> {
+        let mut conn = self.conn.lock().unwrap();
+        self.get_encryption_key(user_id)?;
+        let now = Utc::now().timestamp_millis();
+
+        let result = run_bulk(&mut conn, items, user_id, |conn, item| {
+            with_item_type!(item.kind, |T| recolor_item::<T>(conn, &item.id, color, now))?;
+            Ok(None)
+        })?;
+
+        if result.applied {
+            self.rebuild_search_index(&conn, user_id)?;
+        }
+        Ok(result)
+    }
+
+    pub fn bulk_favorite(&self, items: &[ItemRef], favorite: bool, user_id: i32) -> Result<BulkResult, String> {
+        let mut conn = self.conn.lock().unwrap();
+        self.get_encryption_key(user_id)?;
+
+        let result = run_bulk(&mut conn, items, user_id, |conn, item| {
+            with_item_type!(item.kind, |T| set_item_favorite::<T>(conn, &item.id, favorite, user_id))?;
+            Ok(None)
+        })?;
+
+        if result.applied {
+            self.rebuild_search_index(&conn, user_id)?;
+        }
+        Ok(result)
+    }
+
+    /// Decrypted items, with custom fields and tags, for the frontend to save.
+    pub fn bulk_export(&self, items: &[ItemRef], user_id: i32) -> Result<BulkResult, String> {
+        let mut conn = self.conn.lock().unwrap();
+        self.get_encryption_key(user_id)?;
+
+        run_bulk(&mut conn, items, user_id, |conn, item| {
+            with_item_type!(item.kind, |T| {
+                let keys = self.item_vault_keys::<T>(conn, &item.id, user_id)?;
+                Ok(load_item::<T>(conn, &item.id, &keys)?.map(T::into_item))
+            })
+        })
+    }
+}
+
+/// Applies `apply` to every item of the user listed in `items`, inside one
+/// transaction that is only committed if it succeeded for all of them.
+fn run_bulk<F>(conn: &mut Connection, items: &[ItemRef], user_id: i32, mut apply: F) -> Result<BulkResult, String>
+where
+    F: FnMut(&Connection, &ItemRef) -> Result<Option<Item>, String>,
+{
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+
+    let mut seen = HashSet::new();
+    let mut results = Vec::with_capacity(items.len());
+    for item in items {
+        // An item listed twice is handled once.
+        if !seen.insert(&item.id) {
+            continue;
+        }
+        let outcome = with_item_type!(item.kind, |T| check_item_owner::<T>(&tx, &item.id, user_id))
+            .and_then(|_| apply(&tx, item));
+        let (error, exported) = match outcome {
+            Ok(exported) => (None, exported),
+            Err(e) => (Some(e), None),
+        };
+        results.push(ItemResult { kind: item.kind, id: item.id.clone(), error, item: exported });
+    }
+
+    let applied = results.iter().all(|r| r.error.is_none());
+    if applied {
+        tx.commit().map_err(|e| e.to_string())?;
+    }
+    Ok(BulkResult { applied, items: results })
+}
+
+fn recolor_item<T: VaultItem>(conn: &Connection, item_id: &str, color: &str, now: i64) -> Result<(), String> {
+    conn.execute(
+        &format!("UPDATE {} SET color = ?, updated_at = ? WHERE id = ?", T::TABLE),
+        rusqlite::params![color, now, item_id],
+    ).map_err(|e| e.to_string())?;
+    Ok(())
+}
@@ -4,13 +4,15 @@ use typenum::U32;
 
 use crate::auth::Database;
 use crate::crypto::{decrypt_from_base64, encrypt_to_base64};
+use crate::db::reorder::{renumber_on_collision, ReorderKind};
 use crate::models::Collection;
 
 impl Database {
     pub fn get_collections(&self, user_id: i32) -> Result<Vec<Collection>, String> {
         let conn = self.conn.lock().unwrap();
+        renumber_on_collision(&conn, ReorderKind::Collection, None, user_id)?;
         let mut stmt = conn.prepare(
-            "SELECT id, user_id, name_encrypted, name_nonce, vault_ids, created_at, position FROM collections WHERE user_id = ? ORDER BY position ASC, created_at ASC"
+            "SELECT id, user_id, name_encrypted, name_nonce, vault_ids, created_at, position, COALESCE(updated_at, created_at), last_used_at FROM collections WHERE user_id = ? ORDER BY position ASC, created_at ASC"
         ).map_err(|e| e.to_string())?;
 
         let key = self.get_encryption_key(user_id)?;
@@ -43,8 +45,8 @@ impl Database {
         let vault_ids_json = "[]".to_string();
 
         conn.execute(
-            "INSERT INTO collections (id, user_id, name_encrypted, name_nonce, vault_ids, created_at, position) VALUES (?, ?, ?, ?, ?, ?, ?)",
-            rusqlite::params![&id, user_id, &name_encrypted, &name_nonce, &vault_ids_json, created_at, max_position],
+            "INSERT INTO collections (id, user_id, name_encrypted, name_nonce, vault_ids, created_at, updated_at, position) VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+            rusqlite::params![&id, user_id, &name_encrypted, &name_nonce, &vault_ids_json, created_at, created_at, max_position],
         ).map_err(|e| e.to_string())?;
 
         Ok(Collection {
@@ -54,6 +56,8 @@ impl Database {
             vault_ids: vec![],
             position: max_position,
             created_at,
+            updated_at: created_at,
+            last_used_at: None,
         })
     }
 
@@ -65,10 +69,11 @@ impl Database {
         let (name_encrypted, name_nonce) = encrypt_to_base64(&collection.name, &key)?;
 
         let vault_ids_json = serde_json::to_string(&collection.vault_ids).map_err(|e| e.to_string())?;
+        let updated_at = Utc::now().timestamp_millis();
 
         conn.execute(
-            "UPDATE collections SET name_encrypted = ?, name_nonce = ?, vault_ids = ?, position = ? WHERE id = ?",
-            rusqlite::params![&name_encrypted, &name_nonce, &vault_ids_json, collection.position, &collection.id],
+            "UPDATE collections SET name_encrypted = ?, name_nonce = ?, vault_ids = ?, position = ?, updated_at = ? WHERE id = ?",
+            rusqlite::params![&name_encrypted, &name_nonce, &vault_ids_json, collection.position, updated_at, &collection.id],
         ).map_err(|e| e.to_string())?;
         Ok(())
     }
@@ -90,8 +95,8 @@ impl Database {
         let vault_ids_json = serde_json::to_string(&vault_ids).map_err(|e| e.to_string())?;
 
         conn.execute(
-            "UPDATE collections SET vault_ids = ? WHERE id = ?",
-            rusqlite::params![&vault_ids_json, collection_id],
+            "UPDATE collections SET vault_ids = ?, updated_at = ? WHERE id = ?",
+            rusqlite::params![&vault_ids_json, Utc::now().timestamp_millis(), collection_id],
         ).map_err(|e| e.to_string())?;
 
         Ok(())
@@ -112,8 +117,8 @@ impl Database {
         let vault_ids_json = serde_json::to_string(&vault_ids).map_err(|e| e.to_string())?;
 
         conn.execute(
-            "UPDATE collections SET vault_ids = ? WHERE id = ?",
-            rusqlite::params![&vault_ids_json, collection_id],
+            "UPDATE collections SET vault_ids = ?, updated_at = ? WHERE id = ?",
+            rusqlite::params![&vault_ids_json, Utc::now().timestamp_millis(), collection_id],
         ).map_err(|e| e.to_string())?;
 
         Ok(())
@@ -163,5 +168,7 @@ fn collection_from_row(row: &rusqlite::Row, user_id: i32, key: &GenericArray<u8,
         vault_ids,
         created_at: row.get(5)?,
         position: row.get(6)?,
+        updated_at: row.get(7)?,
+        last_used_at: row.get(8)?,
     })
 }
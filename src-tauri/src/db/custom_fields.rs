@@ -0,0 +1,144 @@
+use chrono::{NaiveDate, Utc};
+use generic_array::GenericArray;
+use rusqlite::Connection;
+use typenum::U32;
+
+use crate::auth::Database;
+use crate::crypto::{decrypt_from_base64, encrypt_to_base64};
+use crate::db::items::{check_item_owner, ItemKind, VaultItem};
+use crate::models::{CustomField, CustomFieldType, NewCustomField};
+use crate::totp::OtpAuth;
+
+impl Database {
+    /// Replaces the custom fields of an item; their order is the order of `fields`.
+    pub fn set_custom_fields<T: VaultItem>(&self, item_id: &str, fields: &[NewCustomField], user_id: i32) -> Result<Vec<CustomField>, String> {
+        for field in fields {
+            validate_custom_field(field)?;
+        }
+
+        let mut conn = self.conn.lock().unwrap();
+        self.get_encryption_key(user_id)?;
+
+        check_item_owner::<T>(&conn, item_id, user_id)?;
+        let key = self.item_vault_keys::<T>(&conn, item_id, user_id)?.items;
+
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        delete_custom_fields(&tx, item_id)?;
+
+        let mut result = Vec::with_capacity(fields.len());
+        for (position, field) in fields.iter().enumerate() {
+            let id = insert_custom_field(&tx, T::KIND, item_id, field, position as i32, &key)?;
+
+            result.push(CustomField {
+                id,
+                label: field.label.clone(),
+                value: field.value.clone(),
+                field_type: field.field_type,
+                position: position as i32,
+            });
+        }
+
+        tx.execute(
+            &format!("UPDATE {} SET updated_at = ? WHERE id = ?", T::TABLE),
+            rusqlite::params![Utc::now().timestamp_millis(), item_id],
+        ).map_err(|e| e.to_string())?;
+
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(result)
+    }
+}
+
+pub(crate) fn load_custom_fields(conn: &Connection, item_id: &str, key: &GenericArray<u8, U32>) -> Result<Vec<CustomField>, String> {
+    let mut stmt = conn.prepare(
+        "SELECT id, label_encrypted, label_nonce, value_encrypted, value_nonce, field_type, position FROM custom_fields WHERE item_id = ? ORDER BY position ASC"
+    ).map_err(|e| e.to_string())?;
+
+    let rows = stmt.query_map([item_id], |row| {
+        Ok((
+            row.get(0)?,
+            row.get(1)?,
+            row.get(2)?,
+            row.get(3)?,
+            row.get(4)?,
+            row.get(5)?,
+            row.get(6)?,
+        ))
+    }).map_err(|e| e.to_string())?;
+
+    let mut result = Vec::new();
+    for row in rows {
+        let (id, label_encrypted, label_nonce, value_encrypted, value_nonce, field_type, position): (String, String, String, String, String, String, i32) = row.map_err(|e| e.to_string())?;
+        result.push(CustomField {
+            id,
+            label: decrypt_from_base64(&label_encrypted, &label_nonce, key)?,
+            value: decrypt_from_base64(&value_encrypted, &value_nonce, key)?,
+            field_type: CustomFieldType::parse(&field_type).unwrap_or(CustomFieldType::Text),
+            position,
+        });
+    }
+    Ok(result)
+}
+
+/// Copies the custom fields of an item to another one, decrypted with `from`
+/// and encrypted again with `to` under fresh nonces.
+pub(crate) fn copy_custom_fields(conn: &Connection, kind: ItemKind, from_item_id: &str, to_item_id: &str, from: &GenericArray<u8, U32>, to: &GenericArray<u8, U32>) -> Result<(), String> {
+    for field in load_custom_fields(conn, from_item_id, from)? {
+        let new_field = NewCustomField {
+            label: field.label,
+            value: field.value,
+            field_type: field.field_type,
+        };
+        insert_custom_field(conn, kind, to_item_id, &new_field, field.position, to)?;
+    }
+    Ok(())
+}
+
+/// Encrypts the custom fields of an item again, from `from` to `to`. They keep
+/// their ids.
+pub(crate) fn reencrypt_custom_fields(conn: &Connection, item_id: &str, from: &GenericArray<u8, U32>, to: &GenericArray<u8, U32>) -> Result<(), String> {
+    for field in load_custom_fields(conn, item_id, from)? {
+        let (label_encrypted, label_nonce) = encrypt_to_base64(&field.label, to)?;
+        let (value_encrypted, value_nonce) = encrypt_to_base64(&field.value, to)?;
+        conn.execute(
+            "UPDATE custom_fields SET label_encrypted = ?, label_nonce = ?, value_encrypted = ?, value_nonce = ? WHERE id = ?",
+            rusqlite::params![&label_encrypted, &label_nonce, &value_encrypted, &value_nonce, &field.id],
+        ).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+fn insert_custom_field(conn: &Connection, kind: ItemKind, item_id: &str, field: &NewCustomField, position: i32, key: &GenericArray<u8, U32>) -> Result<String, String> {
+    let id = uuid::Uuid::new_v4().to_string();
+    let (label_encrypted, label_nonce) = encrypt_to_base64(&field.label, key)?;
+    let (value_encrypted, value_nonce) = encrypt_to_base64(&field.value, key)?;
+
+    conn.execute(
+        "INSERT INTO custom_fields (id, item_kind, item_id, label_encrypted, label_nonce, value_encrypted, value_nonce, field_type, position) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        rusqlite::params![&id, kind.as_str(), item_id, &label_encrypted, &label_nonce, &value_encrypted, &value_nonce, field.field_type.as_str(), position],
+    ).map_err(|e| e.to_string())?;
+    Ok(id)
+}
+
+pub(crate) fn delete_custom_fields(conn: &Connection, item_id: &str) -> Result<(), String> {
+    conn.execute("DELETE FROM custom_fields WHERE item_id = ?", [item_id])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn validate_custom_field(field: &NewCustomField) -> Result<(), String> {
+    if field.label.trim().is_empty() {
+        return Err("Custom field label cannot be empty".to_string());
+    }
+    match field.field_type {
+        CustomFieldType::Email if !field.value.is_empty() && !field.value.contains('@') => {
+            Err(format!("Invalid email in field '{}'", field.label))
+        }
+        CustomFieldType::Date if !field.value.is_empty() && NaiveDate::parse_from_str(&field.value, "%Y-%m-%d").is_err() => {
+            Err(format!("Invalid date in field '{}', expected YYYY-MM-DD", field.label))
+        }
+        CustomFieldType::Totp if !field.value.is_empty() => {
+            OtpAuth::parse(&field.value).map(|_| ()).map_err(|e| format!("{} in field '{}'", e, field.label))
+        }
+        _ => Ok(()),
+    }
+}
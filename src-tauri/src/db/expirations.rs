@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+
+use chrono::{Local, NaiveDate};
+use generic_array::GenericArray;
+use rusqlite::{types::Value, Connection};
+use typenum::U32;
+
+use crate::auth::Database;
+use crate::crypto::decrypt_from_base64;
+use crate::db::items::{query_summaries, with_item_type, ItemKind, VaultItem};
+use crate::models::Expiration;
+
+/// Event emitted when a session starts and something expires within
+/// `DEFAULT_EXPIRY_WINDOW_DAYS`; its payload is the list of `Expiration`s.
+pub const EXPIRATIONS_EVENT: &str = "upcoming-expirations";
+pub const DEFAULT_EXPIRY_WINDOW_DAYS: i64 = 30;
+
+impl Database {
+    /// Items of the user expiring, or due for rotation, within `days` days
+    /// from today, already expired ones included. Soonest first, after those
+    /// whose date does not parse.
+    pub fn upcoming_expirations(&self, days: i64, user_id: i32) -> Result<Vec<Expiration>, String> {
+        if days < 0 {
+            return Err("The number of days cannot be negative".to_string());
+        }
+
+        let conn = self.conn.lock().unwrap();
+        let key = self.get_encryption_key(user_id)?;
+        let today = Local::now().date_naive();
+
+        let mut result = Vec::new();
+        for kind in ItemKind::ALL {
+            with_item_type!(kind, |T| {
+                let due = due_dates::<T>(&conn, today, days, user_id, &key)?;
+                let ids: Vec<Value> = due.keys().cloned().map(Value::from).collect();
+                // Stays well below SQLite's limit on bound parameters.
+                for chunk in ids.chunks(500) {
+                    let clause = format!("WHERE i.id IN ({})", vec!["?"; chunk.len()].join(", "));
+                    for item in query_summaries::<T>(&conn, &clause, chunk, &key)? {
+                        let expiration = match &due[&item.id] {
+                            Ok(expires_on) => Expiration {
+                                item,
+                                expires_on: Some(expires_on.format("%Y-%m-%d").to_string()),
+                                days_left: Some((*expires_on - today).num_days()),
+                                invalid_date: None,
+                            },
+                            Err(e) => Expiration { item, expires_on: None, days_left: None, invalid_date: Some(e.clone()) },
+                        };
+                        result.push(expiration);
+                    }
+                }
+            });
+        }
+        result.sort_by_key(|e| e.days_left);
+        Ok(result)
+    }
+}
+
+/// Expiry dates of the items of type `T` that fall on or before `days` days
+/// after `today`, by item id. Values that do not parse, stored before they
+/// were validated, come with the parse error.
+fn due_dates<T: VaultItem>(conn: &Connection, today: NaiveDate, days: i64, user_id: i32, key: &GenericArray<u8, U32>) -> Result<HashMap<String, Result<NaiveDate, String>>, String> {
+    let field = match T::EXPIRY_FIELD {
+        Some(field) => field,
+        None => return Ok(HashMap::new()),
+    };
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT i.id, i.{0}_encrypted, i.{0}_nonce FROM {1} i JOIN vaults v ON v.id = i.vault_id WHERE v.user_id = ? AND v.vault_key_encrypted IS NULL AND i.{0}_encrypted IS NOT NULL",
+        field, T::TABLE
+    )).map_err(|e| e.to_string())?;
+    let rows = stmt.query_map([user_id], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?))
+    }).map_err(|e| e.to_string())?;
+
+    let mut due = HashMap::new();
+    for row in rows {
+        let (id, encrypted, nonce) = row.map_err(|e| e.to_string())?;
+        let value = decrypt_from_base64(&encrypted, &nonce, key)?;
+        let expires_on = match T::KIND {
+            ItemKind::CreditCard => parse_card_expiry(&value),
+            _ => parse_date(&value, field),
+        };
+        match expires_on {
+            Ok(date) if (date - today).num_days() > days => {}
+            expires_on => {
+                due.insert(id, expires_on);
+            }
+        }
+    }
+    Ok(due)
+}
+
+/// Parses a card expiry in MM/YY form into the last day of that month, the
+/// last day the card is valid.
+pub(crate) fn parse_card_expiry(value: &str) -> Result<NaiveDate, String> {
+    let invalid = || format!("Invalid expiry '{}', expected MM/YY", value);
+
+    let (month, year) = value.trim().split_once('/').ok_or_else(invalid)?;
+    let two_digits = |s: &str| s.len() == 2 && s.bytes().all(|b| b.is_ascii_digit());
+    if !two_digits(month) || !two_digits(year) {
+        return Err(invalid());
+    }
+    let month: u32 = month.parse().map_err(|_| invalid())?;
+    let year: i32 = year.parse().map_err(|_| invalid())?;
+    if !(1..=12).contains(&month) {
+        return Err(invalid());
+    }
+
+    let (next_year, next_month) = if month == 12 { (2001 + year, 1) } else { (2000 + year, month + 1) };
+    NaiveDate::from_ymd_opt(next_year, next_month, 1)
+        .and_then(|first| first.pred_opt())
+        .ok_or_else(invalid)
+}
+
+/// Parses a YYYY-MM-DD date; `what` names it in the error.
+pub(crate) fn parse_date(value: &str, what: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d")
+        .map_err(|_| format!("Invalid {} '{}', expected YYYY-MM-DD", what, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn card_expiry_is_the_last_day_of_its_month() {
+        assert_eq!(parse_card_expiry("01/25"), Ok(date(2025, 1, 31)));
+        assert_eq!(parse_card_expiry("02/28"), Ok(date(2028, 2, 29)));
+        assert_eq!(parse_card_expiry(" 12/30 "), Ok(date(2030, 12, 31)));
+    }
+
+    #[test]
+    fn card_expiry_rejects_other_forms() {
+        for value in ["12/2025", "1/25", "13/25", "00/25", "12-25", "1225", "ab/cd", ""] {
+            assert!(parse_card_expiry(value).is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn dates_are_iso() {
+        assert_eq!(parse_date("2025-06-30", "date"), Ok(date(2025, 6, 30)));
+        assert!(parse_date("30/06/2025", "date").is_err());
+    }
+}
@@ -0,0 +1,77 @@
+use rusqlite::{types::Value, Connection};
+
+use crate::auth::Database;
+use crate::db::items::{check_item_owner, query_summaries, with_item_type, ItemKind, VaultItem};
+use crate::models::ItemSummary;
+
+impl Database {
+    /// Marks or unmarks an item as favorite. New favorites go to the end of
+    /// the favorites list, which is ordered apart from the vaults' `position`.
+    pub fn set_favorite<T: VaultItem>(&self, item_id: &str, favorite: bool, user_id: i32) -> Result<(), String> {
+        let conn = self.conn.lock().unwrap();
+        check_item_owner::<T>(&conn, item_id, user_id)?;
+        set_item_favorite::<T>(&conn, item_id, favorite, user_id)?;
+        self.reindex_item::<T>(&conn, item_id, user_id)
+    }
+
+    pub fn update_favorite_position<T: VaultItem>(&self, item_id: &str, new_position: i32, user_id: i32) -> Result<(), String> {
+        let conn = self.conn.lock().unwrap();
+        check_item_owner::<T>(&conn, item_id, user_id)?;
+        conn.execute(
+            &format!("UPDATE {} SET favorite_position = ? WHERE id = ? AND favorite_position IS NOT NULL", T::TABLE),
+            rusqlite::params![new_position, item_id],
+        ).map_err(|e| e.to_string())?;
+        self.reindex_item::<T>(&conn, item_id, user_id)
+    }
+
+    /// Favorites from every vault of the user, in favorites order.
+    pub fn get_favorites(&self, user_id: i32) -> Result<Vec<ItemSummary>, String> {
+        let conn = self.conn.lock().unwrap();
+        let key = self.get_encryption_key(user_id)?;
+
+        let params = [Value::from(user_id)];
+        let mut favorites = Vec::new();
+        for kind in ItemKind::ALL {
+            with_item_type!(kind, |T| {
+                favorites.extend(query_summaries::<T>(
+                    &conn,
+                    "WHERE v.user_id = ? AND i.favorite_position IS NOT NULL",
+                    &params,
+                    &key,
+                )?);
+            });
+        }
+        favorites.sort_by_key(|f| f.favorite_position);
+        Ok(favorites)
+    }
+}
+
+pub(crate) fn set_item_favorite<T: VaultItem>(conn: &Connection, item_id: &str, favorite: bool, user_id: i32) -> Result<(), String> {
+    if favorite {
+        let position = next_favorite_position(conn, user_id)?;
+        conn.execute(
+            &format!("UPDATE {} SET favorite_position = ? WHERE id = ? AND favorite_position IS NULL", T::TABLE),
+            rusqlite::params![position, item_id],
+        ).map_err(|e| e.to_string())?;
+    } else {
+        conn.execute(
+            &format!("UPDATE {} SET favorite_position = NULL WHERE id = ?", T::TABLE),
+            [item_id],
+        ).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+fn next_favorite_position(conn: &Connection, user_id: i32) -> Result<i32, String> {
+    let selects: Vec<String> = ItemKind::ALL.iter()
+        .map(|kind| format!(
+            "SELECT i.favorite_position AS p FROM {} i JOIN vaults v ON v.id = i.vault_id WHERE v.user_id = ?1",
+            kind.table()
+        ))
+        .collect();
+    conn.query_row(
+        &format!("SELECT COALESCE(MAX(p), -1) + 1 FROM ({})", selects.join(" UNION ALL ")),
+        [user_id],
+        |row| row.get(0),
+    ).map_err(|e| e.to_string())
+}
@@ -0,0 +1,317 @@
+use std::collections::HashSet;
+
+use generic_array::GenericArray;
+use rusqlite::{params_from_iter, types::Value, Connection, OptionalExtension};
+use typenum::U32;
+
+use crate::auth::Database;
+use crate::crypto::{decrypt_from_base64, keyed_hash};
+use crate::db::items::{query_summaries, with_item_type, ItemFields, ItemKind, VaultItem};
+use crate::models::ItemSummary;
+
+/// Domain of the keyed hash turning words into index tokens.
+const TOKEN_DOMAIN: &[u8] = b"full-text-token";
+/// Domain of the value recording which key the stored tokens were made with.
+const KEY_CHECK_DOMAIN: &[u8] = b"full-text-key-check";
+/// Words are also indexed by their prefixes from this length on, so a query
+/// can match the start of a word.
+const MIN_PREFIX_LENGTH: usize = 3;
+/// Only the start of longer words is indexed, and searched for.
+const MAX_WORD_LENGTH: usize = 24;
+/// Hex digits kept of each token hash; a collision only costs a false hit.
+const TOKEN_LENGTH: usize = 32;
+
+impl Database {
+    /// Items of the user whose indexed text (note bodies, login key details)
+    /// contains every word of `query`, or words starting with them.
+    pub fn search_full_text(&self, query: &str, user_id: i32) -> Result<Vec<ItemSummary>, String> {
+        let conn = self.conn.lock().unwrap();
+        let key = self.get_encryption_key(user_id)?;
+
+        let tokens = query_tokens(query, &key);
+        if tokens.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut params = vec![Value::from(user_id)];
+        params.extend(tokens.iter().cloned().map(Value::from));
+        params.push(Value::from(user_id));
+
+        let mut results = Vec::new();
+        for kind in ItemKind::ALL {
+            with_item_type!(kind, |T| {
+                if T::FULL_TEXT_FIELDS.is_empty() {
+                    continue;
+                }
+                let clause = format!(
+                    "WHERE i.id IN (SELECT item_id FROM full_text_tokens WHERE user_id = ? AND item_kind = '{}' AND token IN ({}) GROUP BY item_id HAVING COUNT(DISTINCT token) = {}) AND v.user_id = ? AND v.full_text_search = 1",
+                    kind.as_str(),
+                    vec!["?"; tokens.len()].join(", "),
+                    tokens.len()
+                );
+                results.extend(query_summaries::<T>(&conn, &clause, &params, &key)?);
+            });
+        }
+        results.sort_by_key(|r| r.title.to_lowercase());
+        Ok(results)
+    }
+
+    /// Turns the full-text index of a vault on or off. Turning it off drops the
+    /// tokens already stored for the vault's items. A vault with a passphrase
+    /// cannot have it on.
+    pub fn set_vault_full_text_search(&self, vault_id: &str, enabled: bool, user_id: i32) -> Result<(), String> {
+        let mut conn = self.conn.lock().unwrap();
+        let key = self.get_encryption_key(user_id)?;
+
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let protected: Option<bool> = tx.query_row(
+            "SELECT vault_key_encrypted IS NOT NULL FROM vaults WHERE id = ? AND user_id = ?",
+            rusqlite::params![vault_id, user_id],
+            |row| row.get(0),
+        ).optional().map_err(|e| e.to_string())?;
+        match protected {
+            None => return Err("Vault not found".to_string()),
+            Some(true) if enabled => return Err("A vault with a passphrase cannot be in the full-text index".to_string()),
+            Some(_) => {}
+        }
+        tx.execute(
+            "UPDATE vaults SET full_text_search = ? WHERE id = ?",
+            rusqlite::params![enabled, vault_id],
+        ).map_err(|e| e.to_string())?;
+
+        delete_vault_text(&tx, vault_id)?;
+        if enabled {
+            let params = [Value::from(vault_id.to_string())];
+            for kind in ItemKind::ALL {
+                with_item_type!(kind, |T| index_items::<T>(&tx, "WHERE v.id = ?", &params, &key)?);
+            }
+        }
+        tx.commit().map_err(|e| e.to_string())
+    }
+}
+
+/// Replaces the tokens of an item with those of `fields`, unless its vault
+/// has the full-text index turned off.
+pub(crate) fn index_item_text<T: VaultItem>(conn: &Connection, item_id: &str, fields: &ItemFields, key: &GenericArray<u8, U32>) -> Result<(), String> {
+    if T::FULL_TEXT_FIELDS.is_empty() {
+        return Ok(());
+    }
+    delete_item_text(conn, item_id)?;
+
+    let vault: Option<(i32, bool)> = conn.query_row(
+        &format!("SELECT v.user_id, v.full_text_search FROM {} i JOIN vaults v ON v.id = i.vault_id WHERE i.id = ?", T::TABLE),
+        [item_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    ).optional().map_err(|e| e.to_string())?;
+
+    match vault {
+        Some((user_id, true)) => {
+            let mut tokens = HashSet::new();
+            for field in T::FULL_TEXT_FIELDS {
+                if let Some(text) = fields.get(field) {
+                    add_text_tokens(text, key, &mut tokens);
+                }
+            }
+            insert_tokens(conn, T::KIND, item_id, user_id, &tokens)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Derives the tokens of a stored item again, e.g. after it moved to a vault
+/// with another setting.
+pub(crate) fn reindex_item_text<T: VaultItem>(conn: &Connection, item_id: &str, key: &GenericArray<u8, U32>) -> Result<(), String> {
+    delete_item_text(conn, item_id)?;
+    index_items::<T>(conn, "WHERE i.id = ?", &[Value::from(item_id.to_string())], key)
+}
+
+pub(crate) fn delete_item_text(conn: &Connection, item_id: &str) -> Result<(), String> {
+    conn.execute("DELETE FROM full_text_tokens WHERE item_id = ?", [item_id])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+pub(crate) fn delete_vault_text(conn: &Connection, vault_id: &str) -> Result<(), String> {
+    for kind in ItemKind::ALL {
+        conn.execute(
+            &format!("DELETE FROM full_text_tokens WHERE item_kind = ? AND item_id IN (SELECT id FROM {} WHERE vault_id = ?)", kind.table()),
+            rusqlite::params![kind.as_str(), vault_id],
+        ).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Tokens are only usable with the key they were made with. Rebuilds the
+/// index of a user when it was written with another key, i.e. after their
+/// master key changed, or never written at all.
+pub(crate) fn ensure_full_text_index(conn: &mut Connection, user_id: i32, key: &GenericArray<u8, U32>) -> Result<(), String> {
+    let stored: Option<String> = conn.query_row(
+        "SELECT key_check FROM full_text_state WHERE user_id = ?",
+        [user_id],
+        |row| row.get(0),
+    ).optional().map_err(|e| e.to_string())?;
+
+    if stored.as_deref() == Some(key_check(key).as_str()) {
+        return Ok(());
+    }
+    rebuild_full_text_index(conn, user_id, key)
+}
+
+/// Drops every token of a user and indexes their items again with `key`.
+pub(crate) fn rebuild_full_text_index(conn: &mut Connection, user_id: i32, key: &GenericArray<u8, U32>) -> Result<(), String> {
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    tx.execute("DELETE FROM full_text_tokens WHERE user_id = ?", [user_id])
+        .map_err(|e| e.to_string())?;
+
+    let params = [Value::from(user_id)];
+    for kind in ItemKind::ALL {
+        with_item_type!(kind, |T| index_items::<T>(&tx, "WHERE v.user_id = ?", &params, key)?);
+    }
+
+    tx.execute(
+        "INSERT OR REPLACE INTO full_text_state (user_id, key_check) VALUES (?, ?)",
+        rusqlite::params![user_id, key_check(key)],
+    ).map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())
+}
+
+/// Indexes the items of type `T` matching `clause` (over `i` and its vault
+/// `v`) that sit in vaults with the index turned on.
+fn index_items<T: VaultItem>(conn: &Connection, clause: &str, params: &[Value], key: &GenericArray<u8, U32>) -> Result<(), String> {
+    if T::FULL_TEXT_FIELDS.is_empty() {
+        return Ok(());
+    }
+
+    let columns: Vec<String> = T::FULL_TEXT_FIELDS.iter()
+        .map(|field| format!("i.{0}_encrypted, i.{0}_nonce", field))
+        .collect();
+    let mut stmt = conn.prepare(&format!(
+        "SELECT i.id, v.user_id, {} FROM {} i JOIN vaults v ON v.id = i.vault_id {} AND v.full_text_search = 1",
+        columns.join(", "), T::TABLE, clause
+    )).map_err(|e| e.to_string())?;
+
+    let mut rows = stmt.query(params_from_iter(params)).map_err(|e| e.to_string())?;
+    while let Some(row) = rows.next().map_err(|e| e.to_string())? {
+        let id: String = row.get(0).map_err(|e| e.to_string())?;
+        let user_id: i32 = row.get(1).map_err(|e| e.to_string())?;
+
+        let mut tokens = HashSet::new();
+        for i in 0..T::FULL_TEXT_FIELDS.len() {
+            let encrypted: Option<String> = row.get(2 + i * 2).map_err(|e| e.to_string())?;
+            let nonce: Option<String> = row.get(3 + i * 2).map_err(|e| e.to_string())?;
+            if let Some((encrypted, nonce)) = encrypted.zip(nonce) {
+                add_text_tokens(&decrypt_from_base64(&encrypted, &nonce, key)?, key, &mut tokens);
+            }
+        }
+        insert_tokens(conn, T::KIND, &id, user_id, &tokens)?;
+    }
+    Ok(())
+}
+
+fn insert_tokens(conn: &Connection, kind: ItemKind, item_id: &str, user_id: i32, tokens: &HashSet<String>) -> Result<(), String> {
+    let mut stmt = conn.prepare(
+        "INSERT OR IGNORE INTO full_text_tokens (token, user_id, item_kind, item_id) VALUES (?, ?, ?, ?)"
+    ).map_err(|e| e.to_string())?;
+    for token in tokens {
+        stmt.execute(rusqlite::params![token, user_id, kind.as_str(), item_id])
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Lowercased words of `text`, cut to `MAX_WORD_LENGTH` characters.
+fn words(text: &str) -> Vec<Vec<char>> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_lowercase().chars().take(MAX_WORD_LENGTH).collect())
+        .collect()
+}
+
+/// Keyed, so the stored tokens reveal no words to anyone without the key.
+fn token(word: &[char], key: &GenericArray<u8, U32>) -> String {
+    let word: String = word.iter().collect();
+    let mut token = keyed_hash(key, TOKEN_DOMAIN, word.as_bytes());
+    token.truncate(TOKEN_LENGTH);
+    token
+}
+
+/// Adds a token for every word of `text` and for each prefix of it at least
+/// `MIN_PREFIX_LENGTH` characters long.
+fn add_text_tokens(text: &str, key: &GenericArray<u8, U32>, tokens: &mut HashSet<String>) {
+    for word in words(text) {
+        let shortest = MIN_PREFIX_LENGTH.min(word.len());
+        for length in shortest..=word.len() {
+            tokens.insert(token(&word[..length], key));
+        }
+    }
+}
+
+fn query_tokens(query: &str, key: &GenericArray<u8, U32>) -> Vec<String> {
+    let mut tokens: Vec<String> = words(query).iter().map(|word| token(word, key)).collect();
+    tokens.sort();
+    tokens.dedup();
+    tokens
+}
+
+fn key_check(key: &GenericArray<u8, U32>) -> String {
+    keyed_hash(key, KEY_CHECK_DOMAIN, &[])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::generate_key;
+
+    fn indexed(text: &str, key: &GenericArray<u8, U32>) -> HashSet<String> {
+        let mut tokens = HashSet::new();
+        add_text_tokens(text, key, &mut tokens);
+        tokens
+    }
+
+    fn matches(query: &str, tokens: &HashSet<String>, key: &GenericArray<u8, U32>) -> bool {
+        let query = query_tokens(query, key);
+        !query.is_empty() && query.iter().all(|token| tokens.contains(token))
+    }
+
+    #[test]
+    fn words_are_lowercased_alphanumeric_runs() {
+        let words: Vec<String> = words("Wi-Fi: Guest_Net  42 Café").iter().map(|w| w.iter().collect()).collect();
+        assert_eq!(words, ["wi", "fi", "guest", "net", "42", "café"]);
+    }
+
+    #[test]
+    fn long_words_are_cut() {
+        let long = "a".repeat(MAX_WORD_LENGTH + 10);
+        assert_eq!(words(&long)[0].len(), MAX_WORD_LENGTH);
+    }
+
+    #[test]
+    fn queries_match_words_and_their_prefixes() {
+        let key = generate_key();
+        let tokens = indexed("The router password is on the Fridge", &key);
+
+        assert!(matches("fridge", &tokens, &key));
+        assert!(matches("FRI rout", &tokens, &key));
+        assert!(matches("password router", &tokens, &key));
+        assert!(!matches("fr", &tokens, &key));
+        assert!(!matches("fridges", &tokens, &key));
+        assert!(!matches("router kitchen", &tokens, &key));
+        assert!(!matches("  ", &tokens, &key));
+    }
+
+    #[test]
+    fn long_queries_match_long_words() {
+        let key = generate_key();
+        let word = "x".repeat(MAX_WORD_LENGTH + 5);
+        let tokens = indexed(&word, &key);
+        assert!(matches(&format!("{}yz", word), &tokens, &key));
+    }
+
+    #[test]
+    fn tokens_depend_on_the_key() {
+        let key = generate_key();
+        let tokens = indexed("fridge", &key);
+        assert!(!matches("fridge", &tokens, &generate_key()));
+        assert!(tokens.iter().all(|token| token.len() == TOKEN_LENGTH && !token.contains("fridge")));
+    }
+}
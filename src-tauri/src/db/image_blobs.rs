@@ -0,0 +1,182 @@
+use chrono::Utc;
+use generic_array::GenericArray;
+use rusqlite::{Connection, OptionalExtension, ToSql};
+use typenum::U32;
+
+use crate::auth::Database;
+use crate::crypto::keyed_hash;
+use crate::db::items::ItemKind;
+use crate::db::vault_lock::VAULT_LOCKED;
+use crate::images::{decrypt_image, encrypt_image, sanitize_image, ImageUpdate, SanitizedImage};
+
+/// Domain of the keyed hash that names image blobs.
+const IMAGE_ID_DOMAIN: &[u8] = b"image-blob";
+
+impl Database {
+    /// Loads and decrypts one of the active user's images. Blobs without a
+    /// thumbnail (SVGs) fall back to the full image. An image only used by
+    /// items in locked vaults is refused, like the items themselves.
+    pub fn get_image(&self, blob_id: &str, thumbnail: bool) -> Result<Option<Vec<u8>>, String> {
+        let user_id = self.active_user()?;
+        let column = if thumbnail { "COALESCE(thumbnail, data)" } else { "data" };
+        let blob: Option<Vec<u8>> = {
+            let conn = self.conn.lock().unwrap();
+            let blob = conn.query_row(
+                &format!("SELECT {} FROM image_blobs WHERE id = ? AND user_id = ?", column),
+                rusqlite::params![blob_id, user_id],
+                |row| row.get(0),
+            ).optional().map_err(|e| e.to_string())?;
+            if blob.is_some() && !self.image_visible(&conn, blob_id, user_id)? {
+                return Err(VAULT_LOCKED.to_string());
+            }
+            blob
+        };
+
+        match blob {
+            Some(blob) => {
+                let key = self.get_encryption_key(user_id)?;
+                Ok(Some(decrypt_image(&blob, &key)?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Whether a blob is the user's avatar, a vault image, or the image of an
+    /// item in a vault without a passphrase or currently unlocked.
+    fn image_visible(&self, conn: &Connection, blob_id: &str, user_id: i32) -> Result<bool, String> {
+        let shown: bool = conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM users WHERE id = ?2 AND avatar_id = ?1)
+                 OR EXISTS (SELECT 1 FROM vaults WHERE user_id = ?2 AND image_id = ?1)",
+            rusqlite::params![blob_id, user_id],
+            |row| row.get(0),
+        ).map_err(|e| e.to_string())?;
+        if shown {
+            return Ok(true);
+        }
+
+        for kind in ItemKind::ALL {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT v.id, v.vault_key_encrypted IS NOT NULL FROM {} i JOIN vaults v ON v.id = i.vault_id
+                 WHERE i.image_id = ? AND v.user_id = ?",
+                kind.table()
+            )).map_err(|e| e.to_string())?;
+            let vaults: Vec<(String, bool)> = stmt.query_map(rusqlite::params![blob_id, user_id], |row| Ok((row.get(0)?, row.get(1)?)))
+                .map_err(|e| e.to_string())?
+                .collect::<Result<_, _>>()
+                .map_err(|e| e.to_string())?;
+            if vaults.iter().any(|(vault_id, protected)| !protected || self.vault_unlocked_until(vault_id, user_id).is_some()) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+}
+
+/// Sanitizes `bytes` and stores them, or takes another reference on the blob
+/// already holding the same image. Returns the blob id.
+pub(crate) fn store_image(conn: &Connection, user_id: i32, bytes: &[u8], key: &GenericArray<u8, U32>) -> Result<String, String> {
+    let sanitized = sanitize_image(bytes)?;
+    store_sanitized_image(conn, user_id, &sanitized, key)
+}
+
+pub(crate) fn store_sanitized_image(conn: &Connection, user_id: i32, image: &SanitizedImage, key: &GenericArray<u8, U32>) -> Result<String, String> {
+    // Keyed, so blob ids do not reveal which well known images a user has.
+    let id = keyed_hash(key, IMAGE_ID_DOMAIN, &image.data);
+
+    let updated = conn.execute(
+        "UPDATE image_blobs SET ref_count = ref_count + 1 WHERE id = ? AND user_id = ?",
+        rusqlite::params![&id, user_id],
+    ).map_err(|e| e.to_string())?;
+
+    if updated == 0 {
+        let thumbnail = match &image.thumbnail {
+            Some(thumbnail) => Some(encrypt_image(thumbnail, key)?),
+            None => None,
+        };
+        conn.execute(
+            "INSERT INTO image_blobs (id, user_id, data, thumbnail, ref_count, created_at) VALUES (?, ?, ?, ?, 1, ?)",
+            rusqlite::params![&id, user_id, encrypt_image(&image.data, key)?, thumbnail, Utc::now().timestamp_millis()],
+        ).map_err(|e| e.to_string())?;
+    }
+    Ok(id)
+}
+
+/// Takes another reference on a stored blob, for a row copying an image.
+pub(crate) fn retain_image(conn: &Connection, blob_id: &str, user_id: i32) -> Result<(), String> {
+    conn.execute(
+        "UPDATE image_blobs SET ref_count = ref_count + 1 WHERE id = ? AND user_id = ?",
+        rusqlite::params![blob_id, user_id],
+    )
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Drops one reference to a blob, deleting it once nothing uses it.
+pub(crate) fn release_image(conn: &Connection, blob_id: &str, user_id: i32) -> Result<(), String> {
+    conn.execute(
+        "UPDATE image_blobs SET ref_count = ref_count - 1 WHERE id = ? AND user_id = ?",
+        rusqlite::params![blob_id, user_id],
+    ).map_err(|e| e.to_string())?;
+    conn.execute(
+        "DELETE FROM image_blobs WHERE id = ? AND user_id = ? AND ref_count <= 0",
+        rusqlite::params![blob_id, user_id],
+    )
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Applies an image update to the `column` of a row in `table`, keeping the
+/// reference counts of the old and new blobs in step.
+pub(crate) fn set_row_image(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    row_id: &dyn ToSql,
+    user_id: i32,
+    update: ImageUpdate,
+    key: &GenericArray<u8, U32>,
+) -> Result<(), String> {
+    let new_id = match update {
+        ImageUpdate::Keep => return Ok(()),
+        ImageUpdate::Clear => None,
+        ImageUpdate::Replace(bytes) => Some(store_image(conn, user_id, bytes, key)?),
+    };
+
+    let old_id: Option<String> = conn.query_row(
+        &format!("SELECT {} FROM {} WHERE id = ?", column, table),
+        [row_id],
+        |row| row.get(0),
+    ).optional().map_err(|e| e.to_string())?.flatten();
+
+    conn.execute(
+        &format!("UPDATE {} SET {} = ? WHERE id = ?", table, column),
+        rusqlite::params![new_id, row_id],
+    ).map_err(|e| e.to_string())?;
+
+    if let Some(old_id) = old_id {
+        release_image(conn, &old_id, user_id)?;
+    }
+    Ok(())
+}
+
+/// Recounts the references to every blob of a user and deletes the ones left
+/// unused, e.g. by items that went away together with their vault.
+pub(crate) fn collect_image_garbage(conn: &Connection, user_id: i32) -> Result<usize, String> {
+    let mut references = vec![
+        "(SELECT COUNT(*) FROM users WHERE avatar_id = image_blobs.id)".to_string(),
+        "(SELECT COUNT(*) FROM vaults WHERE image_id = image_blobs.id)".to_string(),
+    ];
+    for kind in ItemKind::ALL {
+        references.push(format!(
+            "(SELECT COUNT(*) FROM {} i JOIN vaults v ON v.id = i.vault_id WHERE i.image_id = image_blobs.id)",
+            kind.table()
+        ));
+    }
+
+    conn.execute(
+        &format!("UPDATE image_blobs SET ref_count = {} WHERE user_id = ?", references.join(" + ")),
+        [user_id],
+    ).map_err(|e| e.to_string())?;
+    conn.execute("DELETE FROM image_blobs WHERE user_id = ? AND ref_count <= 0", [user_id])
+        .map_err(|e| e.to_string())
+}
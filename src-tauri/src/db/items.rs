@@ -0,0 +1,699 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+use generic_array::GenericArray;
+use rusqlite::{params_from_iter, types::Value, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use typenum::U32;
+
+use crate::auth::Database;
+use crate::crypto::{decrypt_from_base64, encrypt_to_base64};
+use crate::db::attachments::{delete_item_attachments, reencrypt_attachments, remove_attachment_files};
+use crate::db::custom_fields::{load_custom_fields, reencrypt_custom_fields};
+use crate::db::full_text::index_item_text;
+use crate::db::image_blobs::{release_image, retain_image, set_row_image, store_image};
+use crate::db::reorder::{renumber_on_collision, ReorderKind};
+use crate::db::tags::load_item_tags;
+use crate::db::vault_lock::VaultKeys;
+use crate::db::vaults::check_vault_owner;
+use crate::images::{image_url, thumbnail_url, ImageUpdate};
+use crate::models::{CreditCard, CustomField, IdCard, ItemSummary, LoginKey, Note, Tag};
+
+/// An encrypted column pair (`<name>_encrypted`, `<name>_nonce`) of an item table.
+pub struct FieldSpec {
+    pub name: &'static str,
+    pub required: bool,
+}
+
+impl FieldSpec {
+    pub const fn required(name: &'static str) -> Self {
+        FieldSpec { name, required: true }
+    }
+
+    pub const fn optional(name: &'static str) -> Self {
+        FieldSpec { name, required: false }
+    }
+}
+
+/// Plaintext values of the encrypted fields of an item, keyed by field name.
+/// A missing optional field is stored as NULL.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(transparent)]
+pub struct ItemFields(HashMap<String, String>);
+
+impl ItemFields {
+    pub fn new() -> Self {
+        ItemFields(HashMap::new())
+    }
+
+    pub fn with(mut self, name: &str, value: &str) -> Self {
+        self.0.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    pub fn with_opt(mut self, name: &str, value: Option<&str>) -> Self {
+        if let Some(v) = value {
+            self.0.insert(name.to_string(), v.to_string());
+        }
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0.get(name).map(|s| s.as_str())
+    }
+
+    pub fn take(&mut self, name: &str) -> Option<String> {
+        self.0.remove(name)
+    }
+
+    /// Whether `name` differs from its value in `previous`, the stored
+    /// fields of the item being updated. Always true for a new item.
+    pub fn changed(&self, name: &str, previous: Option<&ItemFields>) -> bool {
+        previous.is_none_or(|previous| previous.get(name) != self.get(name))
+    }
+
+    /// Adds the values of `other`, replacing those already present.
+    pub fn extend(&mut self, other: ItemFields) {
+        self.0.extend(other.0);
+    }
+}
+
+/// Plain columns shared by every item table.
+pub struct ItemRecord {
+    pub id: String,
+    pub vault_id: String,
+    pub color: String,
+    pub image: Option<String>,
+    pub thumbnail: Option<String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+    pub last_used_at: Option<i64>,
+    pub position: i32,
+    pub favorite_position: Option<i32>,
+}
+
+/// A type stored in its own item table inside a vault.
+///
+/// Implementors only declare their table and encrypted fields; listing, loading,
+/// creating, updating, reordering and deleting are provided by `Database`.
+pub trait VaultItem: Sized {
+    const KIND: ItemKind;
+    const TABLE: &'static str;
+    const FIELDS: &'static [FieldSpec];
+    /// Fields shown when the item is listed outside its vault; never secrets.
+    const TITLE_FIELD: &'static str;
+    const SUBTITLE_FIELD: Option<&'static str>;
+    /// Further non-secret fields the search index covers.
+    const SEARCH_FIELDS: &'static [&'static str];
+    /// Free text fields covered by the optional full-text index, which keeps
+    /// keyed tokens of their words rather than the text itself.
+    const FULL_TEXT_FIELDS: &'static [&'static str];
+    /// Date field `upcoming_expirations` watches, validated on write.
+    const EXPIRY_FIELD: Option<&'static str>;
+
+    fn from_record(record: ItemRecord, fields: ItemFields) -> Self;
+
+    fn into_item(self) -> Item;
+
+    fn set_custom_fields(&mut self, custom_fields: Vec<CustomField>);
+
+    fn set_tags(&mut self, tags: Vec<Tag>);
+
+    /// Type specific checks, run after the field schema has been validated.
+    /// On update `previous` holds the stored fields; values left as they were
+    /// may predate a check, so only changed ones are held to it.
+    fn validate(_fields: &ItemFields, _previous: Option<&ItemFields>) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemKind {
+    LoginKey,
+    CreditCard,
+    IdCard,
+    Note,
+}
+
+impl ItemKind {
+    pub const ALL: [ItemKind; 4] = [ItemKind::LoginKey, ItemKind::CreditCard, ItemKind::IdCard, ItemKind::Note];
+
+    pub fn table(&self) -> &'static str {
+        with_item_type!(*self, |T| T::TABLE)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ItemKind::LoginKey => "login_key",
+            ItemKind::CreditCard => "credit_card",
+            ItemKind::IdCard => "id_card",
+            ItemKind::Note => "note",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        ItemKind::ALL.into_iter().find(|kind| kind.as_str() == value)
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum Item {
+    LoginKey(LoginKey),
+    CreditCard(CreditCard),
+    IdCard(IdCard),
+    Note(Note),
+}
+
+/// Runs `$body` with `$t` bound to the model type of `$kind`.
+macro_rules! with_item_type {
+    ($kind:expr, |$t:ident| $body:expr) => {
+        match $kind {
+            $crate::db::items::ItemKind::LoginKey => {
+                type $t = $crate::models::LoginKey;
+                $body
+            }
+            $crate::db::items::ItemKind::CreditCard => {
+                type $t = $crate::models::CreditCard;
+                $body
+            }
+            $crate::db::items::ItemKind::IdCard => {
+                type $t = $crate::models::IdCard;
+                $body
+            }
+            $crate::db::items::ItemKind::Note => {
+                type $t = $crate::models::Note;
+                $body
+            }
+        }
+    };
+}
+
+pub(crate) use with_item_type;
+
+struct EncryptedItem {
+    record: ItemRecord,
+    image_id: Option<String>,
+    fields: Vec<Option<(String, String)>>,
+}
+
+const BASE_COLUMNS: usize = 9;
+
+fn select_sql<T: VaultItem>() -> String {
+    let mut columns = "id, vault_id, color, image_id, created_at, COALESCE(updated_at, created_at), last_used_at, position, favorite_position".to_string();
+    for field in T::FIELDS {
+        columns.push_str(&format!(", {0}_encrypted, {0}_nonce", field.name));
+    }
+    format!("SELECT {} FROM {}", columns, T::TABLE)
+}
+
+fn encrypted_item_from_row<T: VaultItem>(row: &Row) -> Result<EncryptedItem, rusqlite::Error> {
+    let mut fields = Vec::with_capacity(T::FIELDS.len());
+    for i in 0..T::FIELDS.len() {
+        let encrypted: Option<String> = row.get(BASE_COLUMNS + i * 2)?;
+        let nonce: Option<String> = row.get(BASE_COLUMNS + i * 2 + 1)?;
+        fields.push(encrypted.zip(nonce));
+    }
+
+    Ok(EncryptedItem {
+        record: ItemRecord {
+            id: row.get(0)?,
+            vault_id: row.get(1)?,
+            color: row.get(2)?,
+            image: None,
+            thumbnail: None,
+            created_at: row.get(4)?,
+            updated_at: row.get(5)?,
+            last_used_at: row.get(6)?,
+            position: row.get(7)?,
+            favorite_position: row.get(8)?,
+        },
+        image_id: row.get(3)?,
+        fields,
+    })
+}
+
+fn decrypt_fields<T: VaultItem>(encrypted: &[Option<(String, String)>], key: &GenericArray<u8, U32>) -> Result<ItemFields, String> {
+    let mut fields = ItemFields::new();
+    for (spec, value) in T::FIELDS.iter().zip(encrypted) {
+        if let Some((enc, nonce)) = value {
+            let plain = decrypt_from_base64(enc, nonce, key)?;
+            fields.0.insert(spec.name.to_string(), plain);
+        }
+    }
+    Ok(fields)
+}
+
+fn decrypt_item<T: VaultItem>(encrypted: EncryptedItem, key: &GenericArray<u8, U32>) -> Result<T, String> {
+    let image = encrypted.image_id.as_deref().map(image_url);
+    let thumbnail = encrypted.image_id.as_deref().map(thumbnail_url);
+    let fields = decrypt_fields::<T>(&encrypted.fields, key)?;
+
+    let record = ItemRecord {
+        image,
+        thumbnail,
+        ..encrypted.record
+    };
+
+    Ok(T::from_record(record, fields))
+}
+
+/// Loads and decrypts the items of type `T` matching `clause` (a `WHERE`
+/// and/or `ORDER BY` over the item table), together with their tags. All of
+/// them have to be in vaults `keys` belong to.
+pub(crate) fn query_items<T: VaultItem>(conn: &Connection, clause: &str, params: &[Value], keys: &VaultKeys) -> Result<Vec<T>, String> {
+    let mut stmt = conn.prepare(&format!("{} {}", select_sql::<T>(), clause))
+        .map_err(|e| e.to_string())?;
+
+    let rows = stmt.query_map(params_from_iter(params), |row| encrypted_item_from_row::<T>(row))
+        .map_err(|e| e.to_string())?;
+
+    let mut ids = Vec::new();
+    let mut result = Vec::new();
+    for row in rows {
+        let encrypted = row.map_err(|e| e.to_string())?;
+        ids.push(encrypted.record.id.clone());
+        result.push(decrypt_item::<T>(encrypted, &keys.items)?);
+    }
+
+    let mut tags = load_item_tags(conn, &ids, &keys.session)?;
+    for (item, id) in result.iter_mut().zip(&ids) {
+        item.set_tags(tags.remove(id).unwrap_or_default());
+    }
+    Ok(result)
+}
+
+/// Loads summaries of the items of type `T` matching `clause`, which may refer
+/// to the item table as `i` and to its vault as `v`. Items in vaults with a
+/// passphrase are left out, as `key` does not open them.
+pub(crate) fn query_summaries<T: VaultItem>(conn: &Connection, clause: &str, params: &[Value], key: &GenericArray<u8, U32>) -> Result<Vec<ItemSummary>, String> {
+    let subtitle = match T::SUBTITLE_FIELD {
+        Some(field) => format!("i.{0}_encrypted, i.{0}_nonce", field),
+        None => "NULL, NULL".to_string(),
+    };
+    let mut stmt = conn.prepare(&format!(
+        "SELECT i.id, i.vault_id, v.name_encrypted, v.name_nonce, i.color, i.image_id, i.favorite_position, i.{0}_encrypted, i.{0}_nonce, {1}, i.created_at, COALESCE(i.updated_at, i.created_at), i.last_used_at FROM {2} i JOIN vaults v ON v.id = i.vault_id AND v.vault_key_encrypted IS NULL {3}",
+        T::TITLE_FIELD, subtitle, T::TABLE, clause
+    )).map_err(|e| e.to_string())?;
+
+    let rows = stmt.query_map(params_from_iter(params), |row| {
+        Ok((
+            (row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?, row.get::<_, String>(3)?),
+            (row.get::<_, String>(4)?, row.get::<_, Option<String>>(5)?, row.get::<_, Option<i32>>(6)?),
+            (row.get::<_, String>(7)?, row.get::<_, String>(8)?),
+            row.get::<_, Option<String>>(9)?.zip(row.get::<_, Option<String>>(10)?),
+            (row.get::<_, i64>(11)?, row.get::<_, i64>(12)?, row.get::<_, Option<i64>>(13)?),
+        ))
+    }).map_err(|e| e.to_string())?;
+
+    let mut vault_names: HashMap<String, String> = HashMap::new();
+    let mut result = Vec::new();
+    for row in rows {
+        let ((id, vault_id, vault_name_encrypted, vault_name_nonce), (color, image_id, favorite_position), (title_encrypted, title_nonce), subtitle, (created_at, updated_at, last_used_at)) =
+            row.map_err(|e| e.to_string())?;
+
+        let vault_name = match vault_names.get(&vault_id) {
+            Some(name) => name.clone(),
+            None => {
+                let name = decrypt_from_base64(&vault_name_encrypted, &vault_name_nonce, key)?;
+                vault_names.insert(vault_id.clone(), name.clone());
+                name
+            }
+        };
+        let subtitle = match subtitle {
+            Some((encrypted, nonce)) => Some(decrypt_from_base64(&encrypted, &nonce, key)?),
+            None => None,
+        };
+
+        result.push(ItemSummary {
+            id,
+            kind: T::KIND,
+            vault_id,
+            vault_name,
+            title: decrypt_from_base64(&title_encrypted, &title_nonce, key)?,
+            subtitle,
+            color,
+            image: image_id.as_deref().map(image_url),
+            thumbnail: image_id.as_deref().map(thumbnail_url),
+            favorite_position,
+            created_at,
+            updated_at,
+            last_used_at,
+        });
+    }
+    Ok(result)
+}
+
+/// Loads and decrypts one item with its custom fields and tags.
+pub(crate) fn load_item<T: VaultItem>(conn: &Connection, item_id: &str, keys: &VaultKeys) -> Result<Option<T>, String> {
+    let encrypted = conn.query_row(
+        &format!("{} WHERE id = ?", select_sql::<T>()),
+        [item_id],
+        |row| encrypted_item_from_row::<T>(row),
+    ).optional().map_err(|e| e.to_string())?;
+
+    match encrypted {
+        Some(encrypted) => {
+            let mut item: T = decrypt_item(encrypted, &keys.items)?;
+            item.set_custom_fields(load_custom_fields(conn, item_id, &keys.items)?);
+            let mut tags = load_item_tags(conn, &[item_id.to_string()], &keys.session)?;
+            item.set_tags(tags.remove(item_id).unwrap_or_default());
+            Ok(Some(item))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Inserts a copy of an item into `vault_id` at `position`, with its fields
+/// decrypted with `from` and encrypted again with `to` under fresh nonces.
+/// Color, image and the created and updated times carry over; the copy is
+/// neither a favorite nor used yet. Returns the id of the copy.
+pub(crate) fn insert_item_copy<T: VaultItem>(conn: &Connection, item_id: &str, vault_id: &str, position: i32, from: &GenericArray<u8, U32>, to: &GenericArray<u8, U32>) -> Result<String, String> {
+    let encrypted = load_encrypted_item::<T>(conn, item_id)?;
+    let fields = decrypt_fields::<T>(&encrypted.fields, from)?;
+
+    let id = uuid::Uuid::new_v4().to_string();
+    let mut columns = vec![
+        ("id".to_string(), Value::from(id.clone())),
+        ("vault_id".to_string(), Value::from(vault_id.to_string())),
+        ("color".to_string(), Value::from(encrypted.record.color)),
+        ("image_id".to_string(), Value::from(encrypted.image_id.clone())),
+        ("created_at".to_string(), Value::from(encrypted.record.created_at)),
+        ("updated_at".to_string(), Value::from(encrypted.record.updated_at)),
+        ("position".to_string(), Value::from(position)),
+    ];
+    columns.extend(encrypted_columns::<T>(&fields, to)?);
+    insert_columns(conn, T::TABLE, columns)?;
+
+    if let Some(image_id) = &encrypted.image_id {
+        let user_id: i32 = conn.query_row("SELECT user_id FROM vaults WHERE id = ?", [vault_id], |row| row.get(0))
+            .map_err(|e| e.to_string())?;
+        retain_image(conn, image_id, user_id)?;
+    }
+    Ok(id)
+}
+
+/// Encrypts the fields, custom fields and attachment keys of an item again,
+/// from `from` to `to`, as it moves into or out of a vault with a passphrase.
+pub(crate) fn reencrypt_item<T: VaultItem>(conn: &Connection, item_id: &str, from: &GenericArray<u8, U32>, to: &GenericArray<u8, U32>) -> Result<(), String> {
+    let encrypted = load_encrypted_item::<T>(conn, item_id)?;
+    let fields = decrypt_fields::<T>(&encrypted.fields, from)?;
+    update_columns(conn, T::TABLE, item_id, encrypted_columns::<T>(&fields, to)?)?;
+    reencrypt_custom_fields(conn, item_id, from, to)?;
+    reencrypt_attachments(conn, item_id, from, to)
+}
+
+/// The id of the vault holding an item of type `T`.
+pub(crate) fn item_vault<T: VaultItem>(conn: &Connection, item_id: &str) -> Result<Option<String>, String> {
+    conn.query_row(
+        &format!("SELECT vault_id FROM {} WHERE id = ?", T::TABLE),
+        [item_id],
+        |row| row.get(0)
+    ).optional().map_err(|e| e.to_string())
+}
+
+fn load_encrypted_item<T: VaultItem>(conn: &Connection, item_id: &str) -> Result<EncryptedItem, String> {
+    conn.query_row(
+        &format!("{} WHERE id = ?", select_sql::<T>()),
+        [item_id],
+        |row| encrypted_item_from_row::<T>(row),
+    ).optional().map_err(|e| e.to_string())?
+    .ok_or("Item not found".to_string())
+}
+
+/// The position after the last item of type `T` in a vault.
+pub(crate) fn next_item_position<T: VaultItem>(conn: &Connection, vault_id: &str) -> Result<i32, String> {
+    conn.query_row(
+        &format!("SELECT COALESCE(MAX(position), -1) + 1 FROM {} WHERE vault_id = ?", T::TABLE),
+        [vault_id],
+        |row| row.get(0)
+    ).map_err(|e| e.to_string())
+}
+
+fn insert_columns(conn: &Connection, table: &str, columns: Vec<(String, Value)>) -> Result<(), String> {
+    let (names, values): (Vec<String>, Vec<Value>) = columns.into_iter().unzip();
+    conn.execute(
+        &format!(
+            "INSERT INTO {} ({}) VALUES ({})",
+            table,
+            names.join(", "),
+            vec!["?"; names.len()].join(", ")
+        ),
+        params_from_iter(values),
+    ).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn update_columns(conn: &Connection, table: &str, id: &str, columns: Vec<(String, Value)>) -> Result<(), String> {
+    let (names, mut values): (Vec<String>, Vec<Value>) = columns.into_iter().unzip();
+    let assignments: Vec<String> = names.iter().map(|name| format!("{} = ?", name)).collect();
+    values.push(Value::from(id.to_string()));
+
+    conn.execute(
+        &format!("UPDATE {} SET {} WHERE id = ?", table, assignments.join(", ")),
+        params_from_iter(values),
+    ).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Fails unless `item_id` is an item of type `T` in one of the user's vaults.
+pub(crate) fn check_item_owner<T: VaultItem>(conn: &Connection, item_id: &str, user_id: i32) -> Result<(), String> {
+    let count: i32 = conn.query_row(
+        &format!("SELECT COUNT(*) FROM {} i JOIN vaults v ON v.id = i.vault_id WHERE i.id = ? AND v.user_id = ?", T::TABLE),
+        rusqlite::params![item_id, user_id],
+        |row| row.get(0)
+    ).map_err(|e| e.to_string())?;
+    if count == 0 {
+        return Err("Item not found".to_string());
+    }
+    Ok(())
+}
+
+/// Fails on fields `T` does not declare, without requiring any.
+pub(crate) fn check_field_names<T: VaultItem>(fields: &ItemFields) -> Result<(), String> {
+    for name in fields.0.keys() {
+        if !T::FIELDS.iter().any(|f| f.name == name) {
+            return Err(format!("Unknown field '{}' for {}", name, T::TABLE));
+        }
+    }
+    Ok(())
+}
+
+fn validate_fields<T: VaultItem>(fields: &ItemFields, previous: Option<&ItemFields>) -> Result<(), String> {
+    check_field_names::<T>(fields)?;
+    for spec in T::FIELDS {
+        if spec.required && fields.get(spec.name).is_none() {
+            return Err(format!("Missing required field '{}'", spec.name));
+        }
+    }
+    T::validate(fields, previous)
+}
+
+/// Encrypts every declared field, returning `(column, value)` pairs.
+fn encrypted_columns<T: VaultItem>(
+    fields: &ItemFields,
+    key: &GenericArray<u8, U32>,
+) -> Result<Vec<(String, Value)>, String> {
+    let mut columns = Vec::with_capacity(T::FIELDS.len() * 2);
+
+    for spec in T::FIELDS {
+        let (enc, nonce) = match fields.get(spec.name) {
+            Some(value) => {
+                let (enc, nonce) = encrypt_to_base64(value, key)?;
+                (Some(enc), Some(nonce))
+            }
+            None => (None, None),
+        };
+        columns.push((format!("{}_encrypted", spec.name), Value::from(enc)));
+        columns.push((format!("{}_nonce", spec.name), Value::from(nonce)));
+    }
+
+    Ok(columns)
+}
+
+impl Database {
+    /// The decrypted items of a vault. Revealing them counts as using the
+    /// vault, but none of the items in particular.
+    pub fn list_items<T: VaultItem>(&self, vault_id: &str, user_id: i32) -> Result<Vec<T>, String> {
+        let conn = self.conn.lock().unwrap();
+
+        let keys = self.vault_keys(&conn, vault_id, user_id)?;
+        check_vault_owner(&conn, vault_id, user_id)?;
+        renumber_on_collision(&conn, ReorderKind::from_item_kind(T::KIND), Some(vault_id), user_id)?;
+        record_vault_use(&conn, vault_id, user_id)?;
+
+        query_items(&conn, "WHERE vault_id = ? ORDER BY position ASC, created_at ASC", &[Value::from(vault_id.to_string())], &keys)
+    }
+
+    pub fn get_item<T: VaultItem>(&self, item_id: &str, user_id: i32) -> Result<Option<T>, String> {
+        let conn = self.conn.lock().unwrap();
+
+        self.get_encryption_key(user_id)?;
+        let keys = match item_vault::<T>(&conn, item_id)? {
+            Some(vault_id) => self.vault_keys(&conn, &vault_id, user_id)?,
+            None => return Ok(None),
+        };
+
+        check_item_owner::<T>(&conn, item_id, user_id)?;
+        record_item_use::<T>(&conn, item_id, user_id)?;
+        load_item(&conn, item_id, &keys)
+    }
+
+    pub fn create_item<T: VaultItem>(
+        &self,
+        vault_id: &str,
+        fields: &ItemFields,
+        color: &str,
+        image: Option<&[u8]>,
+        user_id: i32,
+    ) -> Result<T, String> {
+        validate_fields::<T>(fields, None)?;
+
+        let mut conn = self.conn.lock().unwrap();
+        let id = uuid::Uuid::new_v4().to_string();
+        let now = Utc::now().timestamp_millis();
+
+        check_vault_owner(&conn, vault_id, user_id)?;
+        let keys = self.vault_keys(&conn, vault_id, user_id)?;
+
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let position = next_item_position::<T>(&tx, vault_id)?;
+
+        let mut columns = vec![
+            ("id".to_string(), Value::from(id.clone())),
+            ("vault_id".to_string(), Value::from(vault_id.to_string())),
+            ("color".to_string(), Value::from(color.to_string())),
+            ("created_at".to_string(), Value::from(now)),
+            ("updated_at".to_string(), Value::from(now)),
+            ("position".to_string(), Value::from(position)),
+        ];
+        columns.extend(encrypted_columns::<T>(fields, &keys.items)?);
+
+        let image_id = match image {
+            Some(img) => Some(store_image(&tx, user_id, img, &keys.session)?),
+            None => None,
+        };
+        columns.push(("image_id".to_string(), Value::from(image_id.clone())));
+        insert_columns(&tx, T::TABLE, columns)?;
+        index_item_text::<T>(&tx, &id, fields, &keys.session)?;
+        tx.commit().map_err(|e| e.to_string())?;
+        self.reindex_item::<T>(&conn, &id, user_id)?;
+
+        let record = ItemRecord {
+            id,
+            vault_id: vault_id.to_string(),
+            color: color.to_string(),
+            image: image_id.as_deref().map(image_url),
+            thumbnail: image_id.as_deref().map(thumbnail_url),
+            created_at: now,
+            updated_at: now,
+            last_used_at: None,
+            position,
+            favorite_position: None,
+        };
+        Ok(T::from_record(record, fields.clone()))
+    }
+
+    pub fn update_item<T: VaultItem>(
+        &self,
+        item_id: &str,
+        fields: &ItemFields,
+        color: &str,
+        image: ImageUpdate,
+        user_id: i32,
+    ) -> Result<(), String> {
+        let conn = self.conn.lock().unwrap();
+
+        let keys = self.item_vault_keys::<T>(&conn, item_id, user_id)?;
+        let previous = decrypt_fields::<T>(&load_encrypted_item::<T>(&conn, item_id)?.fields, &keys.items)?;
+        validate_fields::<T>(fields, Some(&previous))?;
+        let now = Utc::now().timestamp_millis();
+
+        let mut columns = vec![
+            ("color".to_string(), Value::from(color.to_string())),
+            ("updated_at".to_string(), Value::from(now)),
+        ];
+        columns.extend(encrypted_columns::<T>(fields, &keys.items)?);
+        update_columns(&conn, T::TABLE, item_id, columns)?;
+
+        set_row_image(&conn, T::TABLE, "image_id", &item_id, user_id, image, &keys.session)?;
+        index_item_text::<T>(&conn, item_id, fields, &keys.session)?;
+        self.reindex_item::<T>(&conn, item_id, user_id)
+    }
+
+    pub fn update_item_position<T: VaultItem>(&self, item_id: &str, new_position: i32, user_id: i32) -> Result<(), String> {
+        let conn = self.conn.lock().unwrap();
+        check_item_owner::<T>(&conn, item_id, user_id)?;
+        conn.execute(
+            &format!("UPDATE {} SET position = ? WHERE id = ?", T::TABLE),
+            rusqlite::params![new_position, item_id],
+        ).map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    /// Records that a secret of the item was revealed or copied. Its vault,
+    /// and any collection holding that vault, count as used as well.
+    pub fn mark_item_used<T: VaultItem>(&self, item_id: &str, user_id: i32) -> Result<(), String> {
+        let conn = self.conn.lock().unwrap();
+        check_item_owner::<T>(&conn, item_id, user_id)?;
+        record_item_use::<T>(&conn, item_id, user_id)
+    }
+
+    pub fn delete_item<T: VaultItem>(&self, item_id: &str, user_id: i32) -> Result<(), String> {
+        let mut conn = self.conn.lock().unwrap();
+        check_item_owner::<T>(&conn, item_id, user_id)?;
+
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let attachments = delete_item_rows::<T>(&tx, item_id)?;
+        tx.commit().map_err(|e| e.to_string())?;
+
+        remove_attachment_files(&self.data_dir, &attachments);
+        self.unindex_item(item_id);
+        Ok(())
+    }
+}
+
+/// Sets `last_used_at` on the item, its vault and the collections holding
+/// that vault.
+pub(crate) fn record_item_use<T: VaultItem>(conn: &Connection, item_id: &str, user_id: i32) -> Result<(), String> {
+    let vault_id = item_vault::<T>(conn, item_id)?.ok_or("Item not found".to_string())?;
+    conn.execute(
+        &format!("UPDATE {} SET last_used_at = ? WHERE id = ?", T::TABLE),
+        rusqlite::params![Utc::now().timestamp_millis(), item_id],
+    ).map_err(|e| e.to_string())?;
+    record_vault_use(conn, &vault_id, user_id)
+}
+
+/// Sets `last_used_at` on the vault and the collections holding it.
+pub(crate) fn record_vault_use(conn: &Connection, vault_id: &str, user_id: i32) -> Result<(), String> {
+    let now = Utc::now().timestamp_millis();
+    conn.execute(
+        "UPDATE vaults SET last_used_at = ? WHERE id = ?",
+        rusqlite::params![now, vault_id],
+    ).map_err(|e| e.to_string())?;
+    conn.execute(
+        "UPDATE collections SET last_used_at = ? WHERE user_id = ? AND EXISTS (SELECT 1 FROM json_each(collections.vault_ids) WHERE value = ?)",
+        rusqlite::params![now, user_id, vault_id],
+    ).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Deletes an item with everything hanging off it. Returns the ids of its
+/// attachments, whose files are to be removed once this is committed.
+pub(crate) fn delete_item_rows<T: VaultItem>(conn: &Connection, item_id: &str) -> Result<Vec<String>, String> {
+    let image: Option<(Option<String>, i32)> = conn.query_row(
+        &format!("SELECT i.image_id, v.user_id FROM {} i JOIN vaults v ON v.id = i.vault_id WHERE i.id = ?", T::TABLE),
+        [item_id],
+        |row| Ok((row.get(0)?, row.get(1)?))
+    ).optional().map_err(|e| e.to_string())?;
+
+    // Custom fields, tags and full-text tokens go with the item, by trigger.
+    let attachments = delete_item_attachments(conn, item_id)?;
+    conn.execute(&format!("DELETE FROM {} WHERE id = ?", T::TABLE), [item_id])
+        .map_err(|e| e.to_string())?;
+    if let Some((Some(image_id), user_id)) = image {
+        release_image(conn, &image_id, user_id)?;
+    }
+    Ok(attachments)
+}
@@ -1,5 +1,19 @@
 pub mod collections;
 pub mod vaults;
+pub mod items;
+pub mod custom_fields;
+pub mod expirations;
+pub mod attachments;
+pub mod bulk;
+pub mod image_blobs;
+pub mod favorites;
+pub mod full_text;
+pub mod reorder;
+pub mod sort;
+pub mod tags;
+pub mod templates;
+pub mod transfer;
+pub mod vault_lock;
 pub mod id_cards;
 pub mod credit_cards;
 pub mod login_keys;
@@ -0,0 +1,278 @@
+use std::collections::HashSet;
+
+use rusqlite::{Connection, OptionalExtension};
+use serde::Deserialize;
+
+use crate::auth::Database;
+use crate::db::items::ItemKind;
+use crate::db::vaults::check_vault_owner;
+
+/// What `reorder` rearranges: the collections of a user, the vaults of a
+/// collection (or those in none), or the items of one type in a vault.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReorderKind {
+    Collection,
+    Vault,
+    LoginKey,
+    CreditCard,
+    IdCard,
+    Note,
+}
+
+impl ReorderKind {
+    pub fn from_item_kind(kind: ItemKind) -> Self {
+        match kind {
+            ItemKind::LoginKey => ReorderKind::LoginKey,
+            ItemKind::CreditCard => ReorderKind::CreditCard,
+            ItemKind::IdCard => ReorderKind::IdCard,
+            ItemKind::Note => ReorderKind::Note,
+        }
+    }
+
+    fn item_kind(self) -> Option<ItemKind> {
+        match self {
+            ReorderKind::Collection | ReorderKind::Vault => None,
+            ReorderKind::LoginKey => Some(ItemKind::LoginKey),
+            ReorderKind::CreditCard => Some(ItemKind::CreditCard),
+            ReorderKind::IdCard => Some(ItemKind::IdCard),
+            ReorderKind::Note => Some(ItemKind::Note),
+        }
+    }
+
+    fn table(self) -> &'static str {
+        match self.item_kind() {
+            Some(kind) => kind.table(),
+            None if self == ReorderKind::Collection => "collections",
+            None => "vaults",
+        }
+    }
+}
+
+impl Database {
+    /// Gives the siblings under `parent_id` the positions 0, 1, 2... in the
+    /// order of `ordered_ids`, which has to list each of them exactly once.
+    /// `parent_id` is the vault of items, the collection of vaults (none for
+    /// vaults outside collections) and always none for collections.
+    pub fn reorder(&self, kind: ReorderKind, parent_id: Option<&str>, ordered_ids: &[String], user_id: i32) -> Result<(), String> {
+        let mut conn = self.conn.lock().unwrap();
+        self.get_encryption_key(user_id)?;
+
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        match (kind, parent_id) {
+            (ReorderKind::Collection, Some(_)) => return Err("Collections have no parent".to_string()),
+            (ReorderKind::Collection, None) | (ReorderKind::Vault, None) => {}
+            (ReorderKind::Vault, Some(collection_id)) => {
+                collection_vault_ids(&tx, collection_id, user_id)?;
+            }
+            (_, Some(vault_id)) => check_vault_owner(&tx, vault_id, user_id)?,
+            (_, None) => return Err("Items need the vault they are ordered in".to_string()),
+        }
+
+        let siblings: Vec<String> = siblings(&tx, kind, parent_id, user_id)?
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect();
+        check_same_ids(&siblings, ordered_ids)?;
+        write_positions(&tx, kind, ordered_ids)?;
+
+        if let (ReorderKind::Vault, Some(collection_id)) = (kind, parent_id) {
+            // Collections also keep their vaults as an ordered list.
+            let vault_ids_json = serde_json::to_string(ordered_ids).map_err(|e| e.to_string())?;
+            tx.execute(
+                "UPDATE collections SET vault_ids = ? WHERE id = ?",
+                rusqlite::params![&vault_ids_json, collection_id],
+            ).map_err(|e| e.to_string())?;
+        }
+        tx.commit().map_err(|e| e.to_string())
+    }
+}
+
+/// Renumbers the siblings under `parent_id` from 0 in their current order
+/// when two of them share a position, which per-item position updates that
+/// failed halfway can leave behind.
+pub(crate) fn renumber_on_collision(conn: &Connection, kind: ReorderKind, parent_id: Option<&str>, user_id: i32) -> Result<(), String> {
+    let siblings = siblings(conn, kind, parent_id, user_id)?;
+    let mut positions = HashSet::new();
+    if siblings.iter().all(|(_, position)| positions.insert(*position)) {
+        return Ok(());
+    }
+    let ids: Vec<String> = siblings.into_iter().map(|(id, _)| id).collect();
+    write_positions(conn, kind, &ids)
+}
+
+/// Ids and positions of the siblings under `parent_id`, in display order.
+fn siblings(conn: &Connection, kind: ReorderKind, parent_id: Option<&str>, user_id: i32) -> Result<Vec<(String, i32)>, String> {
+    let (sql, params): (String, Vec<rusqlite::types::Value>) = match (kind.item_kind(), parent_id) {
+        (Some(item_kind), Some(vault_id)) => (
+            format!(
+                "SELECT i.id, i.position FROM {} i JOIN vaults v ON v.id = i.vault_id WHERE i.vault_id = ? AND v.user_id = ? ORDER BY i.position ASC, i.created_at ASC",
+                item_kind.table()
+            ),
+            vec![vault_id.to_string().into(), user_id.into()],
+        ),
+        (Some(_), None) => return Ok(Vec::new()),
+        (None, _) => (
+            format!("SELECT id, position FROM {} WHERE user_id = ? ORDER BY position ASC, created_at ASC", kind.table()),
+            vec![user_id.into()],
+        ),
+    };
+
+    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+    let rows: Vec<(String, i32)> = stmt.query_map(rusqlite::params_from_iter(params), |row| {
+        Ok((row.get(0)?, row.get::<_, Option<i32>>(1)?.unwrap_or(0)))
+    }).map_err(|e| e.to_string())?
+    .collect::<Result<_, _>>()
+    .map_err(|e| e.to_string())?;
+
+    if kind != ReorderKind::Vault {
+        return Ok(rows);
+    }
+
+    // Vaults are ordered within their collection, or among those in none.
+    let vault_ids = match parent_id {
+        Some(collection_id) => collection_vault_ids(conn, collection_id, user_id)?,
+        None => assigned_vault_ids(conn, user_id)?,
+    };
+    let in_collection = parent_id.is_some();
+    Ok(rows.into_iter().filter(|(id, _)| vault_ids.contains(id) == in_collection).collect())
+}
+
+fn collection_vault_ids(conn: &Connection, collection_id: &str, user_id: i32) -> Result<HashSet<String>, String> {
+    let vault_ids_json: String = conn.query_row(
+        "SELECT vault_ids FROM collections WHERE id = ? AND user_id = ?",
+        rusqlite::params![collection_id, user_id],
+        |row| row.get(0)
+    ).optional().map_err(|e| e.to_string())?
+    .ok_or("Collection not found".to_string())?;
+    Ok(serde_json::from_str(&vault_ids_json).unwrap_or_default())
+}
+
+fn assigned_vault_ids(conn: &Connection, user_id: i32) -> Result<HashSet<String>, String> {
+    let mut stmt = conn.prepare("SELECT vault_ids FROM collections WHERE user_id = ?")
+        .map_err(|e| e.to_string())?;
+    let rows = stmt.query_map([user_id], |row| row.get::<_, String>(0))
+        .map_err(|e| e.to_string())?;
+
+    let mut vault_ids = HashSet::new();
+    for row in rows {
+        let ids: Vec<String> = serde_json::from_str(&row.map_err(|e| e.to_string())?).unwrap_or_default();
+        vault_ids.extend(ids);
+    }
+    Ok(vault_ids)
+}
+
+fn check_same_ids(current: &[String], ordered_ids: &[String]) -> Result<(), String> {
+    let mut seen = HashSet::new();
+    for id in ordered_ids {
+        if !seen.insert(id.as_str()) {
+            return Err(format!("'{}' is listed more than once", id));
+        }
+    }
+    let current: HashSet<&str> = current.iter().map(String::as_str).collect();
+    if seen != current {
+        return Err("The new order has to list every element exactly once".to_string());
+    }
+    Ok(())
+}
+
+fn write_positions(conn: &Connection, kind: ReorderKind, ordered_ids: &[String]) -> Result<(), String> {
+    let mut stmt = conn.prepare(&format!("UPDATE {} SET position = ? WHERE id = ?", kind.table()))
+        .map_err(|e| e.to_string())?;
+    for (position, id) in ordered_ids.iter().enumerate() {
+        stmt.execute(rusqlite::params![position as i32, id])
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::schema::create_tables;
+
+    fn connection() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        create_tables(&conn).unwrap();
+        for user_id in [1, 2] {
+            conn.execute(
+                "INSERT INTO users (id, username_encrypted, username_nonce, password_hash, master_key_hash) VALUES (?, '', '', '', '')",
+                [user_id],
+            ).unwrap();
+        }
+        conn
+    }
+
+    fn add_collection(conn: &Connection, id: &str, user_id: i32, position: i32, created_at: i64, vault_ids: &[&str]) {
+        conn.execute(
+            "INSERT INTO collections (id, user_id, name_encrypted, name_nonce, vault_ids, created_at, position) VALUES (?, ?, '', '', ?, ?, ?)",
+            rusqlite::params![id, user_id, serde_json::to_string(vault_ids).unwrap(), created_at, position],
+        ).unwrap();
+    }
+
+    fn add_vault(conn: &Connection, id: &str, position: i32, created_at: i64) {
+        conn.execute(
+            "INSERT INTO vaults (id, user_id, name_encrypted, name_nonce, color, created_at, position) VALUES (?, 1, '', '', '', ?, ?)",
+            rusqlite::params![id, created_at, position],
+        ).unwrap();
+    }
+
+    fn positions(conn: &Connection, kind: ReorderKind, parent_id: Option<&str>) -> Vec<(String, i32)> {
+        siblings(conn, kind, parent_id, 1).unwrap()
+    }
+
+    fn ids(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn collisions_are_renumbered_in_display_order() {
+        let conn = connection();
+        add_collection(&conn, "a", 1, 1, 10, &[]);
+        add_collection(&conn, "b", 1, 1, 20, &[]);
+        add_collection(&conn, "c", 1, 0, 30, &[]);
+        add_collection(&conn, "other", 2, 1, 5, &[]);
+
+        renumber_on_collision(&conn, ReorderKind::Collection, None, 1).unwrap();
+
+        assert_eq!(positions(&conn, ReorderKind::Collection, None), [("c".to_string(), 0), ("a".to_string(), 1), ("b".to_string(), 2)]);
+        let other: i32 = conn.query_row("SELECT position FROM collections WHERE id = 'other'", [], |row| row.get(0)).unwrap();
+        assert_eq!(other, 1);
+    }
+
+    #[test]
+    fn distinct_positions_are_left_alone() {
+        let conn = connection();
+        add_collection(&conn, "a", 1, 5, 10, &[]);
+        add_collection(&conn, "b", 1, 9, 20, &[]);
+
+        renumber_on_collision(&conn, ReorderKind::Collection, None, 1).unwrap();
+
+        assert_eq!(positions(&conn, ReorderKind::Collection, None), [("a".to_string(), 5), ("b".to_string(), 9)]);
+    }
+
+    #[test]
+    fn vaults_collide_only_within_their_collection() {
+        let conn = connection();
+        add_collection(&conn, "work", 1, 0, 10, &["v1", "v2"]);
+        add_vault(&conn, "v1", 0, 10);
+        add_vault(&conn, "v2", 1, 20);
+        add_vault(&conn, "loose1", 0, 30);
+        add_vault(&conn, "loose2", 0, 40);
+
+        renumber_on_collision(&conn, ReorderKind::Vault, Some("work"), 1).unwrap();
+        assert_eq!(positions(&conn, ReorderKind::Vault, Some("work")), [("v1".to_string(), 0), ("v2".to_string(), 1)]);
+
+        renumber_on_collision(&conn, ReorderKind::Vault, None, 1).unwrap();
+        assert_eq!(positions(&conn, ReorderKind::Vault, None), [("loose1".to_string(), 0), ("loose2".to_string(), 1)]);
+    }
+
+    #[test]
+    fn new_order_lists_every_sibling_once() {
+        let current = ids(&["a", "b", "c"]);
+        assert!(check_same_ids(&current, &ids(&["c", "a", "b"])).is_ok());
+        assert!(check_same_ids(&current, &ids(&["a", "b"])).is_err());
+        assert!(check_same_ids(&current, &ids(&["a", "b", "b"])).is_err());
+        assert!(check_same_ids(&current, &ids(&["a", "b", "c", "d"])).is_err());
+    }
+}
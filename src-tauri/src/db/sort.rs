@@ -0,0 +1,108 @@
+use std::cmp::Reverse;
+
+use serde::Deserialize;
+
+use crate::db::items::Item;
+use crate::models::{Collection, CreditCard, IdCard, ItemSummary, LoginKey, Note, Vault};
+
+/// Order of the records returned by list commands. `Position` keeps the
+/// order the list has without one (the user's manual order, or favorites
+/// order); the time based modes put the most recent first.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SortMode {
+    #[default]
+    Position,
+    Name,
+    RecentlyUsed,
+    RecentlyUpdated,
+    Created,
+}
+
+/// What a listed record is sorted by.
+pub trait Sortable {
+    fn sort_name(&self) -> &str;
+    fn created_at(&self) -> i64;
+    fn updated_at(&self) -> i64;
+    fn last_used_at(&self) -> Option<i64>;
+}
+
+/// Sorts `records` by `mode`. The sort is stable, so records that compare
+/// equal, e.g. those never used, keep their order.
+pub fn sort_records<S: Sortable>(records: &mut [S], mode: SortMode) {
+    match mode {
+        SortMode::Position => {}
+        SortMode::Name => records.sort_by_cached_key(|r| r.sort_name().to_lowercase()),
+        SortMode::RecentlyUsed => records.sort_by_key(|r| Reverse(r.last_used_at())),
+        SortMode::RecentlyUpdated => records.sort_by_key(|r| Reverse(r.updated_at())),
+        SortMode::Created => records.sort_by_key(|r| Reverse(r.created_at())),
+    }
+}
+
+macro_rules! impl_sortable {
+    ($t:ty, $name:ident) => {
+        impl Sortable for $t {
+            fn sort_name(&self) -> &str {
+                &self.$name
+            }
+
+            fn created_at(&self) -> i64 {
+                self.created_at
+            }
+
+            fn updated_at(&self) -> i64 {
+                self.updated_at
+            }
+
+            fn last_used_at(&self) -> Option<i64> {
+                self.last_used_at
+            }
+        }
+    };
+}
+
+impl_sortable!(Collection, name);
+impl_sortable!(Vault, name);
+impl_sortable!(LoginKey, site_name);
+impl_sortable!(CreditCard, card_name);
+impl_sortable!(IdCard, id_name);
+impl_sortable!(Note, note_name);
+impl_sortable!(ItemSummary, title);
+
+impl Sortable for Item {
+    fn sort_name(&self) -> &str {
+        match self {
+            Item::LoginKey(item) => item.sort_name(),
+            Item::CreditCard(item) => item.sort_name(),
+            Item::IdCard(item) => item.sort_name(),
+            Item::Note(item) => item.sort_name(),
+        }
+    }
+
+    fn created_at(&self) -> i64 {
+        match self {
+            Item::LoginKey(item) => item.created_at,
+            Item::CreditCard(item) => item.created_at,
+            Item::IdCard(item) => item.created_at,
+            Item::Note(item) => item.created_at,
+        }
+    }
+
+    fn updated_at(&self) -> i64 {
+        match self {
+            Item::LoginKey(item) => item.updated_at,
+            Item::CreditCard(item) => item.updated_at,
+            Item::IdCard(item) => item.updated_at,
+            Item::Note(item) => item.updated_at,
+        }
+    }
+
+    fn last_used_at(&self) -> Option<i64> {
+        match self {
+            Item::LoginKey(item) => item.last_used_at,
+            Item::CreditCard(item) => item.last_used_at,
+            Item::IdCard(item) => item.last_used_at,
+            Item::Note(item) => item.last_used_at,
+        }
+    }
+}
@@ -0,0 +1,277 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+use generic_array::GenericArray;
+use rusqlite::{params_from_iter, types::Value, Connection, OptionalExtension};
+use typenum::U32;
+
+use crate::auth::Database;
+use crate::crypto::{decrypt_from_base64, encrypt_to_base64, keyed_hash};
+use crate::db::items::{check_item_owner, query_items, with_item_type, Item, ItemKind, VaultItem};
+use crate::db::vault_lock::VaultKeys;
+use crate::models::{Tag, TagCount};
+
+const MAX_TAG_NAME_LENGTH: usize = 64;
+/// Domain of the keyed hash used to find a tag by name without decrypting them all.
+const TAG_NAME_DOMAIN: &[u8] = b"tag-name";
+
+impl Database {
+    /// Tags an item, creating the tag on first use. Names match case-insensitively.
+    pub fn add_tag<T: VaultItem>(&self, item_id: &str, name: &str, user_id: i32) -> Result<Tag, String> {
+        let name = normalize_tag_name(name)?;
+        let conn = self.conn.lock().unwrap();
+        let key = self.get_encryption_key(user_id)?;
+
+        check_item_owner::<T>(&conn, item_id, user_id)?;
+
+        let tag_id = find_or_create_tag(&conn, &name, user_id, &key)?;
+        tag_item(&conn, &tag_id, T::KIND, item_id)?;
+
+        self.reindex_item::<T>(&conn, item_id, user_id)?;
+
+        let name = load_tag_name(&conn, &tag_id, &key)?;
+        Ok(Tag { id: tag_id, name })
+    }
+
+    pub fn remove_tag<T: VaultItem>(&self, item_id: &str, tag_id: &str, user_id: i32) -> Result<(), String> {
+        let conn = self.conn.lock().unwrap();
+        check_item_owner::<T>(&conn, item_id, user_id)?;
+        conn.execute(
+            "DELETE FROM item_tags WHERE tag_id = ? AND item_id = ?",
+            rusqlite::params![tag_id, item_id],
+        ).map_err(|e| e.to_string())?;
+        self.reindex_item::<T>(&conn, item_id, user_id)
+    }
+
+    /// Renames a tag. Renaming it to the name of another tag merges the two.
+    pub fn rename_tag(&self, tag_id: &str, name: &str, user_id: i32) -> Result<Tag, String> {
+        let name = normalize_tag_name(name)?;
+        let mut conn = self.conn.lock().unwrap();
+        let key = self.get_encryption_key(user_id)?;
+
+        check_tag_owner(&conn, tag_id, user_id)?;
+
+        let name_hash = tag_name_hash(&name, &key);
+        let duplicate: Option<String> = conn.query_row(
+            "SELECT id FROM tags WHERE user_id = ? AND name_hash = ? AND id != ?",
+            rusqlite::params![user_id, &name_hash, tag_id],
+            |row| row.get(0)
+        ).optional().map_err(|e| e.to_string())?;
+        let (name_encrypted, name_nonce) = encrypt_to_base64(&name, &key)?;
+
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let id = match duplicate {
+            Some(target) => {
+                tx.execute(
+                    "INSERT OR IGNORE INTO item_tags (tag_id, item_kind, item_id) SELECT ?, item_kind, item_id FROM item_tags WHERE tag_id = ?",
+                    rusqlite::params![&target, tag_id],
+                ).map_err(|e| e.to_string())?;
+                tx.execute("DELETE FROM item_tags WHERE tag_id = ?", [tag_id])
+                    .map_err(|e| e.to_string())?;
+                tx.execute("DELETE FROM tags WHERE id = ?", [tag_id])
+                    .map_err(|e| e.to_string())?;
+                target
+            }
+            None => tag_id.to_string(),
+        };
+        tx.execute(
+            "UPDATE tags SET name_encrypted = ?, name_nonce = ?, name_hash = ? WHERE id = ?",
+            rusqlite::params![&name_encrypted, &name_nonce, &name_hash, &id],
+        ).map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())?;
+        self.rebuild_search_index(&conn, user_id)?;
+
+        Ok(Tag { id, name })
+    }
+
+    pub fn delete_tag(&self, tag_id: &str, user_id: i32) -> Result<(), String> {
+        let conn = self.conn.lock().unwrap();
+        check_tag_owner(&conn, tag_id, user_id)?;
+        conn.execute("DELETE FROM item_tags WHERE tag_id = ?", [tag_id])
+            .map_err(|e| e.to_string())?;
+        conn.execute("DELETE FROM tags WHERE id = ?", [tag_id])
+            .map_err(|e| e.to_string())?;
+        self.rebuild_search_index(&conn, user_id)
+    }
+
+    /// All tags of the user with the number of items carrying each, by name.
+    pub fn get_tags(&self, user_id: i32) -> Result<Vec<TagCount>, String> {
+        let conn = self.conn.lock().unwrap();
+        let key = self.get_encryption_key(user_id)?;
+
+        let mut stmt = conn.prepare(
+            "SELECT t.id, t.name_encrypted, t.name_nonce, COUNT(it.item_id) FROM tags t LEFT JOIN item_tags it ON it.tag_id = t.id WHERE t.user_id = ? GROUP BY t.id"
+        ).map_err(|e| e.to_string())?;
+
+        let rows = stmt.query_map([user_id], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, i64>(3)?,
+            ))
+        }).map_err(|e| e.to_string())?;
+
+        let mut result = Vec::new();
+        for row in rows {
+            let (id, name_encrypted, name_nonce, item_count) = row.map_err(|e| e.to_string())?;
+            let name = decrypt_from_base64(&name_encrypted, &name_nonce, &key)?;
+            result.push(TagCount { tag: Tag { id, name }, item_count });
+        }
+        result.sort_by_key(|t| t.tag.name.to_lowercase());
+        Ok(result)
+    }
+
+    /// Items in any vault of the user carrying one of `tag_ids`, or all of them
+    /// when `match_all` is set. Vaults with a passphrase are left out.
+    pub fn get_items_by_tags(&self, tag_ids: &[String], match_all: bool, user_id: i32) -> Result<Vec<Item>, String> {
+        let mut tag_ids = tag_ids.to_vec();
+        tag_ids.sort();
+        tag_ids.dedup();
+        if tag_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let conn = self.conn.lock().unwrap();
+        let keys = VaultKeys::unprotected(self.get_encryption_key(user_id)?);
+
+        let having = if match_all {
+            format!(" HAVING COUNT(DISTINCT tag_id) = {}", tag_ids.len())
+        } else {
+            String::new()
+        };
+        let mut params: Vec<Value> = tag_ids.into_iter().map(Value::from).collect();
+        params.push(Value::from(user_id));
+
+        let mut items = Vec::new();
+        for kind in ItemKind::ALL {
+            let clause = format!(
+                "WHERE id IN (SELECT item_id FROM item_tags WHERE item_kind = '{}' AND tag_id IN ({}) GROUP BY item_id{}) AND vault_id IN (SELECT id FROM vaults WHERE user_id = ? AND vault_key_encrypted IS NULL) ORDER BY created_at ASC",
+                kind.as_str(),
+                vec!["?"; params.len() - 1].join(", "),
+                having
+            );
+            with_item_type!(kind, |T| {
+                let found: Vec<T> = query_items(&conn, &clause, &params, &keys)?;
+                items.extend(found.into_iter().map(T::into_item));
+            });
+        }
+        Ok(items)
+    }
+}
+
+/// Id of the user's tag named `name`, which is created if missing.
+pub(crate) fn find_or_create_tag(conn: &Connection, name: &str, user_id: i32, key: &GenericArray<u8, U32>) -> Result<String, String> {
+    let name_hash = tag_name_hash(name, key);
+    let existing: Option<String> = conn.query_row(
+        "SELECT id FROM tags WHERE user_id = ? AND name_hash = ?",
+        rusqlite::params![user_id, &name_hash],
+        |row| row.get(0)
+    ).optional().map_err(|e| e.to_string())?;
+
+    match existing {
+        Some(id) => Ok(id),
+        None => {
+            let id = uuid::Uuid::new_v4().to_string();
+            let (name_encrypted, name_nonce) = encrypt_to_base64(name, key)?;
+            conn.execute(
+                "INSERT INTO tags (id, user_id, name_encrypted, name_nonce, name_hash, created_at) VALUES (?, ?, ?, ?, ?, ?)",
+                rusqlite::params![&id, user_id, &name_encrypted, &name_nonce, &name_hash, Utc::now().timestamp_millis()],
+            ).map_err(|e| e.to_string())?;
+            Ok(id)
+        }
+    }
+}
+
+pub(crate) fn tag_item(conn: &Connection, tag_id: &str, kind: ItemKind, item_id: &str) -> Result<(), String> {
+    conn.execute(
+        "INSERT OR IGNORE INTO item_tags (tag_id, item_kind, item_id) VALUES (?, ?, ?)",
+        rusqlite::params![tag_id, kind.as_str(), item_id],
+    ).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Tags of each of `item_ids`, sorted by name.
+pub(crate) fn load_item_tags(conn: &Connection, item_ids: &[String], key: &GenericArray<u8, U32>) -> Result<HashMap<String, Vec<Tag>>, String> {
+    let mut result: HashMap<String, Vec<Tag>> = HashMap::new();
+    let mut names: HashMap<String, String> = HashMap::new();
+
+    // Stays well below SQLite's limit on bound parameters.
+    for chunk in item_ids.chunks(500) {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT it.item_id, t.id, t.name_encrypted, t.name_nonce FROM item_tags it JOIN tags t ON t.id = it.tag_id WHERE it.item_id IN ({})",
+            vec!["?"; chunk.len()].join(", ")
+        )).map_err(|e| e.to_string())?;
+
+        let rows = stmt.query_map(params_from_iter(chunk), |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+            ))
+        }).map_err(|e| e.to_string())?;
+
+        for row in rows {
+            let (item_id, tag_id, name_encrypted, name_nonce) = row.map_err(|e| e.to_string())?;
+            let name = match names.get(&tag_id) {
+                Some(name) => name.clone(),
+                None => {
+                    let name = decrypt_from_base64(&name_encrypted, &name_nonce, key)?;
+                    names.insert(tag_id.clone(), name.clone());
+                    name
+                }
+            };
+            result.entry(item_id).or_default().push(Tag { id: tag_id, name });
+        }
+    }
+
+    for tags in result.values_mut() {
+        tags.sort_by_key(|t| t.name.to_lowercase());
+    }
+    Ok(result)
+}
+
+pub(crate) fn copy_item_tags(conn: &Connection, kind: ItemKind, from_item_id: &str, to_item_id: &str) -> Result<(), String> {
+    conn.execute(
+        "INSERT OR IGNORE INTO item_tags (tag_id, item_kind, item_id) SELECT tag_id, ?, ? FROM item_tags WHERE item_id = ?",
+        rusqlite::params![kind.as_str(), to_item_id, from_item_id],
+    ).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn check_tag_owner(conn: &Connection, tag_id: &str, user_id: i32) -> Result<(), String> {
+    let count: i32 = conn.query_row(
+        "SELECT COUNT(*) FROM tags WHERE id = ? AND user_id = ?",
+        rusqlite::params![tag_id, user_id],
+        |row| row.get(0)
+    ).map_err(|e| e.to_string())?;
+    if count == 0 {
+        return Err("Tag not found".to_string());
+    }
+    Ok(())
+}
+
+fn load_tag_name(conn: &Connection, tag_id: &str, key: &GenericArray<u8, U32>) -> Result<String, String> {
+    let (name_encrypted, name_nonce): (String, String) = conn.query_row(
+        "SELECT name_encrypted, name_nonce FROM tags WHERE id = ?",
+        [tag_id],
+        |row| Ok((row.get(0)?, row.get(1)?))
+    ).map_err(|e| e.to_string())?;
+    Ok(decrypt_from_base64(&name_encrypted, &name_nonce, key)?)
+}
+
+pub(crate) fn normalize_tag_name(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Tag name cannot be empty".to_string());
+    }
+    if name.chars().count() > MAX_TAG_NAME_LENGTH {
+        return Err(format!("Tag names are limited to {} characters", MAX_TAG_NAME_LENGTH));
+    }
+    Ok(name.to_string())
+}
+
+fn tag_name_hash(name: &str, key: &GenericArray<u8, U32>) -> String {
+    keyed_hash(key, TAG_NAME_DOMAIN, name.to_lowercase().as_bytes())
+}
@@ -0,0 +1,149 @@
+use chrono::Utc;
+use generic_array::GenericArray;
+use rusqlite::{Connection, OptionalExtension};
+use typenum::U32;
+
+use crate::auth::Database;
+use crate::crypto::{decrypt_from_base64, encrypt_to_base64};
+use crate::db::items::{check_field_names, with_item_type, Item, ItemFields, ItemKind, VaultItem};
+use crate::db::vaults::check_vault_owner;
+use crate::models::ItemTemplate;
+
+impl Database {
+    pub fn create_item_template<T: VaultItem>(&self, name: &str, fields: &ItemFields, color: &str, user_id: i32) -> Result<ItemTemplate, String> {
+        let name = normalize_template_name(name)?;
+        check_field_names::<T>(fields)?;
+
+        let conn = self.conn.lock().unwrap();
+        let key = self.get_encryption_key(user_id)?;
+
+        let id = uuid::Uuid::new_v4().to_string();
+        let created_at = Utc::now().timestamp_millis();
+        let (name_encrypted, name_nonce) = encrypt_to_base64(&name, &key)?;
+        let (fields_encrypted, fields_nonce) = encrypt_fields(fields, &key)?;
+
+        conn.execute(
+            "INSERT INTO item_templates (id, user_id, item_kind, name_encrypted, name_nonce, fields_encrypted, fields_nonce, color, created_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            rusqlite::params![&id, user_id, T::KIND.as_str(), &name_encrypted, &name_nonce, &fields_encrypted, &fields_nonce, color, created_at],
+        ).map_err(|e| e.to_string())?;
+
+        Ok(ItemTemplate {
+            id,
+            kind: T::KIND,
+            name,
+            fields: fields.clone(),
+            color: color.to_string(),
+            created_at,
+        })
+    }
+
+    /// Templates of the user, only those for `kind` if given, by name.
+    pub fn get_item_templates(&self, kind: Option<ItemKind>, user_id: i32) -> Result<Vec<ItemTemplate>, String> {
+        let conn = self.conn.lock().unwrap();
+        let key = self.get_encryption_key(user_id)?;
+
+        let mut stmt = conn.prepare(
+            "SELECT id FROM item_templates WHERE user_id = ? AND (?2 IS NULL OR item_kind = ?2)"
+        ).map_err(|e| e.to_string())?;
+        let ids: Vec<String> = stmt.query_map(rusqlite::params![user_id, kind.map(|k| k.as_str())], |row| row.get(0))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<_, _>>()
+            .map_err(|e| e.to_string())?;
+
+        let mut templates = Vec::with_capacity(ids.len());
+        for id in ids {
+            if let Some(template) = load_template(&conn, &id, user_id, &key)? {
+                templates.push(template);
+            }
+        }
+        templates.sort_by_key(|t| t.name.to_lowercase());
+        Ok(templates)
+    }
+
+    pub fn update_item_template(&self, template_id: &str, name: &str, fields: &ItemFields, color: &str, user_id: i32) -> Result<(), String> {
+        let name = normalize_template_name(name)?;
+
+        let conn = self.conn.lock().unwrap();
+        let key = self.get_encryption_key(user_id)?;
+
+        let template = load_template(&conn, template_id, user_id, &key)?
+            .ok_or("Template not found".to_string())?;
+        with_item_type!(template.kind, |T| check_field_names::<T>(fields))?;
+
+        let (name_encrypted, name_nonce) = encrypt_to_base64(&name, &key)?;
+        let (fields_encrypted, fields_nonce) = encrypt_fields(fields, &key)?;
+        conn.execute(
+            "UPDATE item_templates SET name_encrypted = ?, name_nonce = ?, fields_encrypted = ?, fields_nonce = ?, color = ? WHERE id = ?",
+            rusqlite::params![&name_encrypted, &name_nonce, &fields_encrypted, &fields_nonce, color, template_id],
+        ).map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    pub fn delete_item_template(&self, template_id: &str, user_id: i32) -> Result<(), String> {
+        let conn = self.conn.lock().unwrap();
+        let deleted = conn.execute(
+            "DELETE FROM item_templates WHERE id = ? AND user_id = ?",
+            rusqlite::params![template_id, user_id],
+        ).map_err(|e| e.to_string())?;
+        if deleted == 0 {
+            return Err("Template not found".to_string());
+        }
+        Ok(())
+    }
+
+    /// Creates an item in `vault_id` from a template, `fields` taking
+    /// precedence over the values the template fills in.
+    pub fn create_item_from_template(&self, template_id: &str, vault_id: &str, fields: ItemFields, user_id: i32) -> Result<Item, String> {
+        let template = {
+            let conn = self.conn.lock().unwrap();
+            let key = self.get_encryption_key(user_id)?;
+            check_vault_owner(&conn, vault_id, user_id)?;
+            load_template(&conn, template_id, user_id, &key)?
+                .ok_or("Template not found".to_string())?
+        };
+
+        let mut merged = template.fields;
+        merged.extend(fields);
+        with_item_type!(template.kind, |T| {
+            let item = self.create_item::<T>(vault_id, &merged, &template.color, None, user_id)?;
+            Ok(item.into_item())
+        })
+    }
+}
+
+fn load_template(conn: &Connection, template_id: &str, user_id: i32, key: &GenericArray<u8, U32>) -> Result<Option<ItemTemplate>, String> {
+    let row: Option<(String, String, String, String, String, String, i64)> = conn.query_row(
+        "SELECT item_kind, name_encrypted, name_nonce, fields_encrypted, fields_nonce, color, created_at FROM item_templates WHERE id = ? AND user_id = ?",
+        rusqlite::params![template_id, user_id],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?, row.get(6)?))
+    ).optional().map_err(|e| e.to_string())?;
+
+    let (kind, name_encrypted, name_nonce, fields_encrypted, fields_nonce, color, created_at) = match row {
+        Some(row) => row,
+        None => return Ok(None),
+    };
+    let fields = decrypt_from_base64(&fields_encrypted, &fields_nonce, key)?;
+
+    Ok(Some(ItemTemplate {
+        id: template_id.to_string(),
+        kind: ItemKind::parse(&kind).ok_or(format!("Unknown item kind '{}'", kind))?,
+        name: decrypt_from_base64(&name_encrypted, &name_nonce, key)?,
+        fields: serde_json::from_str(&fields).map_err(|e| e.to_string())?,
+        color,
+        created_at,
+    }))
+}
+
+/// The fields of a template are stored as one encrypted JSON object.
+fn encrypt_fields(fields: &ItemFields, key: &GenericArray<u8, U32>) -> Result<(String, String), String> {
+    let json = serde_json::to_string(fields).map_err(|e| e.to_string())?;
+    Ok(encrypt_to_base64(&json, key)?)
+}
+
+fn normalize_template_name(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Template name cannot be empty".to_string());
+    }
+    Ok(name.to_string())
+}
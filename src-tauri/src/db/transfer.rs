@@ -0,0 +1,166 @@
+use chrono::Utc;
+use rusqlite::{types::Value, Connection};
+
+use crate::auth::Database;
+use crate::db::attachments::{copy_item_attachments, remove_attachment_files};
+use crate::db::custom_fields::copy_custom_fields;
+use crate::db::full_text::reindex_item_text;
+use crate::db::items::{check_item_owner, insert_item_copy, item_vault, load_item, next_item_position, query_items, reencrypt_item, with_item_type, Item, VaultItem};
+use crate::db::tags::copy_item_tags;
+use crate::db::vault_lock::VaultKeys;
+use crate::db::vaults::check_vault_owner;
+use crate::models::ItemRef;
+
+impl Database {
+    /// Moves items of any type to the end of another vault of the user. They
+    /// keep their ids, timestamps, custom fields, tags and attachments. Either
+    /// every item moves or none does.
+    pub fn move_items(&self, items: &[ItemRef], target_vault_id: &str, user_id: i32) -> Result<(), String> {
+        let mut conn = self.conn.lock().unwrap();
+        self.get_encryption_key(user_id)?;
+
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        check_vault_owner(&tx, target_vault_id, user_id)?;
+        let target_keys = self.vault_keys(&tx, target_vault_id, user_id)?;
+        for item in items {
+            with_item_type!(item.kind, |T| self.move_item::<T>(&tx, &item.id, target_vault_id, user_id, &target_keys)?);
+        }
+        tx.commit().map_err(|e| e.to_string())?;
+
+        for item in items {
+            with_item_type!(item.kind, |T| self.reindex_item::<T>(&conn, &item.id, user_id)?);
+        }
+        Ok(())
+    }
+
+    /// Copies items of any type to the end of a vault of the user, custom
+    /// fields, tags and attachments included. Returns the copies in the order
+    /// of `items`.
+    pub fn copy_items(&self, items: &[ItemRef], target_vault_id: &str, user_id: i32) -> Result<Vec<Item>, String> {
+        let mut conn = self.conn.lock().unwrap();
+        self.get_encryption_key(user_id)?;
+
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        check_vault_owner(&tx, target_vault_id, user_id)?;
+        let target_keys = self.vault_keys(&tx, target_vault_id, user_id)?;
+
+        let mut created_files = Vec::new();
+        let mut copies = Vec::with_capacity(items.len());
+        for item in items {
+            let copied = with_item_type!(item.kind, |T| {
+                check_item_owner::<T>(&tx, &item.id, user_id).and_then(|_| {
+                    let position = next_item_position::<T>(&tx, target_vault_id)?;
+                    self.copy_item::<T>(&tx, &item.id, target_vault_id, position, user_id, &mut created_files)
+                })
+            });
+            match copied {
+                Ok(id) => copies.push(ItemRef { kind: item.kind, id }),
+                Err(e) => {
+                    remove_attachment_files(&self.data_dir, &created_files);
+                    return Err(e);
+                }
+            }
+        }
+        if let Err(e) = tx.commit() {
+            remove_attachment_files(&self.data_dir, &created_files);
+            return Err(e.to_string());
+        }
+
+        let mut result = Vec::with_capacity(copies.len());
+        for copy in &copies {
+            with_item_type!(copy.kind, |T| {
+                self.reindex_item::<T>(&conn, &copy.id, user_id)?;
+                let found: Vec<T> = query_items(&conn, "WHERE id = ?", &[Value::from(copy.id.clone())], &target_keys)?;
+                result.extend(found.into_iter().map(T::into_item));
+            });
+        }
+        Ok(result)
+    }
+
+    /// Copies an item into its own vault, right after the original. The copy
+    /// counts as created now.
+    pub fn duplicate_item<T: VaultItem>(&self, item_id: &str, user_id: i32) -> Result<T, String> {
+        let mut conn = self.conn.lock().unwrap();
+        self.get_encryption_key(user_id)?;
+
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        check_item_owner::<T>(&tx, item_id, user_id)?;
+        let keys = self.item_vault_keys::<T>(&tx, item_id, user_id)?;
+        let (vault_id, position): (String, i32) = tx.query_row(
+            &format!("SELECT vault_id, position FROM {} WHERE id = ?", T::TABLE),
+            [item_id],
+            |row| Ok((row.get(0)?, row.get(1)?))
+        ).map_err(|e| e.to_string())?;
+
+        tx.execute(
+            &format!("UPDATE {} SET position = position + 1 WHERE vault_id = ? AND position > ?", T::TABLE),
+            rusqlite::params![&vault_id, position],
+        ).map_err(|e| e.to_string())?;
+
+        let mut created_files = Vec::new();
+        let copied = self.copy_item::<T>(&tx, item_id, &vault_id, position + 1, user_id, &mut created_files)
+            .and_then(|id| {
+                let now = Utc::now().timestamp_millis();
+                tx.execute(
+                    &format!("UPDATE {} SET created_at = ?1, updated_at = ?1 WHERE id = ?2", T::TABLE),
+                    rusqlite::params![now, &id],
+                ).map_err(|e| e.to_string())?;
+                Ok(id)
+            });
+        let id = match copied.and_then(|id| tx.commit().map(|_| id).map_err(|e| e.to_string())) {
+            Ok(id) => id,
+            Err(e) => {
+                remove_attachment_files(&self.data_dir, &created_files);
+                return Err(e);
+            }
+        };
+
+        self.reindex_item::<T>(&conn, &id, user_id)?;
+        load_item(&conn, &id, &keys)?.ok_or("Item not found".to_string())
+    }
+
+    /// Moves an item to the end of `vault_id`, whose keys are `target`. Its
+    /// fields are encrypted again when only one of the vaults has a passphrase.
+    pub(crate) fn move_item<T: VaultItem>(&self, conn: &Connection, item_id: &str, vault_id: &str, user_id: i32, target: &VaultKeys) -> Result<(), String> {
+        check_item_owner::<T>(conn, item_id, user_id)?;
+        let current = item_vault::<T>(conn, item_id)?.ok_or("Item not found".to_string())?;
+        if current == vault_id {
+            return Ok(());
+        }
+
+        let source = self.vault_keys(conn, &current, user_id)?;
+        if source.items != target.items {
+            reencrypt_item::<T>(conn, item_id, &source.items, &target.items)?;
+        }
+        let position = next_item_position::<T>(conn, vault_id)?;
+        conn.execute(
+            &format!("UPDATE {} SET vault_id = ?, position = ? WHERE id = ?", T::TABLE),
+            rusqlite::params![vault_id, position, item_id],
+        ).map_err(|e| e.to_string())?;
+        // The target vault may have the full-text index turned off, or on.
+        reindex_item_text::<T>(conn, item_id, &target.session)
+    }
+
+    /// Copies an item with everything hanging off it into `vault_id` at
+    /// `position`. Ids of the attachment files written are pushed to
+    /// `created_files`. Returns the id of the copy.
+    fn copy_item<T: VaultItem>(
+        &self,
+        conn: &Connection,
+        item_id: &str,
+        vault_id: &str,
+        position: i32,
+        user_id: i32,
+        created_files: &mut Vec<String>,
+    ) -> Result<String, String> {
+        let source = self.item_vault_keys::<T>(conn, item_id, user_id)?;
+        let target = self.vault_keys(conn, vault_id, user_id)?;
+
+        let id = insert_item_copy::<T>(conn, item_id, vault_id, position, &source.items, &target.items)?;
+        copy_custom_fields(conn, T::KIND, item_id, &id, &source.items, &target.items)?;
+        copy_item_tags(conn, T::KIND, item_id, &id)?;
+        copy_item_attachments(conn, &self.data_dir, item_id, &id, &source.items, &target.items, created_files)?;
+        reindex_item_text::<T>(conn, &id, &target.session)?;
+        Ok(id)
+    }
+}
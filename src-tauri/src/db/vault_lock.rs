@@ -0,0 +1,204 @@
+use argon2::password_hash::SaltString;
+use chrono::Utc;
+use generic_array::GenericArray;
+use rand::thread_rng;
+use rusqlite::{Connection, OptionalExtension};
+use typenum::U32;
+
+use crate::auth::keys::{unwrap_key, wrap_key};
+use crate::auth::Database;
+use crate::crypto::{derive_encryption_key, derive_key_from_secret, generate_key};
+use crate::db::full_text::delete_vault_text;
+use crate::db::items::{item_vault, reencrypt_item, with_item_type, ItemKind, VaultItem};
+use crate::db::vaults::check_vault_owner;
+
+/// How long a vault stays unlocked.
+pub const VAULT_UNLOCK_MILLIS: i64 = 5 * 60 * 1000;
+/// Returned when reading or writing the items of a vault with a passphrase
+/// that is not unlocked.
+pub const VAULT_LOCKED: &str = "This vault is locked, unlock it with its passphrase";
+const MIN_PASSPHRASE_LENGTH: usize = 8;
+const VAULT_KEY_DOMAIN: &[u8] = b"vault-passphrase";
+
+/// The key of a vault with a passphrase, held in memory from `unlock_vault`
+/// until it expires or the session ends.
+pub struct VaultUnlock {
+    user_id: i32,
+    key: GenericArray<u8, U32>,
+    expires_at: i64,
+}
+
+/// The keys the items of one vault are read and written with.
+pub(crate) struct VaultKeys {
+    /// The session key. Vault names, tags and images stay encrypted with it
+    /// in every vault.
+    pub session: GenericArray<u8, U32>,
+    /// The key of the items' fields, custom fields and attachment keys: the
+    /// vault's own key when it has a passphrase, otherwise the session key.
+    pub items: GenericArray<u8, U32>,
+}
+
+impl VaultKeys {
+    /// The keys of a vault without a passphrase.
+    pub fn unprotected(session: GenericArray<u8, U32>) -> Self {
+        VaultKeys { session, items: session }
+    }
+}
+
+impl Database {
+    /// Protects a vault with a passphrase. Its items are encrypted again with
+    /// a new key of the vault, wrapped with one derived from the passphrase
+    /// and the session key, so reading them takes `unlock_vault` on top of
+    /// the session. The vault drops out of search, the full-text index,
+    /// favorites and every other listing across vaults.
+    pub fn set_vault_passphrase(&self, vault_id: &str, passphrase: &str, user_id: i32) -> Result<(), String> {
+        if passphrase.chars().count() < MIN_PASSPHRASE_LENGTH {
+            return Err(format!("The vault passphrase must be at least {} characters", MIN_PASSPHRASE_LENGTH));
+        }
+
+        let mut conn = self.conn.lock().unwrap();
+        let session = self.get_encryption_key(user_id)?;
+        check_vault_owner(&conn, vault_id, user_id)?;
+        if stored_vault_key(&conn, vault_id)?.is_some() {
+            return Err("The vault already has a passphrase".to_string());
+        }
+
+        let salt = SaltString::generate(&mut thread_rng());
+        let vault_key = generate_key();
+        let (key_encrypted, key_nonce) = wrap_key(&vault_key, &passphrase_key(passphrase, salt.as_str(), &session)?)?;
+
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        reencrypt_vault_items(&tx, vault_id, &session, &vault_key)?;
+        delete_vault_text(&tx, vault_id)?;
+        tx.execute(
+            "UPDATE vaults SET vault_key_encrypted = ?, vault_key_nonce = ?, passphrase_salt = ?, full_text_search = 0 WHERE id = ?",
+            rusqlite::params![key_encrypted, key_nonce, salt.as_str(), vault_id],
+        ).map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())?;
+
+        self.rebuild_search_index(&conn, user_id)
+    }
+
+    /// Takes the passphrase off a vault and encrypts its items with the
+    /// session key again. The full-text index stays off until turned on.
+    pub fn remove_vault_passphrase(&self, vault_id: &str, passphrase: &str, user_id: i32) -> Result<(), String> {
+        let mut conn = self.conn.lock().unwrap();
+        let session = self.get_encryption_key(user_id)?;
+        check_vault_owner(&conn, vault_id, user_id)?;
+        let vault_key = open_vault_key(&conn, vault_id, passphrase, &session)?;
+
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        reencrypt_vault_items(&tx, vault_id, &vault_key, &session)?;
+        tx.execute(
+            "UPDATE vaults SET vault_key_encrypted = NULL, vault_key_nonce = NULL, passphrase_salt = NULL WHERE id = ?",
+            [vault_id],
+        ).map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())?;
+
+        self.vault_unlocks.lock().unwrap().remove(vault_id);
+        self.rebuild_search_index(&conn, user_id)
+    }
+
+    /// Unlocks a vault with its passphrase for `VAULT_UNLOCK_MILLIS` from now,
+    /// however much it is used meanwhile; ending or locking the session locks
+    /// it earlier. Returns when it locks again, in milliseconds since the epoch.
+    pub fn unlock_vault(&self, vault_id: &str, passphrase: &str, user_id: i32) -> Result<i64, String> {
+        let conn = self.conn.lock().unwrap();
+        let session = self.get_encryption_key(user_id)?;
+        check_vault_owner(&conn, vault_id, user_id)?;
+        let key = open_vault_key(&conn, vault_id, passphrase, &session)?;
+
+        let expires_at = Utc::now().timestamp_millis() + VAULT_UNLOCK_MILLIS;
+        self.vault_unlocks.lock().unwrap().insert(vault_id.to_string(), VaultUnlock { user_id, key, expires_at });
+        Ok(expires_at)
+    }
+
+    pub fn lock_vault(&self, vault_id: &str, user_id: i32) -> Result<(), String> {
+        let conn = self.conn.lock().unwrap();
+        check_vault_owner(&conn, vault_id, user_id)?;
+        self.vault_unlocks.lock().unwrap().remove(vault_id);
+        Ok(())
+    }
+
+    /// When an unlocked vault locks again, or `None` if it is locked now.
+    pub fn vault_unlocked_until(&self, vault_id: &str, user_id: i32) -> Option<i64> {
+        self.vault_unlocks.lock().unwrap()
+            .get(vault_id)
+            .filter(|unlock| unlock.user_id == user_id && unlock.expires_at > Utc::now().timestamp_millis())
+            .map(|unlock| unlock.expires_at)
+    }
+
+    /// Locks every vault the user unlocked, as the session they belong to ends.
+    pub(crate) fn lock_vaults(&self, user_id: i32) {
+        self.vault_unlocks.lock().unwrap().retain(|_, unlock| unlock.user_id != user_id);
+    }
+
+    /// The keys of a vault's items. Fails with `VAULT_LOCKED` for a vault with
+    /// a passphrase that is not unlocked, or whose unlock has expired.
+    pub(crate) fn vault_keys(&self, conn: &Connection, vault_id: &str, user_id: i32) -> Result<VaultKeys, String> {
+        let session = self.get_encryption_key(user_id)?;
+        if stored_vault_key(conn, vault_id)?.is_none() {
+            return Ok(VaultKeys::unprotected(session));
+        }
+
+        let mut unlocks = self.vault_unlocks.lock().unwrap();
+        match unlocks.get(vault_id) {
+            Some(unlock) if unlock.user_id == user_id && unlock.expires_at > Utc::now().timestamp_millis() => {
+                Ok(VaultKeys { session, items: unlock.key })
+            }
+            Some(_) => {
+                unlocks.remove(vault_id);
+                Err(VAULT_LOCKED.to_string())
+            }
+            None => Err(VAULT_LOCKED.to_string()),
+        }
+    }
+
+    /// `vault_keys` of the vault holding an item.
+    pub(crate) fn item_vault_keys<T: VaultItem>(&self, conn: &Connection, item_id: &str, user_id: i32) -> Result<VaultKeys, String> {
+        let vault_id = item_vault::<T>(conn, item_id)?.ok_or("Item not found".to_string())?;
+        self.vault_keys(conn, &vault_id, user_id)
+    }
+}
+
+fn reencrypt_vault_items(conn: &Connection, vault_id: &str, from: &GenericArray<u8, U32>, to: &GenericArray<u8, U32>) -> Result<(), String> {
+    for kind in ItemKind::ALL {
+        let mut stmt = conn.prepare(&format!("SELECT id FROM {} WHERE vault_id = ?", kind.table()))
+            .map_err(|e| e.to_string())?;
+        let ids: Vec<String> = stmt.query_map([vault_id], |row| row.get(0))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<_, _>>()
+            .map_err(|e| e.to_string())?;
+        for id in ids {
+            with_item_type!(kind, |T| reencrypt_item::<T>(conn, &id, from, to)?);
+        }
+    }
+    Ok(())
+}
+
+fn open_vault_key(conn: &Connection, vault_id: &str, passphrase: &str, session: &GenericArray<u8, U32>) -> Result<GenericArray<u8, U32>, String> {
+    let (encrypted, nonce, salt) = stored_vault_key(conn, vault_id)?.ok_or("The vault has no passphrase".to_string())?;
+    unwrap_key(&encrypted, &nonce, &passphrase_key(passphrase, &salt, session)?)
+        .map_err(|_| "Wrong vault passphrase".to_string())
+}
+
+/// The wrapped vault key and the passphrase salt, if the vault has a passphrase.
+fn stored_vault_key(conn: &Connection, vault_id: &str) -> Result<Option<(String, String, String)>, String> {
+    let row: Option<(Option<String>, Option<String>, Option<String>)> = conn.query_row(
+        "SELECT vault_key_encrypted, vault_key_nonce, passphrase_salt FROM vaults WHERE id = ?",
+        [vault_id],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    ).optional().map_err(|e| e.to_string())?;
+
+    Ok(match row {
+        Some((Some(encrypted), Some(nonce), Some(salt))) => Some((encrypted, nonce, salt)),
+        _ => None,
+    })
+}
+
+/// The session key goes into the key wrapping the vault key too, so the
+/// passphrase is useless without the master key.
+fn passphrase_key(passphrase: &str, salt: &str, session: &GenericArray<u8, U32>) -> Result<GenericArray<u8, U32>, String> {
+    let derived = derive_encryption_key(passphrase, salt.as_bytes()).map_err(|e| e.to_string())?;
+    Ok(derive_key_from_secret(&[derived.as_slice(), session.as_slice()].concat(), VAULT_KEY_DOMAIN))
+}
@@ -1,18 +1,23 @@
 use chrono::Utc;
 use generic_array::GenericArray;
-use rusqlite::{Row, OptionalExtension};
+use rusqlite::{Connection, Row, OptionalExtension};
 use typenum::U32;
 
 use crate::auth::Database;
-use crate::crypto::{decrypt_bytes_from_base64, decrypt_from_base64, encrypt_bytes_to_base64, encrypt_to_base64};
+use crate::crypto::{decrypt_from_base64, encrypt_to_base64};
+use crate::db::attachments::remove_attachment_files;
+use crate::db::image_blobs::{collect_image_garbage, release_image, set_row_image, store_image};
+use crate::db::items::{delete_item_rows, with_item_type, ItemKind};
+use crate::db::reorder::{renumber_on_collision, ReorderKind};
+use crate::images::{image_url, thumbnail_url, ImageUpdate};
 use crate::models::Vault;
-use base64::Engine as _;
 
 impl Database {
     pub fn get_vaults(&self, user_id: i32) -> Result<Vec<Vault>, String> {
         let conn = self.conn.lock().unwrap();
+        renumber_vaults_on_collision(&conn, user_id)?;
         let mut stmt = conn.prepare(
-            "SELECT id, user_id, name_encrypted, color, image, image_nonce, name_nonce, created_at, position FROM vaults WHERE user_id = ? ORDER BY position ASC, created_at ASC"
+            "SELECT id, user_id, name_encrypted, color, image_id, name_nonce, created_at, position, full_text_search, COALESCE(updated_at, created_at), last_used_at, vault_key_encrypted IS NOT NULL FROM vaults WHERE user_id = ? ORDER BY position ASC, created_at ASC"
         ).map_err(|e| e.to_string())?;
 
         let key = self.get_encryption_key(user_id)?;
@@ -31,7 +36,7 @@ impl Database {
     pub fn get_vault(&self, vault_id: &str) -> Result<Option<Vault>, String> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
-            "SELECT id, user_id, name_encrypted, color, image, name_nonce, created_at FROM vaults WHERE id = ?"
+            "SELECT id, user_id, name_encrypted, color, image_id, name_nonce, created_at, position, full_text_search, COALESCE(updated_at, created_at), last_used_at, vault_key_encrypted IS NOT NULL FROM vaults WHERE id = ?"
         ).map_err(|e| e.to_string())?;
 
         let user_id: i32 = conn.query_row(
@@ -57,12 +62,9 @@ impl Database {
         let key = self.get_encryption_key(user_id)?;
         let (name_encrypted, name_nonce) = encrypt_to_base64(name, &key)?;
 
-        let (image_encrypted, image_nonce) = match image {
-            Some(img) => {
-                let (enc, nonce) = encrypt_bytes_to_base64(img, &key)?;
-                (Some(enc), Some(nonce))
-            }
-            None => (None, None),
+        let image_id = match image {
+            Some(img) => Some(store_image(&conn, user_id, img, &key)?),
+            None => None,
         };
 
         let vault_position: i32 = conn.query_row(
@@ -72,8 +74,8 @@ impl Database {
         ).unwrap_or(0);
 
         conn.execute(
-            "INSERT INTO vaults (id, user_id, name_encrypted, color, name_nonce, image, image_nonce, created_at, position) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
-            rusqlite::params![&id, user_id, &name_encrypted, color, &name_nonce, image_encrypted, image_nonce, created_at, vault_position],
+            "INSERT INTO vaults (id, user_id, name_encrypted, color, name_nonce, image_id, created_at, updated_at, position) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            rusqlite::params![&id, user_id, &name_encrypted, color, &name_nonce, &image_id, created_at, created_at, vault_position],
         ).map_err(|e| e.to_string())?;
 
         if let Some(col_id) = collection_id {
@@ -86,30 +88,31 @@ impl Database {
             user_id,
             name: name.to_string(),
             color: color.to_string(),
-            image: None,
+            image: image_id.as_deref().map(image_url),
+            thumbnail: image_id.as_deref().map(thumbnail_url),
             created_at,
+            updated_at: created_at,
+            last_used_at: None,
             position: vault_position,
+            full_text_search: true,
+            passphrase_protected: false,
         })
     }
 
-    pub fn update_vault(&self, vault: &Vault, image: Option<&[u8]>) -> Result<(), String> {
+    pub fn update_vault(&self, vault: &Vault, image: ImageUpdate) -> Result<(), String> {
         let conn = self.conn.lock().unwrap();
         let key = self.get_encryption_key(vault.user_id)?;
         let (name_encrypted, name_nonce) = encrypt_to_base64(&vault.name, &key)?;
-
-        let (image_encrypted, image_nonce) = match image {
-            Some(img) => {
-                let (enc, nonce) = encrypt_bytes_to_base64(img, &key)?;
-                (Some(enc), Some(nonce))
-            }
-            None => (None, None),
-        };
+        let updated_at = Utc::now().timestamp_millis();
 
         conn.execute(
-            "UPDATE vaults SET name_encrypted = ?, color = ?, name_nonce = ?, image = ?, image_nonce = ? WHERE id = ?",
-            rusqlite::params![&name_encrypted, &vault.color, &name_nonce, image_encrypted, image_nonce, &vault.id],
+            "UPDATE vaults SET name_encrypted = ?, color = ?, name_nonce = ?, updated_at = ? WHERE id = ?",
+            rusqlite::params![&name_encrypted, &vault.color, &name_nonce, updated_at, &vault.id],
         ).map_err(|e| e.to_string())?;
-        Ok(())
+
+        set_row_image(&conn, "vaults", "image_id", &vault.id, vault.user_id, image, &key)?;
+        // Search results show the vault name of each item.
+        self.rebuild_search_index(&conn, vault.user_id)
     }
 
     pub fn update_vault_position(&self, vault_id: &str, new_position: i32) -> Result<(), String> {
@@ -121,69 +124,105 @@ impl Database {
         Ok(())
     }
 
-    pub fn delete_vault(&self, vault_id: &str) -> Result<(), String> {
-        let conn = self.conn.lock().unwrap();
-        conn.execute("DELETE FROM vaults WHERE id = ?", [vault_id])
+    /// Deletes a vault with every item in it, custom fields, tags and
+    /// attachments included.
+    pub fn delete_vault(&self, vault_id: &str, user_id: i32) -> Result<(), String> {
+        let mut conn = self.conn.lock().unwrap();
+        check_vault_owner(&conn, vault_id, user_id)?;
+
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let attachments = delete_vault_rows(&tx, vault_id)?;
+        collect_image_garbage(&tx, user_id)?;
+        tx.commit().map_err(|e| e.to_string())?;
+
+        remove_attachment_files(&self.data_dir, &attachments);
+        self.vault_unlocks.lock().unwrap().remove(vault_id);
+        self.rebuild_search_index(&conn, user_id)
+    }
+}
+
+/// Deletes a vault and its items with everything hanging off them. Returns
+/// the ids of their attachments, whose files are to be removed once this is
+/// committed.
+pub(crate) fn delete_vault_rows(conn: &Connection, vault_id: &str) -> Result<Vec<String>, String> {
+    let mut attachments = Vec::new();
+    for kind in ItemKind::ALL {
+        let mut stmt = conn.prepare(&format!("SELECT id FROM {} WHERE vault_id = ?", kind.table()))
             .map_err(|e| e.to_string())?;
-        Ok(())
+        let ids: Vec<String> = stmt.query_map([vault_id], |row| row.get(0))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<_, _>>()
+            .map_err(|e| e.to_string())?;
+        for id in ids {
+            attachments.extend(with_item_type!(kind, |T| delete_item_rows::<T>(conn, &id))?);
+        }
+    }
+
+    let vault: Option<(Option<String>, i32)> = conn.query_row(
+        "SELECT image_id, user_id FROM vaults WHERE id = ?",
+        [vault_id],
+        |row| Ok((row.get(0)?, row.get(1)?))
+    ).optional().map_err(|e| e.to_string())?;
+    conn.execute("DELETE FROM vaults WHERE id = ?", [vault_id])
+        .map_err(|e| e.to_string())?;
+    if let Some((Some(image_id), user_id)) = vault {
+        release_image(conn, &image_id, user_id)?;
+    }
+    Ok(attachments)
+}
+
+/// Vault positions count within each collection and among the vaults in none.
+fn renumber_vaults_on_collision(conn: &Connection, user_id: i32) -> Result<(), String> {
+    let mut stmt = conn.prepare("SELECT id FROM collections WHERE user_id = ?")
+        .map_err(|e| e.to_string())?;
+    let collection_ids: Vec<String> = stmt.query_map([user_id], |row| row.get(0))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())?;
+
+    renumber_on_collision(conn, ReorderKind::Vault, None, user_id)?;
+    for collection_id in &collection_ids {
+        renumber_on_collision(conn, ReorderKind::Vault, Some(collection_id), user_id)?;
     }
+    Ok(())
+}
+
+/// Fails unless `vault_id` is a vault of the user.
+pub(crate) fn check_vault_owner(conn: &Connection, vault_id: &str, user_id: i32) -> Result<(), String> {
+    let count: i32 = conn.query_row(
+        "SELECT COUNT(*) FROM vaults WHERE id = ? AND user_id = ?",
+        rusqlite::params![vault_id, user_id],
+        |row| row.get(0)
+    ).map_err(|e| e.to_string())?;
+    if count == 0 {
+        return Err("Vault not found".to_string());
+    }
+    Ok(())
 }
 
 fn vault_from_row(row: &Row, user_id: i32, key: &GenericArray<u8, U32>) -> Result<Vault, rusqlite::Error> {
     let name_encrypted: String = row.get(2)?;
-    let name_nonce: String = row.get(6)?;
-    let image_encrypted: Option<String> = row.get(4)?;
-    let image_nonce: Option<String> = row.get(5)?;
+    let name_nonce: String = row.get(5)?;
+    let id: String = row.get(0)?;
+    let image_id: Option<String> = row.get(4)?;
 
     let name = match decrypt_from_base64(&name_encrypted, &name_nonce, key) {
         Ok(n) => n,
         Err(_) => return Err(rusqlite::Error::InvalidColumnName("Decryption failed".to_string())),
     };
 
-    let image_b64 = match (image_encrypted, image_nonce) {
-        (Some(enc), Some(nonce)) => {
-            match decrypt_bytes_from_base64(&enc, &nonce, key) {
-                Ok(decrypted) => {
-                    let mime = if decrypted.starts_with(b"\x89PNG\r\n\x1a\n") {
-                        "image/png"
-                    } else if decrypted.starts_with(&[0xff, 0xd8, 0xff]) {
-                        "image/jpeg"
-                    } else if decrypted.starts_with(b"<svg") {
-                        "image/svg+xml"
-                    } else {
-                        "image/webp"
-                    };
-                    let b64 = base64::engine::general_purpose::STANDARD.encode(&decrypted);
-                    Some(format!("data:{};base64,{}", mime, b64))
-                }
-                Err(_) => {
-                    let fallback = base64::engine::general_purpose::STANDARD.decode(&enc).ok();
-                    fallback.map(|bytes| {
-                        let mime = if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
-                            "image/png"
-                        } else if bytes.starts_with(&[0xff, 0xd8, 0xff]) {
-                            "image/jpeg"
-                    } else if bytes.starts_with(b"<svg") {
-                        "image/svg+xml"
-                        } else {
-                            "image/webp"
-                        };
-                        let b64 = base64::engine::general_purpose::STANDARD.encode(&bytes);
-                        format!("data:{};base64,{}", mime, b64)
-                    })
-                }
-            }
-        }
-        _ => None,
-    };
-
     Ok(Vault {
-        id: row.get(0)?,
+        image: image_id.as_deref().map(image_url),
+        thumbnail: image_id.as_deref().map(thumbnail_url),
+        id,
         user_id,
         name,
         color: row.get(3)?,
-        image: image_b64,
-        created_at: row.get(7)?,
-        position: row.get(8)?,
+        created_at: row.get(6)?,
+        updated_at: row.get(9)?,
+        last_used_at: row.get(10)?,
+        position: row.get(7)?,
+        full_text_search: row.get(8)?,
+        passphrase_protected: row.get(11)?,
     })
 }
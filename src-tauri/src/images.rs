@@ -0,0 +1,301 @@
+use std::io::Cursor;
+
+use base64::{Engine as _, engine::general_purpose::STANDARD};
+use generic_array::GenericArray;
+use image::codecs::jpeg::JpegEncoder;
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader, Limits};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::{Reader, Writer};
+use rusqlite::{types::Value, Connection};
+use typenum::U32;
+
+use crate::crypto::{decrypt_bytes_from_base64, decrypt_bytes_from_blob, encrypt_bytes_to_blob};
+use crate::db::image_blobs::{collect_image_garbage, store_sanitized_image};
+use crate::db::items::ItemKind;
+
+/// URI scheme the webview loads decrypted images from, see `commands::images`.
+pub const IMAGE_SCHEME: &str = "vaultimg";
+
+/// Largest image accepted from the frontend, before sanitizing.
+pub const MAX_IMAGE_SIZE: usize = 5 * 1024 * 1024;
+const MAX_IMAGE_DIMENSION: u32 = 4096;
+const MAX_DECODE_ALLOC: u64 = 256 * 1024 * 1024;
+const THUMBNAIL_SIZE: u32 = 128;
+const JPEG_QUALITY: u8 = 90;
+
+const UTF8_BOM: &[u8] = &[0xef, 0xbb, 0xbf];
+
+/// SVG elements kept by `sanitize_svg`. Anything else is dropped together with
+/// its children, which removes `script`, `foreignObject`, `style`, animations
+/// that can rewrite attributes and elements that load external content.
+const SVG_ELEMENTS: &[&str] = &[
+    "svg", "g", "defs", "symbol", "use", "title", "desc", "metadata",
+    "path", "rect", "circle", "ellipse", "line", "polyline", "polygon",
+    "text", "tspan", "textPath",
+    "linearGradient", "radialGradient", "stop", "pattern", "clipPath", "mask", "marker",
+    "filter", "feBlend", "feColorMatrix", "feComponentTransfer", "feComposite",
+    "feFlood", "feGaussianBlur", "feMerge", "feMergeNode", "feMorphology",
+    "feOffset", "feFuncR", "feFuncG", "feFuncB", "feFuncA",
+];
+
+/// An image re-encoded by `sanitize_image`, plus a thumbnail for list views.
+/// SVGs have no thumbnail, they are small and scale on their own.
+pub struct SanitizedImage {
+    pub data: Vec<u8>,
+    pub thumbnail: Option<Vec<u8>>,
+}
+
+/// How an update treats the image already stored for a row.
+pub enum ImageUpdate<'a> {
+    Keep,
+    Clear,
+    Replace(&'a [u8]),
+}
+
+impl<'a> ImageUpdate<'a> {
+    pub fn from_request(image: Option<&'a [u8]>, keep_image: bool) -> Self {
+        match image {
+            Some(bytes) => ImageUpdate::Replace(bytes),
+            None if keep_image => ImageUpdate::Keep,
+            None => ImageUpdate::Clear,
+        }
+    }
+}
+
+pub fn mime_type(bytes: &[u8]) -> &'static str {
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        "image/png"
+    } else if bytes.starts_with(&[0xff, 0xd8, 0xff]) {
+        "image/jpeg"
+    } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        "image/gif"
+    } else if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        "image/webp"
+    } else if is_svg(bytes) {
+        "image/svg+xml"
+    } else {
+        "application/octet-stream"
+    }
+}
+
+fn is_svg(bytes: &[u8]) -> bool {
+    let text = bytes.strip_prefix(UTF8_BOM).unwrap_or(bytes);
+    let start = text.iter().position(|b| !b.is_ascii_whitespace()).unwrap_or(text.len());
+    let text = &text[start..];
+    text.starts_with(b"<svg") || text.starts_with(b"<?xml")
+}
+
+/// Validates an uploaded image and rebuilds it from its decoded pixels, so
+/// EXIF/GPS and any other metadata never reach the database. SVGs are run
+/// through an element and attribute allowlist instead.
+pub fn sanitize_image(bytes: &[u8]) -> Result<SanitizedImage, String> {
+    if bytes.is_empty() {
+        return Err("Image is empty".to_string());
+    }
+    if bytes.len() > MAX_IMAGE_SIZE {
+        return Err(format!("Image is larger than {} MB", MAX_IMAGE_SIZE / (1024 * 1024)));
+    }
+
+    match mime_type(bytes) {
+        "image/png" => sanitize_raster(bytes, ImageFormat::Png),
+        "image/jpeg" => sanitize_raster(bytes, ImageFormat::Jpeg),
+        "image/gif" => sanitize_raster(bytes, ImageFormat::Gif),
+        "image/webp" => sanitize_raster(bytes, ImageFormat::WebP),
+        "image/svg+xml" => Ok(SanitizedImage { data: sanitize_svg(bytes)?, thumbnail: None }),
+        _ => Err("Unsupported image format, use PNG, JPEG, GIF, WebP or SVG".to_string()),
+    }
+}
+
+fn sanitize_raster(bytes: &[u8], format: ImageFormat) -> Result<SanitizedImage, String> {
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_IMAGE_DIMENSION);
+    limits.max_image_height = Some(MAX_IMAGE_DIMENSION);
+    limits.max_alloc = Some(MAX_DECODE_ALLOC);
+
+    let mut reader = ImageReader::with_format(Cursor::new(bytes), format);
+    reader.limits(limits);
+    let mut decoder = reader.into_decoder().map_err(|e| format!("Invalid image: {}", e))?;
+    // The orientation lives in the EXIF data we are about to drop.
+    let orientation = decoder.orientation().map_err(|e| format!("Invalid image: {}", e))?;
+    let mut image = DynamicImage::from_decoder(decoder).map_err(|e| format!("Invalid image: {}", e))?;
+    image.apply_orientation(orientation);
+
+    // Photos stay JPEG, everything else becomes a PNG (GIFs keep their first frame).
+    let output = if format == ImageFormat::Jpeg { ImageFormat::Jpeg } else { ImageFormat::Png };
+    let thumbnail = image.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE);
+    Ok(SanitizedImage {
+        data: encode_raster(&image, output)?,
+        thumbnail: Some(encode_raster(&thumbnail, output)?),
+    })
+}
+
+fn encode_raster(image: &DynamicImage, format: ImageFormat) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    let result = if format == ImageFormat::Jpeg {
+        image.to_rgb8().write_with_encoder(JpegEncoder::new_with_quality(&mut out, JPEG_QUALITY))
+    } else {
+        image.write_to(&mut Cursor::new(&mut out), format)
+    };
+    result.map_err(|e| format!("Failed to encode image: {}", e))?;
+    Ok(out)
+}
+
+/// Rewrites an SVG keeping only allowlisted elements, dropping event handler
+/// attributes and any reference that is not local to the document.
+fn sanitize_svg(bytes: &[u8]) -> Result<Vec<u8>, String> {
+    let text = std::str::from_utf8(bytes.strip_prefix(UTF8_BOM).unwrap_or(bytes))
+        .map_err(|_| "Invalid SVG: not UTF-8".to_string())?;
+    let mut reader = Reader::from_str(text);
+    let mut writer = Writer::new(Vec::with_capacity(bytes.len()));
+    let invalid = |e: quick_xml::Error| format!("Invalid SVG: {}", e);
+
+    let mut skip_depth = 0usize;
+    let mut has_root = false;
+    loop {
+        let event = reader.read_event().map_err(invalid)?;
+        match event {
+            Event::Start(ref e) | Event::Empty(ref e) => {
+                let is_start = matches!(event, Event::Start(_));
+                if skip_depth > 0 || !is_allowed_svg_element(e) || (!has_root && e.local_name().as_ref() != b"svg") {
+                    if is_start {
+                        skip_depth += 1;
+                    }
+                    continue;
+                }
+                has_root = true;
+                let element = sanitize_svg_element(e)?;
+                let element = if is_start { Event::Start(element) } else { Event::Empty(element) };
+                writer.write_event(element).map_err(|e| format!("Invalid SVG: {}", e))?;
+            }
+            Event::End(_) if skip_depth > 0 => skip_depth -= 1,
+            Event::End(e) => writer.write_event(Event::End(e)).map_err(|e| format!("Invalid SVG: {}", e))?,
+            Event::Text(_) | Event::CData(_) if skip_depth > 0 => {}
+            Event::Text(e) => writer.write_event(Event::Text(e)).map_err(|e| format!("Invalid SVG: {}", e))?,
+            Event::CData(e) => writer.write_event(Event::CData(e)).map_err(|e| format!("Invalid SVG: {}", e))?,
+            // DOCTYPEs can declare entities, processing instructions can pull in stylesheets.
+            Event::Decl(_) | Event::PI(_) | Event::DocType(_) | Event::Comment(_) => {}
+            Event::Eof => break,
+        }
+    }
+
+    if !has_root {
+        return Err("Invalid SVG: missing <svg> element".to_string());
+    }
+    Ok(writer.into_inner())
+}
+
+fn is_allowed_svg_element(element: &BytesStart) -> bool {
+    let name = element.local_name();
+    SVG_ELEMENTS.iter().any(|allowed| allowed.as_bytes() == name.as_ref())
+}
+
+fn sanitize_svg_element(element: &BytesStart) -> Result<BytesStart<'static>, String> {
+    let name = String::from_utf8_lossy(element.name().as_ref()).into_owned();
+    let mut clean = BytesStart::new(name);
+    for attr in element.attributes() {
+        let attr = attr.map_err(|e| format!("Invalid SVG: {}", e))?;
+        let key = attr.key.local_name();
+        let key = String::from_utf8_lossy(key.as_ref()).to_ascii_lowercase();
+        let value = attr.unescape_value().map_err(|e| format!("Invalid SVG: {}", e))?;
+        if !is_safe_svg_attribute(&key, &value) {
+            continue;
+        }
+        clean.push_attribute(attr);
+    }
+    Ok(clean)
+}
+
+/// Event handlers are dropped, `href`s may only point inside the document and
+/// `url(...)` values (in `style`, `fill`, `filter`, ...) may only reference ids.
+fn is_safe_svg_attribute(key: &str, value: &str) -> bool {
+    if key.starts_with("on") {
+        return false;
+    }
+    let value: String = value.chars().filter(|c| !c.is_whitespace()).collect::<String>().to_ascii_lowercase();
+    match key {
+        "href" | "src" => value.starts_with('#'),
+        _ => {
+            !value.contains("javascript:")
+                && value.split("url(").skip(1).all(|rest| rest.trim_start_matches(['\'', '"']).starts_with('#'))
+        }
+    }
+}
+
+pub fn encrypt_image(bytes: &[u8], key: &GenericArray<u8, U32>) -> Result<Vec<u8>, String> {
+    Ok(encrypt_bytes_to_blob(bytes, key)?)
+}
+
+pub fn decrypt_image(blob: &[u8], key: &GenericArray<u8, U32>) -> Result<Vec<u8>, String> {
+    Ok(decrypt_bytes_from_blob(blob, key)?)
+}
+
+/// URL the webview can load an image blob from. Blob ids name their content,
+/// so the URL changes whenever the image does.
+pub fn image_url(blob_id: &str) -> String {
+    format!("{}/{}", scheme_origin(), blob_id)
+}
+
+/// URL of the thumbnail of an image blob, for list views.
+pub fn thumbnail_url(blob_id: &str) -> String {
+    format!("{}/{}/thumbnail", scheme_origin(), blob_id)
+}
+
+fn scheme_origin() -> String {
+    if cfg!(any(windows, target_os = "android")) {
+        format!("http://{}.localhost", IMAGE_SCHEME)
+    } else {
+        format!("{}://localhost", IMAGE_SCHEME)
+    }
+}
+
+/// Moves images stored inline in a row (base64 text with a separate nonce
+/// column, or `nonce || ciphertext` BLOBs) into the image blob store. Runs when
+/// a session is opened since the old rows can only be read with the user's key.
+pub fn migrate_inline_images(conn: &mut Connection, user_id: i32, key: &GenericArray<u8, U32>) -> Result<(), String> {
+    let mut targets = vec![
+        (
+            "SELECT id, avatar, avatar_nonce FROM users WHERE id = ? AND avatar IS NOT NULL".to_string(),
+            "UPDATE users SET avatar_id = ?, avatar = NULL, avatar_nonce = NULL WHERE id = ?".to_string(),
+        ),
+        (
+            "SELECT id, image, image_nonce FROM vaults WHERE user_id = ? AND image IS NOT NULL".to_string(),
+            "UPDATE vaults SET image_id = ?, image = NULL, image_nonce = NULL WHERE id = ?".to_string(),
+        ),
+    ];
+    for kind in ItemKind::ALL {
+        targets.push((
+            format!("SELECT i.id, i.image, i.image_nonce FROM {} i JOIN vaults v ON v.id = i.vault_id WHERE v.user_id = ? AND i.image IS NOT NULL", kind.table()),
+            format!("UPDATE {} SET image_id = ?, image = NULL, image_nonce = NULL WHERE id = ?", kind.table()),
+        ));
+    }
+
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    for (select, update) in targets {
+        let rows: Vec<(Value, Value, Option<String>)> = {
+            let mut stmt = tx.prepare(&select).map_err(|e| e.to_string())?;
+            let rows = stmt.query_map([user_id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+                .map_err(|e| e.to_string())?;
+            rows.collect::<Result<_, _>>().map_err(|e| e.to_string())?
+        };
+
+        for (id, image, nonce_b64) in rows {
+            let plain = match image {
+                Value::Blob(blob) => decrypt_image(&blob, key).ok(),
+                Value::Text(encrypted_b64) => nonce_b64
+                    .and_then(|nonce| decrypt_bytes_from_base64(&encrypted_b64, &nonce, key).ok())
+                    .or_else(|| STANDARD.decode(&encrypted_b64).ok()),
+                _ => None,
+            };
+            // Images that predate sanitizing and do not pass it are dropped,
+            // as are those that no longer decrypt.
+            let blob_id = match plain.and_then(|bytes| sanitize_image(&bytes).ok()) {
+                Some(image) => Some(store_sanitized_image(&tx, user_id, &image, key)?),
+                None => None,
+            };
+            tx.execute(&update, rusqlite::params![blob_id, id]).map_err(|e| e.to_string())?;
+        }
+    }
+    collect_image_garbage(&tx, user_id)?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(())
+}
@@ -2,7 +2,12 @@ mod auth;
 mod commands;
 mod crypto;
 mod db;
+mod images;
 mod models;
+mod search;
+mod shamir;
+mod totp;
+mod words;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
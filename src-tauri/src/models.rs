@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use crate::db::items::{Item, ItemFields, ItemKind};
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct User {
     pub id: i32,
@@ -9,6 +11,8 @@ pub struct User {
     pub password_hash: String,
     pub master_key_hash: String,
     pub avatar: Option<String>,
+    pub two_factor_enabled: bool,
+    pub secret_key_enabled: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -16,6 +20,12 @@ pub struct UserResponse {
     pub id: i32,
     pub username: String,
     pub avatar: Option<String>,
+    pub two_factor_enabled: bool,
+    pub secret_key_enabled: bool,
+    /// Only set by `register` when a recovery key was asked for; it is not
+    /// stored and cannot be shown again.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recovery_key: Option<String>,
 }
 
 impl From<User> for UserResponse {
@@ -24,6 +34,9 @@ impl From<User> for UserResponse {
             id: user.id,
             username: user.username,
             avatar: user.avatar,
+            two_factor_enabled: user.two_factor_enabled,
+            secret_key_enabled: user.secret_key_enabled,
+            recovery_key: None,
         }
     }
 }
@@ -36,6 +49,10 @@ pub struct Collection {
     pub vault_ids: Vec<String>,
     pub position: i32,
     pub created_at: i64,
+    #[serde(default)]
+    pub updated_at: i64,
+    #[serde(default)]
+    pub last_used_at: Option<i64>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -45,8 +62,17 @@ pub struct Vault {
     pub name: String,
     pub color: String,
     pub image: Option<String>,
+    pub thumbnail: Option<String>,
     pub created_at: i64,
+    #[serde(default)]
+    pub updated_at: i64,
+    #[serde(default)]
+    pub last_used_at: Option<i64>,
     pub position: i32,
+    #[serde(default)]
+    pub full_text_search: bool,
+    #[serde(default)]
+    pub passphrase_protected: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -57,10 +83,20 @@ pub struct IdCard {
     pub id_type: String,
     pub full_name: String,
     pub id_number: String,
+    pub expiry_date: Option<String>,
     pub color: String,
     pub image: Option<String>,
+    pub thumbnail: Option<String>,
     pub created_at: i64,
+    pub updated_at: i64,
+    pub last_used_at: Option<i64>,
     pub position: i32,
+    #[serde(default)]
+    pub favorite: bool,
+    #[serde(default)]
+    pub custom_fields: Vec<CustomField>,
+    #[serde(default)]
+    pub tags: Vec<Tag>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -74,9 +110,17 @@ pub struct CreditCard {
     pub cvv: String,
     pub color: String,
     pub image: Option<String>,
+    pub thumbnail: Option<String>,
     pub created_at: i64,
     pub updated_at: i64,
+    pub last_used_at: Option<i64>,
     pub position: i32,
+    #[serde(default)]
+    pub favorite: bool,
+    #[serde(default)]
+    pub custom_fields: Vec<CustomField>,
+    #[serde(default)]
+    pub tags: Vec<Tag>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -88,11 +132,21 @@ pub struct LoginKey {
     pub username: String,
     pub password: String,
     pub details: Option<String>,
+    pub rotation_due: Option<String>,
+    pub totp: Option<String>,
     pub color: String,
     pub image: Option<String>,
+    pub thumbnail: Option<String>,
     pub created_at: i64,
     pub updated_at: i64,
+    pub last_used_at: Option<i64>,
     pub position: i32,
+    #[serde(default)]
+    pub favorite: bool,
+    #[serde(default)]
+    pub custom_fields: Vec<CustomField>,
+    #[serde(default)]
+    pub tags: Vec<Tag>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -103,6 +157,210 @@ pub struct Note {
     pub content: String,
     pub color: String,
     pub image: Option<String>,
+    pub thumbnail: Option<String>,
     pub created_at: i64,
+    pub updated_at: i64,
+    pub last_used_at: Option<i64>,
+    pub position: i32,
+    #[serde(default)]
+    pub favorite: bool,
+    #[serde(default)]
+    pub custom_fields: Vec<CustomField>,
+    #[serde(default)]
+    pub tags: Vec<Tag>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CustomFieldType {
+    Text,
+    Concealed,
+    Url,
+    Email,
+    Date,
+    Totp,
+}
+
+impl CustomFieldType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CustomFieldType::Text => "text",
+            CustomFieldType::Concealed => "concealed",
+            CustomFieldType::Url => "url",
+            CustomFieldType::Email => "email",
+            CustomFieldType::Date => "date",
+            CustomFieldType::Totp => "totp",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "text" => Some(CustomFieldType::Text),
+            "concealed" => Some(CustomFieldType::Concealed),
+            "url" => Some(CustomFieldType::Url),
+            "email" => Some(CustomFieldType::Email),
+            "date" => Some(CustomFieldType::Date),
+            "totp" => Some(CustomFieldType::Totp),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CustomField {
+    pub id: String,
+    pub label: String,
+    pub value: String,
+    pub field_type: CustomFieldType,
     pub position: i32,
-}
\ No newline at end of file
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NewCustomField {
+    pub label: String,
+    pub value: String,
+    pub field_type: CustomFieldType,
+}
+
+/// The fields of a login key, as sent to create or update one.
+#[derive(Deserialize, Debug, Clone)]
+pub struct LoginKeyInput {
+    pub site_name: String,
+    pub url: Option<String>,
+    pub username: String,
+    pub password: String,
+    pub details: Option<String>,
+    pub rotation_due: Option<String>,
+    pub totp: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct CreditCardInput {
+    pub card_name: String,
+    pub holder_name: String,
+    pub card_number: String,
+    pub expiry: String,
+    pub cvv: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct IdCardInput {
+    pub id_name: String,
+    pub id_type: String,
+    pub full_name: String,
+    pub id_number: String,
+    pub expiry_date: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct NoteInput {
+    pub note_name: String,
+    pub content: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Attachment {
+    pub id: String,
+    pub item_id: String,
+    pub file_name: String,
+    pub size: i64,
+    pub created_at: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Tag {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TagCount {
+    #[serde(flatten)]
+    pub tag: Tag,
+    pub item_count: i64,
+}
+
+/// Non-secret view of an item from any vault, for lists spanning vaults.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ItemSummary {
+    pub id: String,
+    pub kind: ItemKind,
+    pub vault_id: String,
+    pub vault_name: String,
+    pub title: String,
+    pub subtitle: Option<String>,
+    pub color: String,
+    pub image: Option<String>,
+    pub thumbnail: Option<String>,
+    pub favorite_position: Option<i32>,
+    pub created_at: i64,
+    pub updated_at: i64,
+    pub last_used_at: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SearchHit {
+    #[serde(flatten)]
+    pub item: ItemSummary,
+    pub score: u32,
+}
+
+/// An item of any type, as passed to commands acting on several items.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ItemRef {
+    pub kind: ItemKind,
+    pub id: String,
+}
+
+/// Outcome of a bulk operation for one item. `item` is only set by exports.
+#[derive(Serialize, Debug, Clone)]
+pub struct ItemResult {
+    pub kind: ItemKind,
+    pub id: String,
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub item: Option<Item>,
+}
+
+/// Bulk operations run in one transaction: `applied` is false, and nothing
+/// changed, when any item has an error.
+#[derive(Serialize, Debug, Clone)]
+pub struct BulkResult {
+    pub applied: bool,
+    pub items: Vec<ItemResult>,
+}
+
+/// Values pre-filled into new items of one kind; any field may be left out.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ItemTemplate {
+    pub id: String,
+    pub kind: ItemKind,
+    pub name: String,
+    pub fields: ItemFields,
+    pub color: String,
+    pub created_at: i64,
+}
+
+/// An item whose expiry date, or password rotation date for login keys,
+/// falls within the window asked for or has already passed, or whose stored
+/// date does not parse.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Expiration {
+    #[serde(flatten)]
+    pub item: ItemSummary,
+    /// YYYY-MM-DD; for cards the last day of their expiry month. `None` when
+    /// the stored date does not parse.
+    pub expires_on: Option<String>,
+    /// Negative once expired.
+    pub days_left: Option<i64>,
+    /// Why the stored date does not parse, for dates saved before they were
+    /// validated.
+    pub invalid_date: Option<String>,
+}
+
+/// A secret to add to an authenticator app, typed in or scanned from `uri`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TwoFactorEnrollment {
+    pub secret: String,
+    pub uri: String,
+}
@@ -0,0 +1,282 @@
+use std::collections::HashMap;
+
+use generic_array::GenericArray;
+use rusqlite::{params_from_iter, types::Value, Connection};
+use typenum::U32;
+use zeroize::Zeroize;
+
+use crate::auth::Database;
+use crate::crypto::decrypt_from_base64;
+use crate::db::items::{query_summaries, with_item_type, ItemKind, VaultItem};
+use crate::db::tags::load_item_tags;
+use crate::models::{ItemSummary, SearchHit};
+
+pub const DEFAULT_SEARCH_LIMIT: usize = 50;
+
+const TITLE_WEIGHT: u32 = 4;
+const TAG_WEIGHT: u32 = 3;
+const FIELD_WEIGHT: u32 = 2;
+const VAULT_WEIGHT: u32 = 1;
+const FAVORITE_BONUS: u32 = 5;
+
+/// Decrypted non-secret fields of every item of one user, kept only while
+/// their session is open. Secrets (passwords, card numbers, note bodies...)
+/// are never decrypted into it.
+#[derive(Default)]
+pub struct SearchIndex {
+    entries: HashMap<String, SearchEntry>,
+}
+
+struct SearchEntry {
+    summary: ItemSummary,
+    fields: Vec<IndexedField>,
+}
+
+struct IndexedField {
+    weight: u32,
+    text: String,
+    words: Vec<String>,
+}
+
+impl IndexedField {
+    fn new(weight: u32, text: &str) -> Self {
+        let text = text.to_lowercase();
+        let words = text.split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .map(str::to_string)
+            .collect();
+        IndexedField { weight, text, words }
+    }
+
+    fn score(&self, token: &str) -> u32 {
+        let score = if self.text == token {
+            100
+        } else if self.words.iter().any(|w| w == token) {
+            90
+        } else if self.words.iter().any(|w| w.starts_with(token)) {
+            70
+        } else if self.text.contains(token) {
+            40
+        } else {
+            fuzzy_score(token, &self.words)
+        };
+        score * self.weight
+    }
+}
+
+impl Drop for SearchEntry {
+    fn drop(&mut self) {
+        self.summary.title.zeroize();
+        self.summary.subtitle.zeroize();
+        self.summary.vault_name.zeroize();
+        for field in &mut self.fields {
+            field.text.zeroize();
+            field.words.zeroize();
+        }
+    }
+}
+
+impl SearchIndex {
+    fn search(&self, query: &str, limit: usize) -> Vec<SearchHit> {
+        let query = query.to_lowercase();
+        let tokens: Vec<&str> = query.split_whitespace().collect();
+        if tokens.is_empty() {
+            return Vec::new();
+        }
+
+        let mut hits: Vec<SearchHit> = self.entries.values()
+            .filter_map(|entry| {
+                // Every token has to match some field; each counts its best match.
+                let mut total = 0;
+                for token in &tokens {
+                    let best = entry.fields.iter().map(|f| f.score(token)).max().unwrap_or(0);
+                    if best == 0 {
+                        return None;
+                    }
+                    total += best;
+                }
+                if entry.summary.favorite_position.is_some() {
+                    total += FAVORITE_BONUS;
+                }
+                Some(SearchHit { item: entry.summary.clone(), score: total })
+            })
+            .collect();
+
+        hits.sort_by(|a, b| {
+            b.score.cmp(&a.score)
+                .then_with(|| a.item.title.to_lowercase().cmp(&b.item.title.to_lowercase()))
+        });
+        hits.truncate(limit);
+        hits
+    }
+}
+
+/// Typo tolerance grows with the token, short tokens have to match exactly.
+fn fuzzy_score(token: &str, words: &[String]) -> u32 {
+    let token_len = token.chars().count();
+    let allowed = match token_len {
+        0..=3 => return 0,
+        4..=7 => 1,
+        _ => 2,
+    };
+
+    let best = words.iter()
+        .map(|word| {
+            // Also compare against the start of longer words, for half typed queries.
+            let prefix: String = word.chars().take(token_len).collect();
+            edit_distance(token, word).min(edit_distance(token, &prefix))
+        })
+        .min();
+
+    match best {
+        Some(distance) if distance <= allowed => 30 - 10 * distance as u32,
+        _ => 0,
+    }
+}
+
+/// Edit distance where swapping two neighbouring characters counts as one edit.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut d = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1).min(d[i][j - 1] + 1).min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+/// Loads index entries for the items of type `T` matching `clause`, see
+/// `query_summaries`.
+fn load_entries<T: VaultItem>(conn: &Connection, clause: &str, params: &[Value], key: &GenericArray<u8, U32>) -> Result<Vec<SearchEntry>, String> {
+    let summaries = query_summaries::<T>(conn, clause, params, key)?;
+    let ids: Vec<String> = summaries.iter().map(|s| s.id.clone()).collect();
+    let mut tags = load_item_tags(conn, &ids, key)?;
+    let mut extra = load_search_fields::<T>(conn, clause, params, key)?;
+
+    let mut entries = Vec::with_capacity(summaries.len());
+    for summary in summaries {
+        let mut fields = vec![
+            IndexedField::new(TITLE_WEIGHT, &summary.title),
+            IndexedField::new(VAULT_WEIGHT, &summary.vault_name),
+        ];
+        if let Some(subtitle) = &summary.subtitle {
+            fields.push(IndexedField::new(FIELD_WEIGHT, subtitle));
+        }
+        for value in extra.remove(&summary.id).unwrap_or_default() {
+            fields.push(IndexedField::new(FIELD_WEIGHT, &value));
+        }
+        for tag in tags.remove(&summary.id).unwrap_or_default() {
+            fields.push(IndexedField::new(TAG_WEIGHT, &tag.name));
+        }
+        entries.push(SearchEntry { summary, fields });
+    }
+    Ok(entries)
+}
+
+/// Decrypts `T::SEARCH_FIELDS`, the searchable fields beyond title and subtitle.
+fn load_search_fields<T: VaultItem>(conn: &Connection, clause: &str, params: &[Value], key: &GenericArray<u8, U32>) -> Result<HashMap<String, Vec<String>>, String> {
+    let mut result: HashMap<String, Vec<String>> = HashMap::new();
+    if T::SEARCH_FIELDS.is_empty() {
+        return Ok(result);
+    }
+
+    let columns: Vec<String> = T::SEARCH_FIELDS.iter()
+        .map(|field| format!("i.{0}_encrypted, i.{0}_nonce", field))
+        .collect();
+    let mut stmt = conn.prepare(&format!(
+        "SELECT i.id, {} FROM {} i JOIN vaults v ON v.id = i.vault_id AND v.vault_key_encrypted IS NULL {}",
+        columns.join(", "), T::TABLE, clause
+    )).map_err(|e| e.to_string())?;
+
+    let mut rows = stmt.query(params_from_iter(params)).map_err(|e| e.to_string())?;
+    while let Some(row) = rows.next().map_err(|e| e.to_string())? {
+        let id: String = row.get(0).map_err(|e| e.to_string())?;
+        let mut values = Vec::new();
+        for i in 0..T::SEARCH_FIELDS.len() {
+            let encrypted: Option<String> = row.get(1 + i * 2).map_err(|e| e.to_string())?;
+            let nonce: Option<String> = row.get(2 + i * 2).map_err(|e| e.to_string())?;
+            if let Some((encrypted, nonce)) = encrypted.zip(nonce) {
+                values.push(decrypt_from_base64(&encrypted, &nonce, key)?);
+            }
+        }
+        result.insert(id, values);
+    }
+    Ok(result)
+}
+
+impl Database {
+    /// Builds the search index of a user from scratch. Called when the session
+    /// is opened and after changes touching many items (vault or tag renames).
+    pub(crate) fn build_search_index(&self, conn: &Connection, user_id: i32, key: &GenericArray<u8, U32>) -> Result<(), String> {
+        let params = [Value::from(user_id)];
+        let mut index = SearchIndex::default();
+        for kind in ItemKind::ALL {
+            with_item_type!(kind, |T| {
+                for entry in load_entries::<T>(conn, "WHERE v.user_id = ?", &params, key)? {
+                    index.entries.insert(entry.summary.id.clone(), entry);
+                }
+            });
+        }
+        self.search_indexes.lock().unwrap().insert(user_id, index);
+        Ok(())
+    }
+
+    /// Rebuilds the index of a user if their session has one.
+    pub(crate) fn rebuild_search_index(&self, conn: &Connection, user_id: i32) -> Result<(), String> {
+        let key = match self.get_encryption_key(user_id) {
+            Ok(key) => key,
+            Err(_) => return Ok(()),
+        };
+        if !self.search_indexes.lock().unwrap().contains_key(&user_id) {
+            return Ok(());
+        }
+        self.build_search_index(conn, user_id, &key)
+    }
+
+    /// Refreshes the entry of one item after it was created or changed.
+    pub(crate) fn reindex_item<T: VaultItem>(&self, conn: &Connection, item_id: &str, user_id: i32) -> Result<(), String> {
+        let key = match self.get_encryption_key(user_id) {
+            Ok(key) => key,
+            Err(_) => return Ok(()),
+        };
+        let entry = load_entries::<T>(conn, "WHERE i.id = ? AND v.user_id = ?", &[Value::from(item_id.to_string()), Value::from(user_id)], &key)?
+            .pop();
+
+        let mut indexes = self.search_indexes.lock().unwrap();
+        if let Some(index) = indexes.get_mut(&user_id) {
+            match entry {
+                Some(entry) => index.entries.insert(item_id.to_string(), entry),
+                None => index.entries.remove(item_id),
+            };
+        }
+        Ok(())
+    }
+
+    pub(crate) fn unindex_item(&self, item_id: &str) {
+        let mut indexes = self.search_indexes.lock().unwrap();
+        for index in indexes.values_mut() {
+            index.entries.remove(item_id);
+        }
+    }
+
+    /// Searches titles, usernames, URLs, card and ID names, tags and vault
+    /// names of every item of the user, best matches first.
+    pub fn search(&self, query: &str, limit: usize, user_id: i32) -> Result<Vec<SearchHit>, String> {
+        self.get_encryption_key(user_id)?;
+        let indexes = self.search_indexes.lock().unwrap();
+        let index = indexes.get(&user_id).ok_or("Search index not ready".to_string())?;
+        Ok(index.search(query, limit))
+    }
+}
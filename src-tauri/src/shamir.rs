@@ -0,0 +1,147 @@
+use rand::RngCore;
+
+/// One share of a split secret: the point `x` and the polynomials' values
+/// there, one per byte of the secret.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Share {
+    pub x: u8,
+    pub y: Vec<u8>,
+}
+
+/// Splits `secret` into `count` shares, any `threshold` of which give it back
+/// while fewer tell nothing about it. Each byte is the constant term of its
+/// own random polynomial of degree `threshold - 1` over GF(256).
+pub fn split(secret: &[u8], threshold: u8, count: u8) -> Result<Vec<Share>, String> {
+    check_parameters(threshold, count)?;
+
+    let mut rng = rand::thread_rng();
+    let mut coefficients = vec![0u8; threshold as usize - 1];
+    let mut shares: Vec<Share> = (1..=count).map(|x| Share { x, y: Vec::with_capacity(secret.len()) }).collect();
+    for &byte in secret {
+        rng.fill_bytes(&mut coefficients);
+        for share in &mut shares {
+            // Horner's rule, highest coefficient first.
+            let value = coefficients.iter().rev().fold(0, |acc, &c| mul(acc, share.x) ^ c);
+            share.y.push(mul(value, share.x) ^ byte);
+        }
+    }
+    Ok(shares)
+}
+
+pub fn check_parameters(threshold: u8, count: u8) -> Result<(), String> {
+    if threshold < 2 {
+        return Err("The threshold must be at least 2".to_string());
+    }
+    if count < threshold {
+        return Err("There cannot be fewer shares than the threshold".to_string());
+    }
+    Ok(())
+}
+
+/// The secret the shares were split from, by Lagrange interpolation at zero.
+/// Needs at least the threshold of shares; with fewer the result is garbage,
+/// which callers notice when the secret does not unwrap anything.
+pub fn combine(shares: &[Share]) -> Result<Vec<u8>, String> {
+    let first = shares.first().ok_or("No shares given")?;
+    if shares.iter().any(|s| s.y.len() != first.y.len()) {
+        return Err("The shares are not from the same secret".to_string());
+    }
+    for (i, share) in shares.iter().enumerate() {
+        if share.x == 0 || shares[..i].iter().any(|s| s.x == share.x) {
+            return Err("The same share was given twice".to_string());
+        }
+    }
+
+    let weights: Vec<u8> = shares.iter()
+        .map(|share| {
+            shares.iter()
+                .filter(|other| other.x != share.x)
+                .fold(1, |acc, other| mul(acc, div(other.x, other.x ^ share.x)))
+        })
+        .collect();
+    Ok((0..first.y.len())
+        .map(|i| shares.iter().zip(&weights).fold(0, |acc, (share, &w)| acc ^ mul(share.y[i], w)))
+        .collect())
+}
+
+/// Multiplication in GF(256) with the AES polynomial.
+fn mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+    while b != 0 {
+        if b & 1 != 0 {
+            product ^= a;
+        }
+        let carry = a & 0x80 != 0;
+        a <<= 1;
+        if carry {
+            a ^= 0x1b;
+        }
+        b >>= 1;
+    }
+    product
+}
+
+/// `a / b` for non-zero `b`, as `a * b^254` since `b^255 = 1`.
+fn div(a: u8, b: u8) -> u8 {
+    let mut inverse = 1;
+    for _ in 0..254 {
+        inverse = mul(inverse, b);
+    }
+    mul(a, inverse)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &[u8] = b"sixteen byte key";
+
+    #[test]
+    fn any_threshold_of_shares_gives_the_secret_back() {
+        let shares = split(SECRET, 3, 5).unwrap();
+        assert_eq!(shares.len(), 5);
+        for i in 0..5 {
+            for j in i + 1..5 {
+                for k in j + 1..5 {
+                    let subset = [shares[i].clone(), shares[j].clone(), shares[k].clone()];
+                    assert_eq!(combine(&subset).unwrap(), SECRET);
+                }
+            }
+        }
+        assert_eq!(combine(&shares).unwrap(), SECRET);
+    }
+
+    #[test]
+    fn fewer_shares_than_the_threshold_do_not() {
+        let shares = split(SECRET, 3, 5).unwrap();
+        assert_ne!(combine(&shares[..2]).unwrap(), SECRET);
+    }
+
+    #[test]
+    fn threshold_may_equal_count() {
+        let shares = split(SECRET, 4, 4).unwrap();
+        assert_eq!(combine(&shares).unwrap(), SECRET);
+        assert_ne!(combine(&shares[1..]).unwrap(), SECRET);
+    }
+
+    #[test]
+    fn rejects_bad_parameters_and_shares() {
+        assert!(split(SECRET, 1, 3).is_err());
+        assert!(split(SECRET, 4, 3).is_err());
+
+        let shares = split(SECRET, 2, 3).unwrap();
+        assert!(combine(&[]).is_err());
+        assert!(combine(&[shares[0].clone(), shares[0].clone()]).is_err());
+        let short = Share { x: shares[1].x, y: shares[1].y[1..].to_vec() };
+        assert!(combine(&[shares[0].clone(), short]).is_err());
+    }
+
+    #[test]
+    fn division_inverts_multiplication() {
+        for a in 0..=255u8 {
+            for b in 1..=255u8 {
+                assert_eq!(div(mul(a, b), b), a);
+            }
+        }
+    }
+}
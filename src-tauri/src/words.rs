@@ -0,0 +1,136 @@
+use sha2::{Digest, Sha256};
+
+/// One word per byte. Every word has a distinct first four letters, so those
+/// are enough when typing them back in.
+const WORDS: [&str; 256] = [
+    "acid", "acorn", "actor", "adapt", "agent", "alarm", "album", "alert", "alley", "amber",
+    "anchor", "angle", "ankle", "apple", "april", "arch", "arena", "argue", "armor", "arrow",
+    "aspen", "atlas", "attic", "audio", "autumn", "avoid", "award", "bacon", "badge", "bagel",
+    "baker", "bamboo", "banjo", "barn", "basil", "beach", "beard", "beaver", "bench", "berry",
+    "bicycle", "birch", "bishop", "blade", "blanket", "blossom", "board", "bonus", "border",
+    "bottle", "bounce", "brain", "brave", "bread", "brick", "bridge", "broom", "bubble", "bucket",
+    "buffalo", "bundle", "burst", "butter", "cabin", "cactus", "camel", "candle", "canoe", "canvas",
+    "carbon", "cargo", "carpet", "castle", "cedar", "cello", "chalk", "cherry", "chess", "chimney",
+    "cider", "circle", "clay", "cliff", "clock", "cloud", "clover", "coast", "cobra", "coffee",
+    "comet", "copper", "coral", "cotton", "cousin", "crane", "crater", "cricket", "crystal",
+    "cupboard", "curtain", "dagger", "daisy", "dance", "delta", "denim", "desert", "diamond",
+    "dinner", "dolphin", "donkey", "dragon", "drift", "drum", "eagle", "earth", "echo", "eclipse",
+    "elbow", "elder", "ember", "empire", "engine", "equal", "fabric", "falcon", "feather", "fence",
+    "ferry", "fiber", "field", "finger", "flame", "flute", "forest", "fossil", "fox", "frost",
+    "galaxy", "garden", "garlic", "gentle", "ginger", "glacier", "globe", "goat", "gold", "gorilla",
+    "grape", "gravel", "guitar", "habit", "hammer", "harbor", "harvest", "hazel", "helmet",
+    "hermit", "honey", "horizon", "hotel", "husky", "igloo", "index", "indigo", "island", "ivory",
+    "jacket", "jaguar", "jasmine", "jelly", "jewel", "jungle", "kayak", "kettle", "kidney",
+    "kingdom", "kitten", "koala", "ladder", "lagoon", "lantern", "laptop", "lava", "lemon",
+    "leopard", "letter", "lilac", "lizard", "lobster", "locket", "lotus", "lunar", "magnet",
+    "mango", "maple", "marble", "meadow", "melon", "mercy", "meteor", "mirror", "mosaic", "muffin",
+    "museum", "napkin", "nectar", "needle", "noble", "novel", "nutmeg", "oasis", "ocean", "olive",
+    "onion", "opera", "orbit", "orchid", "otter", "oyster", "paddle", "palace", "panda", "parrot",
+    "pasta", "peach", "pebble", "pepper", "piano", "pigeon", "pilot", "planet", "plum", "pocket",
+    "polar", "pony", "prism", "pumpkin", "puzzle", "quartz", "quiet", "rabbit", "radar", "raven",
+    "ribbon", "river", "rocket", "saddle", "salmon", "sandal", "scarf", "shadow", "shell", "silver",
+    "spider", "tiger", "walnut",
+];
+
+/// Writes `bytes` as words, followed by a checksum word so a mistyped or
+/// missing word is caught when reading them back.
+pub fn encode_words(bytes: &[u8]) -> String {
+    bytes.iter()
+        .chain(std::iter::once(&checksum(bytes)))
+        .map(|&b| WORDS[b as usize])
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Reads back what `encode_words` wrote. Case, extra whitespace and dashes
+/// between words do not matter.
+pub fn decode_words(text: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = text
+        .split(|c: char| c.is_whitespace() || c == '-')
+        .filter(|w| !w.is_empty())
+        .map(|word| decode_word(&word.to_lowercase()))
+        .collect::<Result<Vec<u8>, String>>()?;
+
+    let expected = bytes.pop().ok_or("No words given")?;
+    if checksum(&bytes) != expected {
+        return Err("The words do not check out, one may be mistyped or missing".to_string());
+    }
+    Ok(bytes)
+}
+
+/// A word is taken whole or by its first four letters, which tell every word
+/// apart; fewer letters could stand for more than one.
+fn decode_word(word: &str) -> Result<u8, String> {
+    if let Some(i) = WORDS.iter().position(|w| *w == word) {
+        return Ok(i as u8);
+    }
+    let letters = word.chars().count();
+    let matches: Vec<usize> = WORDS.iter()
+        .enumerate()
+        .filter(|(_, w)| w.starts_with(word))
+        .map(|(i, _)| i)
+        .collect();
+    match matches[..] {
+        [i] if letters == 4 => Ok(i as u8),
+        [_, ..] if letters < 4 => Err(format!("'{}' is too short to tell which word it is, type at least 4 letters", word)),
+        _ => Err(format!("Unknown word '{}'", word)),
+    }
+}
+
+fn checksum(bytes: &[u8]) -> u8 {
+    Sha256::digest(bytes)[0]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn words_round_trip() {
+        let bytes: Vec<u8> = (0..=255).collect();
+        assert_eq!(decode_words(&encode_words(&bytes)).unwrap(), bytes);
+    }
+
+    #[test]
+    fn words_are_distinct_in_their_first_four_letters() {
+        let prefixes: std::collections::HashSet<String> = WORDS.iter().map(|w| w.chars().take(4).collect()).collect();
+        assert_eq!(prefixes.len(), WORDS.len());
+    }
+
+    #[test]
+    fn case_dashes_and_prefixes_are_accepted() {
+        let bytes = [0u8, 42, 255, 7];
+        let encoded = encode_words(&bytes);
+        let typed: Vec<String> = encoded.split(' ')
+            .map(|w| w.chars().take(4).collect::<String>().to_uppercase())
+            .collect();
+        assert_eq!(decode_words(&format!("  {}  ", typed.join(" - "))).unwrap(), bytes);
+    }
+
+    #[test]
+    fn short_prefixes_are_refused() {
+        let word = WORDS[1];
+        let short: String = word.chars().take(2).collect();
+        let err = decode_words(&format!("{} {}", short, WORDS[0])).unwrap_err();
+        assert!(err.contains(&format!("'{}'", short)) && err.contains("at least 4 letters"), "{}", err);
+        // A word shorter than four letters is still taken whole.
+        for (i, w) in WORDS.iter().enumerate().filter(|(_, w)| w.len() < 4) {
+            assert_eq!(decode_word(w).unwrap(), i as u8);
+        }
+    }
+
+    #[test]
+    fn mistakes_are_caught() {
+        let encoded = encode_words(&[1, 2, 3, 4]);
+        let mut words: Vec<&str> = encoded.split(' ').collect();
+
+        let missing = words[1..].join(" ");
+        assert!(decode_words(&missing).is_err());
+
+        words.swap(0, 1);
+        assert!(decode_words(&words.join(" ")).is_err());
+        assert!(decode_words("acid notaword").is_err());
+        assert!(decode_words("acid acorns").is_err());
+        assert!(decode_words("").is_err());
+    }
+}
@@ -140,7 +140,7 @@ export function CollectionAccordion({
                       title={vault.name}
                       color={vault.color}
                       colorPalette={VAULT_COLORS_HEX}
-                      avatarSrc={vault.image}
+                      avatarSrc={vault.thumbnail ?? vault.image}
                       avatarFallback={vault.name.charAt(0).toUpperCase()}
                       isSelected={activeVault === vault.id}
                       onClick={() => onVaultClick(vault.id)}
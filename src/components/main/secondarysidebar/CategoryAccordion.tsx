@@ -163,7 +163,7 @@ export function CategoryAccordion({
                   title={idCard.id_name}
                   color={idCard.color}
                   colorPalette={IDCARD_COLORS_HEX}
-                  avatarSrc={idCard.image}
+                  avatarSrc={idCard.thumbnail ?? idCard.image}
                   avatarFallback={idCard.id_name.charAt(0).toUpperCase()}
                   isSelected={selectedItemId === idCard.id}
                   onClick={() => onSelectItem?.(idCard.id)}
@@ -184,7 +184,7 @@ export function CategoryAccordion({
                   title={creditCard.card_name}
                   color={creditCard.color}
                   colorPalette={CREDITCARD_COLORS_HEX}
-                  avatarSrc={creditCard.image}
+                  avatarSrc={creditCard.thumbnail ?? creditCard.image}
                   avatarFallback={creditCard.card_name.charAt(0).toUpperCase()}
                   isSelected={selectedItemId === creditCard.id}
                   onClick={() => onSelectItem?.(creditCard.id)}
@@ -205,7 +205,7 @@ export function CategoryAccordion({
                   title={loginkey.site_name}
                   color={loginkey.color}
                   colorPalette={LOGINKEY_COLORS_HEX}
-                  avatarSrc={loginkey.image}
+                  avatarSrc={loginkey.thumbnail ?? loginkey.image}
                   avatarFallback={loginkey.site_name.charAt(0).toUpperCase()}
                   isSelected={selectedItemId === loginkey.id}
                   onClick={() => onSelectItem?.(loginkey.id)}
@@ -226,7 +226,7 @@ export function CategoryAccordion({
                   title={note.note_name}
                   color={note.color}
                   colorPalette={NOTE_COLORS_HEX}
-                  avatarSrc={note.image}
+                  avatarSrc={note.thumbnail ?? note.image}
                   avatarFallback={note.note_name.charAt(0).toUpperCase()}
                   isSelected={selectedItemId === note.id}
                   onClick={() => onSelectItem?.(note.id)}
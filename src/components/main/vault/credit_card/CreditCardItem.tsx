@@ -8,6 +8,7 @@ import VisibilityOffIcon from '@mui/icons-material/VisibilityOff';
 import LockIcon from '@mui/icons-material/Lock';
 import LockOpenIcon from '@mui/icons-material/LockOpen';
 import { CreditCard, CREDITCARD_COLORS_HEX } from '../../../../types/credit_card';
+import { useItemUsage } from '../../../../hooks/items/useItemUsage';
 
 interface CreditCardItemProps {
   creditCard: CreditCard;
@@ -17,6 +18,7 @@ interface CreditCardItemProps {
 }
 
 export function CreditCardItem({ creditCard, isLockedByDefault = false, newlyCreatedId, onUpdate }: CreditCardItemProps) {
+  const { markItemUsed } = useItemUsage();
   const [holderName, setHolderName] = useState(creditCard.holder_name);
   const [cardNumber, setCardNumber] = useState(creditCard.card_number);
   const [expiry, setExpiry] = useState(creditCard.expiry);
@@ -46,9 +48,17 @@ export function CreditCardItem({ creditCard, isLockedByDefault = false, newlyCre
     setTimeout(() => setSaved(false), 3000);
   };
 
+  const reveal = (shown: boolean, setShown: (value: boolean) => void) => {
+    if (!shown) {
+      markItemUsed('credit_card', creditCard.id);
+    }
+    setShown(!shown);
+  };
+
   const handleCopyCardNumber = async () => {
     if (cardNumber) {
       await navigator.clipboard.writeText(cardNumber);
+      markItemUsed('credit_card', creditCard.id);
       setCopiedCardNumber(true);
       setTimeout(() => setCopiedCardNumber(false), 3000);
     }
@@ -57,6 +67,7 @@ export function CreditCardItem({ creditCard, isLockedByDefault = false, newlyCre
   const handleCopyCvv = async () => {
     if (cvv) {
       await navigator.clipboard.writeText(cvv);
+      markItemUsed('credit_card', creditCard.id);
       setCopiedCvv(true);
       setTimeout(() => setCopiedCvv(false), 3000);
     }
@@ -65,6 +76,7 @@ export function CreditCardItem({ creditCard, isLockedByDefault = false, newlyCre
   const handleCopyHolderName = async () => {
     if (holderName) {
       await navigator.clipboard.writeText(holderName);
+      markItemUsed('credit_card', creditCard.id);
       setCopiedHolderName(true);
       setTimeout(() => setCopiedHolderName(false), 3000);
     }
@@ -148,7 +160,7 @@ export function CreditCardItem({ creditCard, isLockedByDefault = false, newlyCre
               <InputAdornment position="end" sx={{ ml: 1, mr: -1 }}>
                 <IconButton
                   size="small"
-                  onClick={() => setShowHolderName(!showHolderName)}
+                  onClick={() => reveal(showHolderName, setShowHolderName)}
                   edge="end"
                   sx={{ opacity: 0.6 }}
                 >
@@ -189,7 +201,7 @@ export function CreditCardItem({ creditCard, isLockedByDefault = false, newlyCre
               <InputAdornment position="end" sx={{ ml: 1, mr: -1 }}>
                 <IconButton
                   size="small"
-                  onClick={() => setShowCardNumber(!showCardNumber)}
+                  onClick={() => reveal(showCardNumber, setShowCardNumber)}
                   edge="end"
                   sx={{ opacity: 0.6 }}
                 >
@@ -246,7 +258,7 @@ export function CreditCardItem({ creditCard, isLockedByDefault = false, newlyCre
               <InputAdornment position="end" sx={{ ml: 1, mr: -1 }}>
                 <IconButton
                   size="small"
-                  onClick={() => setShowCvv(!showCvv)}
+                  onClick={() => reveal(showCvv, setShowCvv)}
                   edge="end"
                   sx={{ opacity: 0.6 }}
                 >
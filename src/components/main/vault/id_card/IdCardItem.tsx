@@ -8,6 +8,7 @@ import VisibilityOffIcon from '@mui/icons-material/VisibilityOff';
 import LockIcon from '@mui/icons-material/Lock';
 import LockOpenIcon from '@mui/icons-material/LockOpen';
 import { IdCard, IDCARD_COLORS_HEX } from '../../../../types/id_card';
+import { useItemUsage } from '../../../../hooks/items/useItemUsage';
 
 interface IdCardItemProps {
   idCard: IdCard;
@@ -17,6 +18,7 @@ interface IdCardItemProps {
 }
 
 export function IdCardItem({ idCard, isLockedByDefault = false, newlyCreatedId, onUpdate }: IdCardItemProps) {
+  const { markItemUsed } = useItemUsage();
   const [idType, setIdType] = useState(idCard.id_type);
   const [fullName, setFullName] = useState(idCard.full_name);
   const [idNumber, setIdNumber] = useState(idCard.id_number);
@@ -42,9 +44,17 @@ export function IdCardItem({ idCard, isLockedByDefault = false, newlyCreatedId,
     setTimeout(() => setSaved(false), 3000);
   };
 
+  const reveal = (shown: boolean, setShown: (value: boolean) => void) => {
+    if (!shown) {
+      markItemUsed('id_card', idCard.id);
+    }
+    setShown(!shown);
+  };
+
   const handleCopyNumber = async () => {
     if (idNumber) {
       await navigator.clipboard.writeText(idNumber);
+      markItemUsed('id_card', idCard.id);
       setCopiedIdNumber(true);
       setTimeout(() => setCopiedIdNumber(false), 3000);
     }
@@ -53,6 +63,7 @@ export function IdCardItem({ idCard, isLockedByDefault = false, newlyCreatedId,
   const handleCopyFullName = async () => {
     if (fullName) {
       await navigator.clipboard.writeText(fullName);
+      markItemUsed('id_card', idCard.id);
       setCopiedFullName(true);
       setTimeout(() => setCopiedFullName(false), 3000);
     }
@@ -152,7 +163,7 @@ export function IdCardItem({ idCard, isLockedByDefault = false, newlyCreatedId,
               <InputAdornment position="end" sx={{ ml: 1, mr: -1 }}>
                 <IconButton
                   size="small"
-                  onClick={() => setShowFullName(!showFullName)}
+                  onClick={() => reveal(showFullName, setShowFullName)}
                   edge="end"
                   sx={{ opacity: 0.6 }}
                 >
@@ -193,7 +204,7 @@ export function IdCardItem({ idCard, isLockedByDefault = false, newlyCreatedId,
               <InputAdornment position="end" sx={{ ml: 1, mr: -1 }}>
                 <IconButton
                   size="small"
-                  onClick={() => setShowIdNumber(!showIdNumber)}
+                  onClick={() => reveal(showIdNumber, setShowIdNumber)}
                   edge="end"
                   sx={{ opacity: 0.6 }}
                 >
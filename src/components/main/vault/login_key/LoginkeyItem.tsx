@@ -10,6 +10,7 @@ import LockOpenIcon from '@mui/icons-material/LockOpen';
 import { openUrl } from '@tauri-apps/plugin-opener';
 import { LoginKey } from '../../../../types/loginkey';
 import { LOGINKEY_COLORS_HEX } from '../../../../types/loginkey';
+import { useItemUsage } from '../../../../hooks/items/useItemUsage';
 
 interface LoginkeyItemProps {
   loginkey: LoginKey;
@@ -19,6 +20,7 @@ interface LoginkeyItemProps {
 }
 
 export function LoginkeyItem({ loginkey, isLockedByDefault = false, newlyCreatedId, onUpdate }: LoginkeyItemProps) {
+  const { markItemUsed } = useItemUsage();
   const [url, setUrl] = useState(loginkey.url || '');
   const [username, setUsername] = useState(loginkey.username);
   const [password, setPassword] = useState(loginkey.password);
@@ -46,9 +48,17 @@ export function LoginkeyItem({ loginkey, isLockedByDefault = false, newlyCreated
     setTimeout(() => setSaved(false), 3000);
   };
 
+  const reveal = (shown: boolean, setShown: (value: boolean) => void) => {
+    if (!shown) {
+      markItemUsed('login_key', loginkey.id);
+    }
+    setShown(!shown);
+  };
+
   const handleCopyUsername = async () => {
     if (username) {
       await navigator.clipboard.writeText(username);
+      markItemUsed('login_key', loginkey.id);
       setCopiedUsername(true);
       setTimeout(() => setCopiedUsername(false), 3000);
     }
@@ -57,6 +67,7 @@ export function LoginkeyItem({ loginkey, isLockedByDefault = false, newlyCreated
   const handleCopyPassword = async () => {
     if (password) {
       await navigator.clipboard.writeText(password);
+      markItemUsed('login_key', loginkey.id);
       setCopiedPassword(true);
       setTimeout(() => setCopiedPassword(false), 3000);
     }
@@ -193,7 +204,7 @@ export function LoginkeyItem({ loginkey, isLockedByDefault = false, newlyCreated
               <InputAdornment position="end" sx={{ ml: 1, mr: -1 }}>
                 <IconButton
                   size="small"
-                  onClick={() => setShowUsername(!showUsername)}
+                  onClick={() => reveal(showUsername, setShowUsername)}
                   edge="end"
                   sx={{ opacity: 0.6 }}
                 >
@@ -233,7 +244,7 @@ export function LoginkeyItem({ loginkey, isLockedByDefault = false, newlyCreated
               <InputAdornment position="end" sx={{ ml: 1, mr: -1 }}>
                 <IconButton
                   size="small"
-                  onClick={() => setShowPassword(!showPassword)}
+                  onClick={() => reveal(showPassword, setShowPassword)}
                   edge="end"
                   sx={{ opacity: 0.6 }}
                 >
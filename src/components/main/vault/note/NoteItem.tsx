@@ -8,6 +8,7 @@ import CheckIcon from '@mui/icons-material/Check';
 import LockIcon from '@mui/icons-material/Lock';
 import LockOpenIcon from '@mui/icons-material/LockOpen';
 import { Note, NOTE_COLORS_HEX } from '../../../../types/note';
+import { useItemUsage } from '../../../../hooks/items/useItemUsage';
 
 interface NoteItemProps {
   note: Note;
@@ -17,6 +18,7 @@ interface NoteItemProps {
 }
 
 export function NoteItem({ note, isLockedByDefault = false, newlyCreatedId, onUpdate }: NoteItemProps) {
+  const { markItemUsed } = useItemUsage();
   const [content, setContent] = useState(note.content);
   const [pasted, setPasted] = useState(false);
   const [cut, setCut] = useState(false);
@@ -50,6 +52,7 @@ export function NoteItem({ note, isLockedByDefault = false, newlyCreatedId, onUp
   const handleCut = async () => {
     if (content) {
       await navigator.clipboard.writeText(content);
+      markItemUsed('note', note.id);
       setContent('');
       setCut(true);
       onUpdate(note.id, note.note_name, '', note.color);
@@ -60,6 +63,7 @@ export function NoteItem({ note, isLockedByDefault = false, newlyCreatedId, onUp
   const handleCopy = async () => {
     if (content) {
       await navigator.clipboard.writeText(content);
+      markItemUsed('note', note.id);
       setCopied(true);
       setTimeout(() => setCopied(false), 3000);
     }
@@ -1,26 +1,41 @@
-import { createContext, useContext, ReactNode } from 'react';
+import { createContext, useContext, useEffect, useState, ReactNode } from 'react';
+import { listen } from '@tauri-apps/api/event';
 import { useBackend } from '../../hooks/core/useBackend';
+import { Expiration } from '../../types/expiration';
 
 interface SessionContextType {
-  initSession: (userId: number, masterKey: string) => Promise<void>;
+  initSession: (userId: number, masterKey: string, twoFactorCode?: string) => Promise<void>;
   clearSession: (userId: number) => Promise<void>;
+  expirations: Expiration[];
 }
 
 const SessionContext = createContext<SessionContextType | undefined>(undefined);
 
 export function SessionProvider({ children }: { children: ReactNode }) {
   const { invoke } = useBackend();
+  const [expirations, setExpirations] = useState<Expiration[]>([]);
 
-  const initSession = async (userId: number, masterKey: string) => {
-    await invoke('init_session', { userId, masterKey });
+  // Sent by init_session when something expires soon.
+  useEffect(() => {
+    const unlisten = listen<Expiration[]>('upcoming-expirations', (event) => {
+      setExpirations(event.payload);
+    });
+    return () => {
+      unlisten.then((stop) => stop());
+    };
+  }, []);
+
+  const initSession = async (userId: number, masterKey: string, twoFactorCode?: string) => {
+    await invoke('init_session', { userId, masterKey, twoFactorCode });
   };
 
   const clearSession = async (userId: number) => {
     await invoke('logout', { userId });
+    setExpirations([]);
   };
 
   return (
-    <SessionContext.Provider value={{ initSession, clearSession }}>
+    <SessionContext.Provider value={{ initSession, clearSession, expirations }}>
       {children}
     </SessionContext.Provider>
   );
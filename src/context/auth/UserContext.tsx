@@ -7,9 +7,13 @@ interface UserContextType {
   user: User | null;
   isAuthenticated: boolean;
   isLoadingContent: boolean;
-  login: (username: string, password: string, masterKey?: string) => Promise<void>;
-  register: (username: string, password: string, masterKey: string) => Promise<void>;
+  login: (username: string, password: string, masterKey?: string, twoFactorCode?: string, secretKey?: string) => Promise<void>;
+  register: (username: string, password: string, masterKey: string, createRecoveryKey?: boolean) => Promise<string | undefined>;
   recoverPassword: (username: string, masterKey: string, newPassword: string) => Promise<void>;
+  recoverWithRecoveryKey: (username: string, recoveryKey: string, newMasterKey: string, newPassword: string) => Promise<string>;
+  createRecoveryKey: (masterKey: string) => Promise<string | undefined>;
+  splitRecoveryKey: (masterKey: string, threshold: number, count: number) => Promise<string[]>;
+  recoverWithShares: (username: string, shares: string[], newMasterKey: string, newPassword: string) => Promise<string[]>;
   changePassword: (masterKey: string, newPassword: string) => Promise<void>;
   deleteAccount: (masterKey: string) => Promise<void>;
   logout: () => void;
@@ -24,7 +28,7 @@ export function UserProvider({ children }: { children: ReactNode }) {
   const [user, setUser] = useState<User | null>(null);
   const [isLoadingContent, setIsLoadingContent] = useState(false);
   const { invoke } = useBackend();
-  const { clearSession } = useSession();
+  const { initSession, clearSession } = useSession();
 
   useEffect(() => {
     const masterKey = localStorage.getItem('masterKey');
@@ -33,9 +37,18 @@ export function UserProvider({ children }: { children: ReactNode }) {
     }
   }, []);
 
-  const login = async (username: string, password: string, masterKey?: string) => {
+  const login = async (username: string, password: string, masterKey?: string, twoFactorCode?: string, secretKey?: string) => {
     const userWithoutAvatar = await invoke<User>('login', { username, password, masterKey: masterKey || '' });
-    
+
+    // The second factor and the secret key are checked when the session is
+    // unlocked, so unlock here where a missing one can still be reported.
+    if ((userWithoutAvatar.two_factor_enabled || userWithoutAvatar.secret_key_enabled) && masterKey) {
+      if (secretKey) {
+        await invoke('import_secret_key', { userId: userWithoutAvatar.id, secretKey });
+      }
+      await initSession(userWithoutAvatar.id, masterKey, twoFactorCode);
+    }
+
     setUser(userWithoutAvatar);
     
     if (masterKey) {
@@ -44,17 +57,41 @@ export function UserProvider({ children }: { children: ReactNode }) {
     }
   };
 
-  const register = async (username: string, password: string, masterKey: string) => {
-    const user = await invoke<User>('register', { username, password, masterKey });
+  // Returns the recovery key, when asked for, to show once.
+  const register = async (username: string, password: string, masterKey: string, createRecoveryKey?: boolean) => {
+    const { recovery_key, ...user } = await invoke<User>('register', { username, password, masterKey, createRecoveryKey });
     setUser(user);
     localStorage.setItem('masterKey', masterKey);
     setIsLoadingContent(true);
+    return recovery_key;
   };
 
   const recoverPassword = async (username: string, masterKey: string, newPassword: string) => {
     await invoke('recover_password', { username, masterKey, newPassword });
   };
 
+  // Returns the recovery key replacing the one used.
+  const recoverWithRecoveryKey = async (username: string, recoveryKey: string, newMasterKey: string, newPassword: string) => {
+    return invoke<string>('recover_with_recovery_key', { username, recoveryKey, newMasterKey, newPassword });
+  };
+
+  const createRecoveryKey = async (masterKey: string) => {
+    if (user) {
+      return invoke<string>('create_recovery_key', { userId: user.id, masterKey });
+    }
+  };
+
+  // Each share goes to a different person; `threshold` of them recover the account.
+  const splitRecoveryKey = async (masterKey: string, threshold: number, count: number) => {
+    if (!user) return [];
+    return invoke<string[]>('split_recovery_key', { userId: user.id, masterKey, threshold, count });
+  };
+
+  // Returns new shares replacing the ones used.
+  const recoverWithShares = async (username: string, shares: string[], newMasterKey: string, newPassword: string) => {
+    return invoke<string[]>('recover_with_shares', { username, shares, newMasterKey, newPassword });
+  };
+
   const changePassword = async (masterKey: string, newPassword: string) => {
     if (user) {
       await invoke('change_password', { userId: user.id, masterKey, newPassword });
@@ -109,7 +146,7 @@ export function UserProvider({ children }: { children: ReactNode }) {
   };
 
   return (
-    <UserContext.Provider value={{ user, isAuthenticated: !!user, isLoadingContent, login, register, recoverPassword, changePassword, deleteAccount, logout, updateUser, setIsLoadingContent, setUser }}>
+    <UserContext.Provider value={{ user, isAuthenticated: !!user, isLoadingContent, login, register, recoverPassword, recoverWithRecoveryKey, createRecoveryKey, splitRecoveryKey, recoverWithShares, changePassword, deleteAccount, logout, updateUser, setIsLoadingContent, setUser }}>
       {children}
     </UserContext.Provider>
   );
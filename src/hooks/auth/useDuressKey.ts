@@ -0,0 +1,26 @@
+import { useUser } from '../../context/AuthContext';
+import { useBackend } from '../core/useBackend';
+
+export function useDuressKey() {
+  const { user } = useUser();
+  const { invoke } = useBackend();
+
+  // Logging in with the duress master key opens a separate decoy profile.
+  const setDuressMasterKey = async (masterKey: string, duressMasterKey: string, wipeDataKey: boolean) => {
+    if (!user) return;
+    await invoke('set_duress_master_key', { userId: user.id, masterKey, duressMasterKey, wipeDataKey });
+  };
+
+  // Deletes the decoy profile with everything in it.
+  const removeDuressMasterKey = async (masterKey: string) => {
+    if (!user) return;
+    await invoke('remove_duress_master_key', { userId: user.id, masterKey });
+  };
+
+  const isDuressMasterKeyEnabled = async () => {
+    if (!user) return false;
+    return invoke<boolean>('duress_master_key_enabled', { userId: user.id });
+  };
+
+  return { setDuressMasterKey, removeDuressMasterKey, isDuressMasterKeyEnabled };
+}
@@ -0,0 +1,36 @@
+import { useUser } from '../../context/AuthContext';
+import { useBackend } from '../core/useBackend';
+
+export function useQuickUnlock() {
+  const { user } = useUser();
+  const { invoke } = useBackend();
+
+  // Only kept in the backend's memory, so it has to be set up again after a restart.
+  const enableQuickUnlock = async (pin: string) => {
+    if (!user) return;
+    await invoke('enable_quick_unlock', { userId: user.id, pin });
+  };
+
+  const disableQuickUnlock = async () => {
+    if (!user) return;
+    await invoke('disable_quick_unlock', { userId: user.id });
+  };
+
+  const isQuickUnlockAvailable = async () => {
+    if (!user) return false;
+    return invoke<boolean>('quick_unlock_available', { userId: user.id });
+  };
+
+  // Locks the backend session without logging out, so the PIN can unlock it.
+  const lockSession = async () => {
+    if (!user) return;
+    await invoke('lock_session', { userId: user.id });
+  };
+
+  const quickUnlock = async (pin: string) => {
+    if (!user) return;
+    await invoke('quick_unlock', { userId: user.id, pin });
+  };
+
+  return { enableQuickUnlock, disableQuickUnlock, isQuickUnlockAvailable, lockSession, quickUnlock };
+}
@@ -0,0 +1,32 @@
+import { useUser } from '../../context/AuthContext';
+import { useBackend } from '../core/useBackend';
+
+export function useSecretKey() {
+  const { user, setUser } = useUser();
+  const { invoke } = useBackend();
+
+  // Returns the new secret key; other devices need it imported to unlock.
+  const createSecretKey = async (masterKey: string) => {
+    if (!user) return;
+    const secretKey = await invoke<string>('create_secret_key', { userId: user.id, masterKey });
+    setUser({ ...user, secret_key_enabled: true });
+    return secretKey;
+  };
+
+  const rotateSecretKey = async (masterKey: string) => {
+    if (!user) return;
+    return invoke<string>('rotate_secret_key', { userId: user.id, masterKey });
+  };
+
+  const exportSecretKey = async (masterKey: string) => {
+    if (!user) return;
+    return invoke<string>('export_secret_key', { userId: user.id, masterKey });
+  };
+
+  const importSecretKey = async (secretKey: string) => {
+    if (!user) return;
+    await invoke('import_secret_key', { userId: user.id, secretKey });
+  };
+
+  return { createSecretKey, rotateSecretKey, exportSecretKey, importSecretKey };
+}
@@ -0,0 +1,35 @@
+import { useUser } from '../../context/AuthContext';
+import { useBackend } from '../core/useBackend';
+import { TwoFactorEnrollment } from '../../types/user';
+
+export function useTwoFactor() {
+  const { user, setUser } = useUser();
+  const { invoke } = useBackend();
+
+  // Starts enrollment; the returned secret is only pending until confirmed.
+  const beginEnrollment = async (masterKey: string): Promise<TwoFactorEnrollment | null> => {
+    if (!user) return null;
+    return invoke<TwoFactorEnrollment>('begin_two_factor_enrollment', { userId: user.id, masterKey });
+  };
+
+  // Returns the backup codes, which cannot be shown again.
+  const confirmEnrollment = async (code: string): Promise<string[]> => {
+    if (!user) return [];
+    const backupCodes = await invoke<string[]>('confirm_two_factor', { userId: user.id, code });
+    setUser({ ...user, two_factor_enabled: true });
+    return backupCodes;
+  };
+
+  const regenerateBackupCodes = async (code: string): Promise<string[]> => {
+    if (!user) return [];
+    return invoke<string[]>('regenerate_backup_codes', { userId: user.id, code });
+  };
+
+  const disable = async (code: string) => {
+    if (!user) return;
+    await invoke('disable_two_factor', { userId: user.id, code });
+    setUser({ ...user, two_factor_enabled: false });
+  };
+
+  return { beginEnrollment, confirmEnrollment, regenerateBackupCodes, disable };
+}
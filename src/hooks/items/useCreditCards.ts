@@ -49,11 +49,13 @@ export function useCreditCards(): UseCreditCardsReturn {
     if (!user) return undefined;
     const newCreditCard = await invoke<CreditCard>('create_credit_card', {
       vaultId,
-      cardName,
-      holderName,
-      cardNumber,
-      expiry,
-      cvv,
+      creditCard: {
+        card_name: cardName,
+        holder_name: holderName,
+        card_number: cardNumber,
+        expiry,
+        cvv,
+      },
       color,
       userId: user.id,
     });
@@ -74,13 +76,16 @@ export function useCreditCards(): UseCreditCardsReturn {
     const imageBytes = parseImageToBytes(image);
     await invoke('update_credit_card', {
       cardId: creditCardId,
-      cardName,
-      holderName,
-      cardNumber,
-      expiry,
-      cvv,
+      creditCard: {
+        card_name: cardName,
+        holder_name: holderName,
+        card_number: cardNumber,
+        expiry,
+        cvv,
+      },
       color: color || 'blue',
       image: imageBytes,
+      keepImage: !!image && imageBytes === undefined,
       userId: user?.id,
     });
     setCreditCards((prev) =>
@@ -93,16 +98,21 @@ export function useCreditCards(): UseCreditCardsReturn {
   };
 
   const deleteCreditCard = async (creditCardId: string) => {
-    await invoke('delete_credit_card', { cardId: creditCardId });
+    if (!user) return;
+    await invoke('delete_credit_card', { cardId: creditCardId, userId: user.id });
     setCreditCards((prev) => prev.filter((cc) => cc.id !== creditCardId));
   };
 
   const reorderCreditCards = async (reorderedCreditCards: CreditCard[]) => {
     const updatedCreditCards = reorderedCreditCards.map((cc, i) => ({ ...cc, position: i }));
     setCreditCards(updatedCreditCards);
-    for (let i = 0; i < updatedCreditCards.length; i++) {
-      await invoke('update_credit_card_position', { cardId: updatedCreditCards[i].id, newPosition: i });
-    }
+    if (!user || updatedCreditCards.length === 0) return;
+    await invoke('reorder', {
+      kind: 'credit_card',
+      parentId: updatedCreditCards[0].vault_id,
+      orderedIds: updatedCreditCards.map((cc) => cc.id),
+      userId: user.id,
+    });
   };
 
   const clearCreditCards = () => {
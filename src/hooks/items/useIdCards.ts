@@ -7,8 +7,8 @@ import { parseImageToBytes } from '../sensors/usePositionUtils';
 interface UseIdCardsReturn {
   idCards: IdCard[];
   loadIdCards: (vaultId: string) => Promise<void>;
-  createIdCard: (vaultId: string, idName: string, idType: string, fullName: string, idNumber: string, color?: string) => Promise<IdCard | undefined>;
-  updateIdCard: (idCardId: string, idName: string, idType: string, fullName: string, idNumber: string, color?: string, image?: string | null) => Promise<void>;
+  createIdCard: (vaultId: string, idName: string, idType: string, fullName: string, idNumber: string, color?: string, expiryDate?: string | null) => Promise<IdCard | undefined>;
+  updateIdCard: (idCardId: string, idName: string, idType: string, fullName: string, idNumber: string, color?: string, image?: string | null, expiryDate?: string | null) => Promise<void>;
   deleteIdCard: (idCardId: string) => Promise<void>;
   reorderIdCards: (idCards: IdCard[]) => Promise<void>;
   setIdCards: React.Dispatch<React.SetStateAction<IdCard[]>>;
@@ -43,15 +43,19 @@ export function useIdCards(): UseIdCardsReturn {
     idType: string,
     fullName: string,
     idNumber: string,
-    color: string = 'blue'
+    color: string = 'blue',
+    expiryDate: string | null = null
   ) => {
     if (!user) return undefined;
     const newIdCard = await invoke<IdCard>('create_id_card', {
       vaultId,
-      idName,
-      idType,
-      fullName,
-      idNumber,
+      idCard: {
+        id_name: idName,
+        id_type: idType,
+        full_name: fullName,
+        id_number: idNumber,
+        expiry_date: expiryDate,
+      },
       color,
       userId: user.id,
     });
@@ -66,39 +70,53 @@ export function useIdCards(): UseIdCardsReturn {
     fullName: string,
     idNumber: string,
     color?: string,
-    image?: string | null
+    image?: string | null,
+    expiryDate?: string | null
   ) => {
     const imageBytes = parseImageToBytes(image);
+    // Callers that do not edit the expiry date keep the stored one.
+    const finalExpiryDate = expiryDate === undefined
+      ? idCards.find((ic) => ic.id === idCardId)?.expiry_date ?? null
+      : expiryDate;
     await invoke('update_id_card', {
       cardId: idCardId,
-      idName,
-      idType,
-      fullName,
-      idNumber,
+      idCard: {
+        id_name: idName,
+        id_type: idType,
+        full_name: fullName,
+        id_number: idNumber,
+        expiry_date: finalExpiryDate,
+      },
       color: color || 'blue',
       image: imageBytes,
+      keepImage: !!image && imageBytes === undefined,
       userId: user?.id,
     });
     setIdCards((prev) =>
       prev.map((ic) =>
         ic.id === idCardId
-          ? { ...ic, id_name: idName, id_type: idType, full_name: fullName, id_number: idNumber, color: color || ic.color }
+          ? { ...ic, id_name: idName, id_type: idType, full_name: fullName, id_number: idNumber, expiry_date: finalExpiryDate, color: color || ic.color }
           : ic
       )
     );
   };
 
   const deleteIdCard = async (idCardId: string) => {
-    await invoke('delete_id_card', { cardId: idCardId });
+    if (!user) return;
+    await invoke('delete_id_card', { cardId: idCardId, userId: user.id });
     setIdCards((prev) => prev.filter((ic) => ic.id !== idCardId));
   };
 
   const reorderIdCards = async (reorderedIdCards: IdCard[]) => {
     const updatedIdCards = reorderedIdCards.map((ic, i) => ({ ...ic, position: i }));
     setIdCards(updatedIdCards);
-    for (let i = 0; i < updatedIdCards.length; i++) {
-      await invoke('update_id_card_position', { cardId: updatedIdCards[i].id, newPosition: i });
-    }
+    if (!user || updatedIdCards.length === 0) return;
+    await invoke('reorder', {
+      kind: 'id_card',
+      parentId: updatedIdCards[0].vault_id,
+      orderedIds: updatedIdCards.map((ic) => ic.id),
+      userId: user.id,
+    });
   };
 
   const clearIdCards = () => {
@@ -0,0 +1,21 @@
+import { useUser } from '../../context/AuthContext';
+import { useBackend } from '../core/useBackend';
+
+export type ItemKind = 'login_key' | 'credit_card' | 'id_card' | 'note';
+
+export function useItemUsage() {
+  const { user } = useUser();
+  const { invoke } = useBackend();
+
+  // Records a reveal or copy of an item's secrets, for the "recently used" sort.
+  const markItemUsed = async (kind: ItemKind, itemId: string) => {
+    if (!user) return;
+    try {
+      await invoke('mark_item_used', { kind, itemId, userId: user.id });
+    } catch (error) {
+      console.error('Error marking item as used:', error);
+    }
+  };
+
+  return { markItemUsed };
+}
@@ -1,5 +1,5 @@
 import { useState, useEffect, useRef } from 'react';
-import { LoginKey } from '../../types/loginkey';
+import { LoginKey, OtpCode } from '../../types/loginkey';
 import { useUser } from '../../context/AuthContext';
 import { useBackend } from '../core/useBackend';
 import { reorderItems, parseImageToBytes } from '../sensors/usePositionUtils';
@@ -7,9 +7,10 @@ import { reorderItems, parseImageToBytes } from '../sensors/usePositionUtils';
 interface UseLoginKeysReturn {
   loginKeys: LoginKey[];
   loadLoginKeys: (vaultId: string) => Promise<void>;
-  createLoginKey: (vaultId: string, siteName: string, url: string | null, username: string, password: string, details: string | null, color?: string) => Promise<LoginKey | undefined>;
-  updateLoginKey: (loginKeyId: string, siteName: string, url: string | null, username: string, password: string, details: string | null, color?: string, image?: string | null) => Promise<void>;
+  createLoginKey: (vaultId: string, siteName: string, url: string | null, username: string, password: string, details: string | null, color?: string, rotationDue?: string | null, totp?: string | null) => Promise<LoginKey | undefined>;
+  updateLoginKey: (loginKeyId: string, siteName: string, url: string | null, username: string, password: string, details: string | null, color?: string, image?: string | null, rotationDue?: string | null, totp?: string | null) => Promise<void>;
   deleteLoginKey: (loginKeyId: string) => Promise<void>;
+  getTotpCode: (loginKeyId: string) => Promise<OtpCode | undefined>;
   reorderLoginKeys: (loginKeys: LoginKey[]) => Promise<void>;
   setLoginKeys: React.Dispatch<React.SetStateAction<LoginKey[]>>;
   clearLoginKeys: () => void;
@@ -44,16 +45,22 @@ export function useLoginKeys(): UseLoginKeysReturn {
     username: string,
     password: string,
     details: string | null,
-    color: string = 'blue'
+    color: string = 'blue',
+    rotationDue: string | null = null,
+    totp: string | null = null
   ) => {
     if (!user) return undefined;
     const newLoginKey = await invoke<LoginKey>('create_login_key', {
       vaultId,
-      siteName,
-      url,
-      username,
-      password,
-      details,
+      loginKey: {
+        site_name: siteName,
+        url,
+        username,
+        password,
+        details,
+        rotation_due: rotationDue,
+        totp,
+      },
       color,
       userId: user.id,
     });
@@ -69,48 +76,76 @@ export function useLoginKeys(): UseLoginKeysReturn {
     password: string,
     details: string | null,
     color: string = 'blue',
-    image?: string | null
+    image?: string | null,
+    rotationDue?: string | null,
+    totp?: string | null
   ) => {
     if (!user) return;
     const imageBytes = parseImageToBytes(image);
+    // Callers that do not edit the rotation date or OTP secret keep the stored ones.
+    const finalRotationDue = rotationDue === undefined
+      ? loginKeys.find((lk) => lk.id === loginKeyId)?.rotation_due ?? null
+      : rotationDue;
+    const finalTotp = totp === undefined
+      ? loginKeys.find((lk) => lk.id === loginKeyId)?.totp ?? null
+      : totp;
     await invoke('update_login_key', {
       loginKeyId,
-      siteName,
-      url,
-      username,
-      password,
-      details,
+      loginKey: {
+        site_name: siteName,
+        url,
+        username,
+        password,
+        details,
+        rotation_due: finalRotationDue,
+        totp: finalTotp,
+      },
       color,
       image: imageBytes,
+      keepImage: !!image && imageBytes === undefined,
       userId: user.id,
     });
     const finalImage = image === null ? null : image ?? null;
     setLoginKeys((prev) =>
       prev.map((lk) =>
         lk.id === loginKeyId
-          ? { ...lk, site_name: siteName, url, username, password, details, color, image: finalImage, updated_at: Date.now() }
+          ? { ...lk, site_name: siteName, url, username, password, details, rotation_due: finalRotationDue, totp: finalTotp, color, image: finalImage, thumbnail: undefined, updated_at: Date.now() }
           : lk
       )
     );
   };
 
   const deleteLoginKey = async (loginKeyId: string) => {
-    await invoke('delete_login_key', { loginKeyId });
+    if (!user) return;
+    await invoke('delete_login_key', { loginKeyId, userId: user.id });
     const remainingLoginKeys = loginKeys
       .filter((lk) => lk.id !== loginKeyId)
       .sort((a, b) => a.position - b.position);
-    for (let i = 0; i < remainingLoginKeys.length; i++) {
-      await invoke('update_login_key_position', { loginKeyId: remainingLoginKeys[i].id, newPosition: i });
-    }
     setLoginKeys((prev) => prev.filter((lk) => lk.id !== loginKeyId));
+    if (!user || remainingLoginKeys.length === 0) return;
+    await invoke('reorder', {
+      kind: 'login_key',
+      parentId: remainingLoginKeys[0].vault_id,
+      orderedIds: remainingLoginKeys.map((lk) => lk.id),
+      userId: user.id,
+    });
   };
 
   const reorderLoginKeys = async (reorderedLoginKeys: LoginKey[]) => {
     const updatedLoginKeys = reorderItems(reorderedLoginKeys);
     setLoginKeys(updatedLoginKeys);
-    for (let i = 0; i < updatedLoginKeys.length; i++) {
-      await invoke('update_login_key_position', { loginKeyId: updatedLoginKeys[i].id, newPosition: i });
-    }
+    if (!user || updatedLoginKeys.length === 0) return;
+    await invoke('reorder', {
+      kind: 'login_key',
+      parentId: updatedLoginKeys[0].vault_id,
+      orderedIds: updatedLoginKeys.map((lk) => lk.id),
+      userId: user.id,
+    });
+  };
+
+  const getTotpCode = async (loginKeyId: string) => {
+    if (!user) return undefined;
+    return invoke<OtpCode>('get_totp_code', { loginKeyId, userId: user.id });
   };
 
   const clearLoginKeys = () => {
@@ -123,6 +158,7 @@ export function useLoginKeys(): UseLoginKeysReturn {
     createLoginKey,
     updateLoginKey,
     deleteLoginKey,
+    getTotpCode,
     reorderLoginKeys,
     setLoginKeys,
     clearLoginKeys,
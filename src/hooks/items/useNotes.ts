@@ -50,8 +50,7 @@ export function useNotes(): UseNotesReturn {
     if (!user) return undefined;
     const newNote = await invoke<Note>('create_note', {
       vaultId,
-      title,
-      content,
+      note: { note_name: title, content },
       color,
       userId: user.id,
     });
@@ -64,36 +63,45 @@ export function useNotes(): UseNotesReturn {
     const imageBytes = parseImageToBytes(image);
     await invoke('update_note', {
       noteId,
-      title,
-      content,
+      note: { note_name: title, content },
       color,
       image: imageBytes,
+      keepImage: !!image && imageBytes === undefined,
       userId: user.id,
     });
     setNotes((prev) =>
       prev.map((n) =>
-        n.id === noteId ? { ...n, note_name: title, content, color, image: image === null ? undefined : image, updated_at: Date.now() } : n
+        n.id === noteId ? { ...n, note_name: title, content, color, image: image === null ? undefined : image, thumbnail: undefined, updated_at: Date.now() } : n
       )
     );
   };
 
   const deleteNote = async (noteId: string) => {
-    await invoke('delete_note', { noteId });
+    if (!user) return;
+    await invoke('delete_note', { noteId, userId: user.id });
     const remainingNotes = notes
       .filter((n) => n.id !== noteId)
       .sort((a, b) => a.position - b.position);
-    for (let i = 0; i < remainingNotes.length; i++) {
-      await invoke('update_note_position', { noteId: remainingNotes[i].id, newPosition: i });
-    }
     setNotes((prev) => prev.filter((n) => n.id !== noteId));
+    if (!user || remainingNotes.length === 0) return;
+    await invoke('reorder', {
+      kind: 'note',
+      parentId: remainingNotes[0].vault_id,
+      orderedIds: remainingNotes.map((n) => n.id),
+      userId: user.id,
+    });
   };
 
   const reorderNotes = async (reorderedNotes: Note[]) => {
     const updatedNotes = reorderItems(reorderedNotes);
     setNotes(updatedNotes);
-    for (let i = 0; i < updatedNotes.length; i++) {
-      await invoke('update_note_position', { noteId: updatedNotes[i].id, newPosition: i });
-    }
+    if (!user || updatedNotes.length === 0) return;
+    await invoke('reorder', {
+      kind: 'note',
+      parentId: updatedNotes[0].vault_id,
+      orderedIds: updatedNotes.map((n) => n.id),
+      userId: user.id,
+    });
   };
 
   const clearNotes = () => {
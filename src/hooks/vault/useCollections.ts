@@ -62,11 +62,12 @@ export function useCollections(): UseCollectionsReturn {
   };
 
   const deleteCollection = async (collectionId: string) => {
+    if (!user) return;
     const deletedCollection = collections.find((c) => c.id === collectionId);
     const vaultIdsInDeletedCollection = deletedCollection?.vault_ids || [];
 
     for (const vaultId of vaultIdsInDeletedCollection) {
-      await invoke('delete_vault', { vaultId });
+      await invoke('delete_vault', { vaultId, userId: user.id });
     }
 
     await invoke('delete_collection', { collectionId });
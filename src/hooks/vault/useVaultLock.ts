@@ -0,0 +1,35 @@
+import { useUser } from '../../context/AuthContext';
+import { useBackend } from '../core/useBackend';
+
+export function useVaultLock() {
+  const { user } = useUser();
+  const { invoke } = useBackend();
+
+  const setVaultPassphrase = async (vaultId: string, passphrase: string) => {
+    if (!user) return;
+    await invoke('set_vault_passphrase', { vaultId, passphrase, userId: user.id });
+  };
+
+  const removeVaultPassphrase = async (vaultId: string, passphrase: string) => {
+    if (!user) return;
+    await invoke('remove_vault_passphrase', { vaultId, passphrase, userId: user.id });
+  };
+
+  // Resolves to when the vault locks again by itself, in milliseconds since the epoch.
+  const unlockVault = async (vaultId: string, passphrase: string) => {
+    if (!user) return null;
+    return invoke<number>('unlock_vault', { vaultId, passphrase, userId: user.id });
+  };
+
+  const lockVault = async (vaultId: string) => {
+    if (!user) return;
+    await invoke('lock_vault', { vaultId, userId: user.id });
+  };
+
+  const vaultUnlockedUntil = async (vaultId: string) => {
+    if (!user) return null;
+    return invoke<number | null>('vault_unlocked_until', { vaultId, userId: user.id });
+  };
+
+  return { setVaultPassphrase, removeVaultPassphrase, unlockVault, lockVault, vaultUnlockedUntil };
+}
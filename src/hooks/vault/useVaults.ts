@@ -77,18 +77,20 @@ export function useVaults(): UseVaultsReturn {
       name: vault.name,
       color: vault.color,
       image: imageBytes,
+      keepImage: !!image && imageBytes === undefined,
     });
     setVaults((prev) =>
       prev.map((v) =>
         v.id === vault.id
-          ? { ...v, name: vault.name, color: vault.color, image: image === null ? undefined : image }
+          ? { ...v, name: vault.name, color: vault.color, image: image === null ? undefined : image, thumbnail: undefined }
           : v
       )
     );
   };
 
   const deleteVault = async (vaultId: string) => {
-    await invoke('delete_vault', { vaultId });
+    if (!user) return;
+    await invoke('delete_vault', { vaultId, userId: user.id });
     setVaults((prev) => prev.filter((v) => v.id !== vaultId));
     if (activeVault === vaultId) {
       setActiveVault(null);
@@ -102,12 +104,16 @@ export function useVaults(): UseVaultsReturn {
       const assignedVaults = prev.filter(v => collectionVaultIds.includes(v.id));
       return [...assignedVaults, ...updatedVaults];
     });
-    for (let i = 0; i < updatedVaults.length; i++) {
-      await invoke('update_vault_position', { vaultId: updatedVaults[i].id, newPosition: i });
-    }
+    if (!user) return;
+    await invoke('reorder', {
+      kind: 'vault',
+      parentId: null,
+      orderedIds: updatedVaults.map((v) => v.id),
+      userId: user.id,
+    });
   };
 
-  const reorderVaultsInCollection = async (_collectionId: string, newVaultIds: string[]) => {
+  const reorderVaultsInCollection = async (collectionId: string, newVaultIds: string[]) => {
     setVaults(prev =>
       prev.map(v => {
         const newIndex = newVaultIds.indexOf(v.id);
@@ -117,9 +123,13 @@ export function useVaults(): UseVaultsReturn {
         return v;
       })
     );
-    for (let i = 0; i < newVaultIds.length; i++) {
-      await invoke('update_vault_position', { vaultId: newVaultIds[i], newPosition: i });
-    }
+    if (!user) return;
+    await invoke('reorder', {
+      kind: 'vault',
+      parentId: collectionId,
+      orderedIds: newVaultIds,
+      userId: user.id,
+    });
   };
 
   const selectVault = async (vaultId: string) => {
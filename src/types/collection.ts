@@ -4,5 +4,7 @@ export interface Collection {
   name: string;
   vault_ids: string[];
   created_at: number;
+  updated_at?: number;
+  last_used_at?: number | null;
   position: number;
 }
@@ -10,9 +10,12 @@ export interface CreditCard {
   cvv: string;
   color: string;
   image?: string;
+  thumbnail?: string;
   created_at: number;
   updated_at: number;
+  last_used_at?: number | null;
   position: number;
+  favorite?: boolean;
 }
 
 export type CreditCardColor = AppColor;
@@ -0,0 +1,16 @@
+export type ExpiringItemKind = 'login_key' | 'credit_card' | 'id_card';
+
+export interface Expiration {
+  id: string;
+  kind: ExpiringItemKind;
+  vault_id: string;
+  vault_name: string;
+  title: string;
+  subtitle: string | null;
+  color: string;
+  image: string | null;
+  thumbnail: string | null;
+  expires_on: string | null;
+  days_left: number | null;
+  invalid_date: string | null;
+}
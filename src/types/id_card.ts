@@ -7,10 +7,15 @@ export interface IdCard {
   id_type: string;
   full_name: string;
   id_number: string;
+  expiry_date?: string | null;
   color: string;
   image?: string;
+  thumbnail?: string;
   created_at: number;
+  updated_at?: number;
+  last_used_at?: number | null;
   position: number;
+  favorite?: boolean;
 }
 
 export type IdCardColor = AppColor;
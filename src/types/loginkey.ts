@@ -8,11 +8,22 @@ export interface LoginKey {
   username: string;
   password: string;
   details: string | null;
+  rotation_due?: string | null;
+  totp?: string | null;
   color: string;
   image: string | null;
+  thumbnail?: string;
   created_at: number;
   updated_at: number;
+  last_used_at?: number | null;
   position: number;
+  favorite?: boolean;
+}
+
+export interface OtpCode {
+  code: string;
+  remaining_seconds: number | null;
+  period: number | null;
 }
 
 export type LoginKeyColor = AppColor;
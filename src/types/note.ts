@@ -7,8 +7,12 @@ export interface Note {
   content: string;
   color: string;
   image?: string;
+  thumbnail?: string;
   created_at: number;
+  updated_at?: number;
+  last_used_at?: number | null;
   position: number;
+  favorite?: boolean;
 }
 
 export type NoteColor = AppColor;
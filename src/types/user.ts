@@ -2,4 +2,12 @@ export interface User {
   id: number;
   username: string;
   avatar?: string | null;
+  two_factor_enabled?: boolean;
+  secret_key_enabled?: boolean;
+  recovery_key?: string;
+}
+
+export interface TwoFactorEnrollment {
+  secret: string;
+  uri: string;
 }
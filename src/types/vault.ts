@@ -6,8 +6,13 @@ export interface Vault {
   name: string;
   color: string;
   image?: string;
+  thumbnail?: string;
   created_at: number;
+  updated_at?: number;
+  last_used_at?: number | null;
   position: number;
+  full_text_search?: boolean;
+  passphrase_protected?: boolean;
 }
 
 export type VaultColor = AppColor;
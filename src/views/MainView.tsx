@@ -104,9 +104,14 @@ export function MainView() {
             setAvatarLoading(false);
             setIsLoadingContent(false);
           });
-        }).catch(() => {
+        }).catch((error) => {
           setAvatarLoading(false);
           setIsLoadingContent(false);
+          // The app restarted since a two-factor unlock, or the secret key
+          // went missing; sign in again.
+          if (String(error).includes('Two-factor code required') || String(error).includes('secret key')) {
+            logout();
+          }
         });
       } else {
         setIsLoadingContent(false);
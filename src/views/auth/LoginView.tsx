@@ -12,6 +12,7 @@ import Typography from '@mui/material/Typography';
 import KeyIcon from '@mui/icons-material/Key';
 import LockIcon from '@mui/icons-material/Lock';
 import PersonIcon from '@mui/icons-material/Person';
+import PinIcon from '@mui/icons-material/Pin';
 import Visibility from '@mui/icons-material/Visibility';
 import VisibilityOff from '@mui/icons-material/VisibilityOff';
 import { CenteredCard, TopBar } from '../../components/common';
@@ -27,6 +28,11 @@ function LoginView() {
   const [username, setUsername] = useState('');
   const [password, setPassword] = useState('');
   const [masterKey, setMasterKey] = useState('');
+  const [twoFactorCode, setTwoFactorCode] = useState('');
+  // Asked for only once the backend says the account needs it.
+  const [needsTwoFactor, setNeedsTwoFactor] = useState(false);
+  const [secretKey, setSecretKey] = useState('');
+  const [needsSecretKey, setNeedsSecretKey] = useState(false);
 
   const [usernameError, setUsernameError] = useState(false);
   const [usernameNotFoundError, setUsernameNotFoundError] = useState(false);
@@ -34,6 +40,8 @@ function LoginView() {
   const [passwordInvalidError, setPasswordInvalidError] = useState(false);
   const [masterKeyError, setMasterKeyError] = useState(false);
   const [masterKeyInvalidError, setMasterKeyInvalidError] = useState(false);
+  const [twoFactorInvalidError, setTwoFactorInvalidError] = useState(false);
+  const [secretKeyInvalidError, setSecretKeyInvalidError] = useState(false);
 
   const [showPassword, setShowPassword] = useState(false);
   const [showMasterKey, setShowMasterKey] = useState(false);
@@ -44,12 +52,18 @@ function LoginView() {
     setUsername('');
     setPassword('');
     setMasterKey('');
+    setTwoFactorCode('');
+    setNeedsTwoFactor(false);
+    setSecretKey('');
+    setNeedsSecretKey(false);
     setUsernameError(false);
     setUsernameNotFoundError(false);
     setPasswordError(false);
     setPasswordInvalidError(false);
     setMasterKeyError(false);
     setMasterKeyInvalidError(false);
+    setTwoFactorInvalidError(false);
+    setSecretKeyInvalidError(false);
     setError('');
   }, [view]);
 
@@ -85,7 +99,7 @@ function LoginView() {
 
     try {
       setIsLoading(true);
-      await login(username, password, masterKey);
+      await login(username, password, masterKey, needsTwoFactor ? twoFactorCode : undefined, needsSecretKey ? secretKey : undefined);
     } catch (err) {
       const errorMessage = err instanceof Error ? err.message : String(err);
 
@@ -95,6 +109,14 @@ function LoginView() {
         setPasswordInvalidError(true);
       } else if (errorMessage.includes('Invalid master key')) {
         setMasterKeyInvalidError(true);
+      } else if (errorMessage.includes('Two-factor code required')) {
+        setNeedsTwoFactor(true);
+      } else if (errorMessage.includes('Invalid two-factor code')) {
+        setTwoFactorInvalidError(true);
+      } else if (errorMessage.includes('Invalid secret key') || errorMessage.includes('does not belong to the account')) {
+        setSecretKeyInvalidError(true);
+      } else if (errorMessage.includes('secret key')) {
+        setNeedsSecretKey(true);
       } else if (errorMessage.includes('network') || errorMessage.includes('fetch') || errorMessage.includes('connection')) {
         setError(t('login.networkError'));
       } else {
@@ -242,6 +264,62 @@ function LoginView() {
           sx={{ mt: 1 }}
         />
 
+        {needsSecretKey && (
+          <TextField
+            id="secretKey"
+            name="secretKey"
+            type="text"
+            label={t('login.secretKey')}
+            placeholder={t('login.secretKeyPlaceholder')}
+            autoComplete="off"
+            autoFocus
+            fullWidth
+            variant="outlined"
+            value={secretKey}
+            error={secretKeyInvalidError}
+            helperText={secretKeyInvalidError ? t('login.invalidSecretKey') : t('login.secretKeyHelp')}
+            onChange={(e) => {
+              setSecretKey(e.target.value);
+              if (secretKeyInvalidError) setSecretKeyInvalidError(false);
+              if (error) setError('');
+            }}
+            slotProps={{
+              input: {
+                startAdornment: <KeyIcon sx={{ color: 'action.active', mr: 1 }} />,
+              },
+            }}
+            sx={{ mt: 1 }}
+          />
+        )}
+
+        {needsTwoFactor && (
+          <TextField
+            id="twoFactorCode"
+            name="twoFactorCode"
+            type="text"
+            label={t('login.twoFactorCode')}
+            placeholder={t('login.twoFactorCodePlaceholder')}
+            autoComplete="one-time-code"
+            autoFocus
+            fullWidth
+            variant="outlined"
+            value={twoFactorCode}
+            error={twoFactorInvalidError}
+            helperText={twoFactorInvalidError ? t('login.invalidTwoFactorCode') : t('login.twoFactorCodeHelp')}
+            onChange={(e) => {
+              setTwoFactorCode(e.target.value);
+              if (twoFactorInvalidError) setTwoFactorInvalidError(false);
+              if (error) setError('');
+            }}
+            slotProps={{
+              input: {
+                startAdornment: <PinIcon sx={{ color: 'action.active', mr: 1 }} />,
+              },
+            }}
+            sx={{ mt: 1 }}
+          />
+        )}
+
         <Button
           type="submit"
           fullWidth